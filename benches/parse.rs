@@ -0,0 +1,40 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// `sample.phyA` and `sample.phys` are synthetic: structurally valid physics
+// files full of junk values, not real physics from any scenario.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use physics_eater::{parse_physics_from_bytes, NameDbs, NameStyle};
+
+const M2_SAMPLE: &[u8] = include_bytes!("sample.phyA");
+const M1_SAMPLE: &[u8] = include_bytes!("sample.phys");
+
+fn parse(c: &mut Criterion) {
+    let namedbs = NameDbs::new(None, NameStyle::default()).unwrap();
+    c.bench_function("parse M2 physics", |b| {
+        b.iter(|| parse_physics_from_bytes(black_box(M2_SAMPLE), &namedbs))
+    });
+    c.bench_function("parse M1 physics", |b| {
+        b.iter(|| parse_physics_from_bytes(black_box(M1_SAMPLE), &namedbs))
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
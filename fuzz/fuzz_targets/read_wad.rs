@@ -0,0 +1,31 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use physics_eater::{Diagnostics, Wad};
+
+fuzz_target!(|data: &[u8]| {
+    // Both ways of reading a WAD should fail cleanly, never panic, hang or
+    // blow up memory.
+    let diagnostics = Diagnostics::default();
+    let _ = Wad::read_wad(Cursor::new(data), false, &diagnostics);
+    let _ = Wad::read_wad_from_bytes(data, true, &diagnostics);
+});
@@ -0,0 +1,69 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Reading input files out of zip archives, which is how most scenarios are
+//! distributed. Anywhere an input path is taken, `zip:ARCHIVE!ENTRY` names
+//! the entry `ENTRY` inside the zip file `ARCHIVE`, e.g.
+//! `zip:Eternal.zip!Eternal/Physics Model`.
+
+use std::path::Path;
+
+#[cfg(not(feature = "zip"))]
+use anyhow::anyhow;
+#[cfg(feature = "zip")]
+use anyhow::Context;
+
+const ZIP_PATH_PREFIX: &str = "zip:";
+
+/// Splits a `zip:ARCHIVE!ENTRY` path into the archive's path and the entry's
+/// name. Returns None if the path isn't of that form. The split is at the
+/// first `!`, so an entry name may contain one but an archive path may not.
+pub fn split_zip_path(path: &Path) -> Option<(&Path, &str)> {
+    let (archive, entry) = path
+        .to_str()?
+        .strip_prefix(ZIP_PATH_PREFIX)?
+        .split_once('!')?;
+    Some((Path::new(archive), entry))
+}
+
+/// Reads one entry of a zip archive into memory. The readers need to seek,
+/// and a compressed entry can't, so it's read all at once.
+#[cfg(feature = "zip")]
+pub fn read_zip_entry(archive: &Path, entry: &str) -> anyhow::Result<Vec<u8>> {
+    use std::{fs::File, io::BufReader, io::Read};
+    let file = File::open(archive)
+        .with_context(|| format!("unable to open {archive:?}"))?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("unable to read {archive:?} as a zip"))?;
+    let mut file = zip
+        .by_name(entry)
+        .with_context(|| format!("unable to find {entry:?} in {archive:?}"))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).with_context(|| {
+        format!("unable to extract {entry:?} from {archive:?}")
+    })?;
+    Ok(data)
+}
+
+/// Without the `zip` feature, zip paths are recognized but can't be read.
+#[cfg(not(feature = "zip"))]
+pub fn read_zip_entry(archive: &Path, entry: &str) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow!(
+        "unable to read {entry:?} from {archive:?}: this build doesn't have \
+         the zip feature"
+    ))
+}
@@ -0,0 +1,156 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The reverse of `--explode`: putting a directory of per-definition JSON
+//! files back together into the JSON for a whole physics file.
+
+use super::*;
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context};
+use serde_json::{Map, Value};
+
+/// Reads every JSON file in a directory, and puts them in order by index.
+/// The index comes from the file's "index" key (which is removed), or, if
+/// there isn't one, its file name. Missing indices are filled with null, with
+/// a warning. Returns None if the directory doesn't exist.
+fn assemble_table(
+    dir: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Option<Vec<Value>>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut definitions = BTreeMap::new();
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("unable to read {dir:?}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        let file = File::open(&path)
+            .with_context(|| format!("unable to open {path:?}"))?;
+        let mut definition: Value =
+            serde_json::from_reader(std::io::BufReader::new(file))
+                .with_context(|| format!("unable to parse {path:?}"))?;
+        let Value::Object(map) = &mut definition else { return Err(anyhow!("{path:?} doesn't contain a JSON object")) };
+        let index = match map.shift_remove("index") {
+            Some(index) => {
+                index.as_u64().map(|x| x as usize).ok_or_else(|| {
+                    anyhow!("{path:?}: \"index\" isn't a valid index")
+                })?
+            }
+            None => path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{path:?} has no \"index\" key, and its name isn't \
+                         a number"
+                    )
+                })?,
+        };
+        if definitions.insert(index, definition).is_some() {
+            return Err(anyhow!(
+                "{dir:?} has more than one definition with index {index}"
+            ));
+        }
+    }
+    let count = definitions.keys().next_back().map_or(0, |x| x + 1);
+    let mut ret = vec![Value::Null; count];
+    for (index, definition) in definitions {
+        ret[index] = definition;
+    }
+    for (index, definition) in ret.iter().enumerate() {
+        if definition.is_null() {
+            diagnostics.warn(
+                WarningKind::MissingDefinition,
+                format_args!(
+                    "{dir:?} has no definition with index {index}, leaving a \
+                     null"
+                ),
+            );
+        }
+    }
+    Ok(Some(ret))
+}
+
+/// Reads a directory written by `--explode`, and puts the physics back
+/// together as the same JSON that would have been output without it.
+pub fn assemble_physics(
+    dir: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Value> {
+    let mut ret = Map::new();
+    for (subdir, key) in TABLE_FILE_NAMES {
+        if let Some(table) = assemble_table(&dir.join(subdir), diagnostics)? {
+            ret.insert(key.to_string(), Value::Array(table));
+        }
+    }
+    if let Some(models) = assemble_table(&dir.join("physics"), diagnostics)? {
+        let mut models = models.into_iter();
+        let (Some(walking), Some(running)) = (models.next(), models.next())
+        else {
+            return Err(anyhow!(
+                "{:?} needs at least two physics models (walking and \
+                 running)",
+                dir.join("physics")
+            ));
+        };
+        let mut physics = Map::new();
+        physics.insert("walking".to_string(), walking);
+        physics.insert("running".to_string(), running);
+        let extra: Vec<Value> = models.collect();
+        if !extra.is_empty() {
+            physics.insert("extra".to_string(), Value::Array(extra));
+        }
+        ret.insert("physics".to_string(), Value::Object(physics));
+    }
+    if ret.is_empty() {
+        return Err(anyhow!("{dir:?} doesn't contain any exploded tables"));
+    }
+    Ok(Value::Object(ret))
+}
+
+/// Assembles an exploded directory, and writes the JSON to the given file,
+/// or stdout.
+pub fn assemble(
+    dir: &Path,
+    output: Option<&Path>,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let physics = assemble_physics(dir, diagnostics)?;
+    diagnostics.check()?;
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("unable to create {path:?}"))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut out, &physics)?;
+    out.flush()?;
+    Ok(())
+}
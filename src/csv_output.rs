@@ -0,0 +1,152 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! `--format csv`: one spreadsheet-friendly CSV file per table. Each
+//! definition is flattened into a single row. Nested objects become dotted
+//! columns (`shrapnel_damage.base`), fixed arrays of objects become numbered
+//! ones (`triggers.0.ticks_per_round`), and lists of names or numbers are
+//! joined with semicolons.
+
+use super::*;
+
+use std::path::Path;
+
+use anyhow::Context;
+use serde_json::{Map, Value};
+
+/// Renders a scalar, or a name object, as the text of one cell.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(x) => x.clone(),
+        // an `--unnamed object` or `--bit-objects` entry
+        Value::Object(x) => match x.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => x
+                .get("index")
+                .or_else(|| x.get("bit"))
+                .map(cell)
+                .unwrap_or_default(),
+        },
+        Value::Array(x) => x.iter().map(cell).collect::<Vec<_>>().join(";"),
+        x => x.to_string(),
+    }
+}
+
+/// Returns true if this object is a name (with `--unnamed object` or
+/// `--bit-objects`), which goes in one cell, rather than a struct, which
+/// gets a column per field.
+pub fn is_name_object(map: &Map<String, Value>) -> bool {
+    map.contains_key("name")
+        && map
+            .keys()
+            .all(|x| matches!(x.as_str(), "name" | "index" | "bit"))
+}
+
+/// Flattens one value into (column, cell) pairs, appending them to `row`.
+fn flatten(prefix: &str, value: &Value, row: &mut Vec<(String, String)>) {
+    let join = |key: &str| match prefix {
+        "" => key.to_string(),
+        _ => format!("{prefix}.{key}"),
+    };
+    match value {
+        Value::Object(map) if !is_name_object(map) => {
+            for (key, value) in map {
+                flatten(&join(key), value, row);
+            }
+        }
+        Value::Array(list)
+            if list.iter().any(
+                |x| matches!(x, Value::Object(x) if !is_name_object(x)),
+            ) =>
+        {
+            for (index, value) in list.iter().enumerate() {
+                flatten(&join(&index.to_string()), value, row);
+            }
+        }
+        _ => row.push((prefix.to_string(), cell(value))),
+    }
+}
+
+/// Writes rows to a CSV file. The columns are every column of every row, in
+/// the order they're first seen; a row without one of them gets an empty
+/// cell.
+fn write_csv(
+    path: &Path,
+    rows: &[Vec<(String, String)>],
+) -> anyhow::Result<()> {
+    let mut columns: Vec<&str> = vec![];
+    for row in rows {
+        for (column, _) in row {
+            if !columns.contains(&column.as_str()) {
+                columns.push(column);
+            }
+        }
+    }
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("unable to create {path:?}"))?;
+    writer.write_record(&columns)?;
+    for row in rows {
+        writer.write_record(columns.iter().map(|column| {
+            row.iter()
+                .find(|(x, _)| x == column)
+                .map_or("", |(_, cell)| cell.as_str())
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes each table of already-serialized physics to its own CSV file in
+/// the given directory, e.g. "monsters.csv". Each row starts with the
+/// definition's index. The physics models go in "physics.csv", one row per
+/// model.
+pub fn write_csv_tables(physics: &Value, dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("unable to create {dir:?}"))?;
+    for (file_name, key) in TABLE_FILE_NAMES {
+        let Some(Value::Array(records)) = physics.get(key) else { continue };
+        let rows: Vec<_> = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let mut row = vec![("index".to_string(), index.to_string())];
+                flatten("", record, &mut row);
+                row
+            })
+            .collect();
+        write_csv(&dir.join(format!("{file_name}.csv")), &rows)?;
+    }
+    if let Some(Value::Object(models)) = physics.get("physics") {
+        let extra = models.get("extra").and_then(Value::as_array);
+        let models = [
+            ("walking", &models["walking"]),
+            ("running", &models["running"]),
+        ]
+        .into_iter()
+        .chain(extra.into_iter().flatten().map(|x| ("extra", x)));
+        let rows: Vec<_> = models
+            .map(|(model, record)| {
+                let mut row = vec![("model".to_string(), model.to_string())];
+                flatten("", record, &mut row);
+                row
+            })
+            .collect();
+        write_csv(&dir.join("physics.csv"), &rows)?;
+    }
+    Ok(())
+}
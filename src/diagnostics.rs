@@ -0,0 +1,311 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Warnings about things that are odd, but not wrong enough to stop for.
+//!
+//! Everything that can warn takes a `Diagnostics`, and reports to it instead
+//! of printing anything itself. It records each warning with its kind and
+//! where it happened, and can also render them to stderr as they come in,
+//! as text or (with the `json` feature) as JSON lines. Kinds of warning can
+//! be made errors (`--strict` and `--warnings-as-errors`); those are still
+//! recorded where they happen, so that every one of them gets seen, and then
+//! `check` fails. Notes, which are just information (what `--list-names`
+//! and `--report-chunks` have to say, say), go the same way, but are never
+//! errors.
+
+use super::*;
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+/// The kinds of warning there are, for choosing which ones are errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "json", derive(Serialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "kebab-case"))]
+pub enum WarningKind {
+    /// A flags field has bits set that we don't know the meaning of.
+    UnknownFlags,
+    /// A physics file has no chunk for one of the tables.
+    MissingChunk,
+    /// There are stray bytes after the last record of a chunk, (with
+    /// `--lenient`) after the last complete chunk of an M1 file, or (with
+    /// `verify --check-extent`) after the end of a WAD's directory.
+    TrailingBytes,
+    /// A physics chunk has more physics models than walking and running.
+    ExtraPhysicsModels,
+    /// A WAD chunk has a nonzero "offset" field (only with `--lenient`).
+    ChunkOffset,
+    /// An overlay is being converted without its parent.
+    MissingParent,
+    /// A sequence is out of range for its collection (only with `--shapes`).
+    BadSequence,
+    /// A definition refers to an effect or projectile past the end of its
+    /// table (only with `--check-refs`).
+    DanglingReference,
+    /// An MML file has elements that aren't physics.
+    NotPhysicsMml,
+    /// A directory being assembled is missing a definition.
+    MissingDefinition,
+    /// Something is wrong with a patch (or imported MML): a name that
+    /// doesn't resolve, a value that doesn't fit its field... Always an
+    /// error; the patched file isn't written.
+    BadPatch,
+    /// Something was done the way that's going away in the next release.
+    Deprecated,
+    /// An AppleDouble sidecar was given, and its companion file is being
+    /// read instead. Only ever a note.
+    AppleDouble,
+    /// Where the names in one name table came from (only with
+    /// `--list-names`). Only ever a note.
+    NameSource,
+    /// One chunk of a physics file, and whether it was used (only with
+    /// `--report-chunks`). Only ever a note.
+    ChunkReport,
+}
+
+/// Whether a diagnostic is just a note, just a warning, or a warning that
+/// was made an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "snake_case"))]
+pub enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+/// One warning, as recorded by `Diagnostics`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct Diagnostic {
+    pub level: Level,
+    pub code: WarningKind,
+    pub message: String,
+    /// Where the warning is about, outermost first, e.g. `["Physics.phyA",
+    /// "\"MNpx\" monster definition #3"]`. Empty if it's about nothing in
+    /// particular.
+    pub location: Vec<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self.level {
+            Level::Note => write!(fmt, "note: ")?,
+            Level::Warning => write!(fmt, "warning: ")?,
+            Level::Error => write!(fmt, "error: ")?,
+        }
+        for location in self.location.iter() {
+            write!(fmt, "{location}: ")?;
+        }
+        write!(fmt, "{}", self.message)
+    }
+}
+
+/// How `Diagnostics` shows warnings as they're reported, if at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rendering {
+    /// Don't; only record them.
+    #[default]
+    Quiet,
+    /// As a line of text each, on stderr.
+    Text,
+    /// As a line of JSON each, on stderr.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// The part of a `Diagnostics` that all of its copies share.
+#[derive(Debug, Default)]
+struct Sink {
+    rendering: Rendering,
+    promoted: Vec<WarningKind>,
+    recorded: Mutex<Vec<Diagnostic>>,
+    unchecked_errors: AtomicUsize,
+}
+
+/// Where warnings go. Cheap to clone; clones (and the copies made by `at`)
+/// all report to the same place.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    sink: Arc<Sink>,
+    location: Vec<Arc<str>>,
+}
+
+impl Diagnostics {
+    /// Makes a sink that shows warnings as they're reported (see
+    /// `Rendering`), and treats the given kinds of warning as errors.
+    pub fn new(rendering: Rendering, promoted: &[WarningKind]) -> Diagnostics {
+        Diagnostics {
+            sink: Arc::new(Sink {
+                rendering,
+                promoted: promoted.to_vec(),
+                ..Default::default()
+            }),
+            location: vec![],
+        }
+    }
+    /// Returns a copy that reports to the same place, but with another,
+    /// more specific, location added to everything it reports.
+    pub fn at(&self, location: impl Display) -> Diagnostics {
+        let mut ret = self.clone();
+        ret.location.push(location.to_string().into());
+        ret
+    }
+    /// Returns true if the given kind of warning is being treated as an
+    /// error.
+    pub fn is_promoted(&self, kind: WarningKind) -> bool {
+        self.sink.promoted.contains(&kind)
+    }
+    /// Reports a warning.
+    pub fn warn(&self, code: WarningKind, message: impl Display) {
+        let level = if self.is_promoted(code) {
+            Level::Error
+        } else {
+            Level::Warning
+        };
+        self.report(level, code, message)
+    }
+    /// Reports something that's an error whether or not its kind has been
+    /// made one. Like a promoted warning, it makes `check` fail.
+    pub fn error(&self, code: WarningKind, message: impl Display) {
+        self.report(Level::Error, code, message)
+    }
+    /// Reports something that's only for information. It's never an error,
+    /// even if its kind has been made one.
+    pub fn note(&self, code: WarningKind, message: impl Display) {
+        self.report(Level::Note, code, message)
+    }
+    fn report(&self, level: Level, code: WarningKind, message: impl Display) {
+        if level == Level::Error {
+            self.sink.unchecked_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let diagnostic = Diagnostic {
+            level,
+            code,
+            message: message.to_string(),
+            location: self.location.iter().map(|x| x.to_string()).collect(),
+        };
+        match self.sink.rendering {
+            Rendering::Quiet => (),
+            Rendering::Text => eprintln!("{diagnostic}"),
+            #[cfg(feature = "json")]
+            Rendering::Json => eprintln!(
+                "{}",
+                serde_json::to_string(&diagnostic)
+                    .expect("diagnostics are always serializable")
+            ),
+        }
+        self.sink.recorded.lock().unwrap().push(diagnostic);
+    }
+    /// Returns everything that has been reported so far.
+    pub fn recorded(&self) -> Vec<Diagnostic> {
+        self.sink.recorded.lock().unwrap().clone()
+    }
+    /// Fails if any warnings that are being treated as errors have been
+    /// reported since the last check.
+    pub fn check(&self) -> Result<(), PhysicsEaterError> {
+        match self.sink.unchecked_errors.swap(0, Ordering::Relaxed) {
+            0 => Ok(()),
+            count => Err(PhysicsEaterError::WarningsAsErrors { count }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_are_recorded_where_they_happen() {
+        let diagnostics = Diagnostics::default();
+        diagnostics.warn(WarningKind::MissingChunk, "nowhere in particular");
+        let chunk = diagnostics.at("Physics.phyA").at("\"MNpx\" chunk");
+        chunk.warn(WarningKind::TrailingBytes, "2 stray bytes");
+        let recorded = diagnostics.recorded();
+        assert_eq!(
+            recorded,
+            [
+                Diagnostic {
+                    level: Level::Warning,
+                    code: WarningKind::MissingChunk,
+                    message: "nowhere in particular".to_string(),
+                    location: vec![],
+                },
+                Diagnostic {
+                    level: Level::Warning,
+                    code: WarningKind::TrailingBytes,
+                    message: "2 stray bytes".to_string(),
+                    location: vec![
+                        "Physics.phyA".to_string(),
+                        "\"MNpx\" chunk".to_string(),
+                    ],
+                },
+            ]
+        );
+        assert_eq!(
+            recorded[1].to_string(),
+            "warning: Physics.phyA: \"MNpx\" chunk: 2 stray bytes"
+        );
+        // plain warnings never fail the check
+        assert!(diagnostics.check().is_ok());
+    }
+
+    #[test]
+    fn promoted_warnings_fail_the_check() {
+        let diagnostics =
+            Diagnostics::new(Rendering::Quiet, &[WarningKind::UnknownFlags]);
+        diagnostics.warn(WarningKind::UnknownFlags, "bit 15");
+        diagnostics
+            .at("elsewhere")
+            .warn(WarningKind::UnknownFlags, "bit 14");
+        diagnostics.warn(WarningKind::MissingChunk, "no MNpx");
+        let levels: Vec<Level> =
+            diagnostics.recorded().iter().map(|x| x.level).collect();
+        assert_eq!(levels, [Level::Error, Level::Error, Level::Warning]);
+        assert_eq!(
+            diagnostics.check().unwrap_err().to_string(),
+            "2 warnings were treated as errors"
+        );
+        // the count starts again after each check
+        assert!(diagnostics.check().is_ok());
+    }
+
+    #[test]
+    fn errors_and_notes_ignore_promotion() {
+        let diagnostics =
+            Diagnostics::new(Rendering::Quiet, &[WarningKind::ChunkReport]);
+        diagnostics.note(WarningKind::ChunkReport, "MNpx used");
+        assert!(diagnostics.check().is_ok());
+        diagnostics.error(WarningKind::BadPatch, "no such monster");
+        assert_eq!(
+            diagnostics.check().unwrap_err().to_string(),
+            "1 warning was treated as error"
+        );
+        let recorded = diagnostics.recorded();
+        assert_eq!(recorded[0].to_string(), "note: MNpx used");
+        assert_eq!(recorded[1].to_string(), "error: no such monster");
+    }
+}
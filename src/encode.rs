@@ -0,0 +1,556 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Turning the JSON form of a definition back into the bytes of a record.
+//!
+//! The encoders work on `serde_json::Value`s rather than on the definition
+//! structs, so they accept everything the readers can output (names,
+//! indices, `--unnamed object` objects, ticks with seconds...) as well as
+//! hand-written JSON. Encoding isn't a perfect inverse of reading: a 16.16
+//! fixed point value over 256 doesn't survive the trip through `f32`. So
+//! rather than writing out a whole encoded record, `patch_record` only
+//! writes the fields that a patch actually changed, and every other field
+//! keeps its original bytes.
+//!
+//! A bad field doesn't stop the encoding: the writer collects the error and
+//! carries on, so that every problem with a patch can be reported at once.
+
+use super::*;
+
+use std::{ops::Range, path::Path};
+
+use anyhow::{anyhow, Context};
+use serde_json::{Map, Value};
+
+/// Collects the bytes of one record as it's encoded, remembering where each
+/// field went and what was wrong with any fields that couldn't be encoded.
+#[derive(Default)]
+pub struct RecordWriter {
+    pub data: Vec<u8>,
+    fields: Vec<Range<usize>>,
+    pub errors: Vec<anyhow::Error>,
+}
+
+impl RecordWriter {
+    /// Writes a 16-bit field. If it couldn't be encoded, keeps the error and
+    /// leaves the field's original bytes alone.
+    pub fn write16(&mut self, value: anyhow::Result<u16>) {
+        match value {
+            Ok(x) => self.field(&x.to_be_bytes()),
+            Err(x) => {
+                self.errors.push(x);
+                self.skip(2);
+            }
+        }
+    }
+    /// Like `write16`, but 32 bits wide.
+    pub fn write32(&mut self, value: anyhow::Result<u32>) {
+        match value {
+            Ok(x) => self.field(&x.to_be_bytes()),
+            Err(x) => {
+                self.errors.push(x);
+                self.skip(4);
+            }
+        }
+    }
+    /// Leaves room for a field we know nothing about (like the fields of an
+    /// attack that isn't there). Unlike a written field, it never replaces
+    /// the original bytes.
+    pub fn skip(&mut self, length: usize) {
+        self.data.resize(self.data.len() + length, 0);
+    }
+    fn field(&mut self, bytes: &[u8]) {
+        let start = self.data.len();
+        self.data.extend_from_slice(bytes);
+        self.fields.push(start..self.data.len());
+    }
+}
+
+/// The fields of one JSON object being encoded, and where the object is,
+/// for error messages.
+pub struct Fields<'a> {
+    map: &'a Map<String, Value>,
+    path: String,
+}
+
+/// Works out the index of something that was identified by `NameDb::identify`
+/// or `identify_bit`: a bare index, a name to look up, or an object with an
+/// index or bit number in it.
+pub fn resolve_name(value: &Value, db: &NameDb) -> anyhow::Result<u64> {
+    match value {
+        Value::Number(x) => {
+            x.as_u64().ok_or_else(|| anyhow!("{x} isn't a valid index"))
+        }
+        Value::String(x) => Ok(db.resolve(x)? as u64),
+        Value::Object(x) => x
+            .get("index")
+            .or_else(|| x.get("bit"))
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow!("an object needs an \"index\" or \"bit\"")),
+        _ => Err(anyhow!("expected a name or an index, not {value}")),
+    }
+}
+
+/// Whether a value means NONE: null, or -1 (which is what NONE is in the
+/// file, and in MML).
+fn is_none(value: &Value) -> bool {
+    value.is_null() || value.as_i64() == Some(-1)
+}
+
+/// A whole bitfield, as a number.
+fn bitfield(value: &Value) -> anyhow::Result<u32> {
+    value
+        .as_u64()
+        .and_then(|x| u32::try_from(x).ok())
+        .ok_or_else(|| anyhow!("{value} isn't a valid bitfield"))
+}
+
+fn fixed_point(value: f64, scale: f64, min: f64, max: f64) -> Option<i64> {
+    let ret = (value * scale).round();
+    (value.is_finite() && ret >= min && ret <= max).then_some(ret as i64)
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(value: &'a Value, path: &str) -> anyhow::Result<Fields<'a>> {
+        match value {
+            Value::Object(map) => Ok(Fields {
+                map,
+                path: path.to_string(),
+            }),
+            _ => Err(anyhow!("{path}: expected an object, not {value}")),
+        }
+    }
+    /// Where this object is, e.g. "monster_definitions/7".
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    fn get(&self, key: &str) -> anyhow::Result<&'a Value> {
+        self.map
+            .get(key)
+            .ok_or_else(|| anyhow!("{}/{key} is missing", self.path))
+    }
+    /// Runs `f` on the given field, adding the field's path to any error.
+    fn with<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&'a Value) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        f(self.get(key)?).with_context(|| format!("{}/{key}", self.path))
+    }
+    pub fn object(&self, key: &str) -> anyhow::Result<Fields<'a>> {
+        Fields::new(self.get(key)?, &format!("{}/{key}", self.path))
+    }
+    /// Like `object`, but null (or -1) gives None.
+    pub fn optional_object(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<Option<Fields<'a>>> {
+        match self.get(key)? {
+            x if is_none(x) => Ok(None),
+            _ => self.object(key).map(Some),
+        }
+    }
+    /// An array of exactly `N` objects.
+    pub fn objects<const N: usize>(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<[Fields<'a>; N]> {
+        let elements = self.with(key, |x| match x {
+            Value::Array(x) if x.len() == N => Ok(x),
+            _ => Err(anyhow!("expected an array of {N} objects")),
+        })?;
+        let ret: Vec<Fields> = elements
+            .iter()
+            .enumerate()
+            .map(|(index, x)| {
+                Fields::new(x, &format!("{}/{key}/{index}", self.path))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(ret.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+    /// A weapon's two triggers: an array of two objects, or an object with
+    /// "primary" and "secondary" keys, as output with `--named-triggers`.
+    pub fn triggers(&self) -> anyhow::Result<[Fields<'a>; 2]> {
+        match self.get("triggers")? {
+            Value::Object(_) => {
+                let triggers = self.object("triggers")?;
+                let [primary, secondary] = TRIGGER_NAMES;
+                Ok([triggers.object(primary)?, triggers.object(secondary)?])
+            }
+            _ => self.objects::<2>("triggers"),
+        }
+    }
+    /// A plain 16-bit integer, signed or unsigned.
+    pub fn int16(&self, key: &str) -> anyhow::Result<u16> {
+        self.with(key, |x| {
+            x.as_i64()
+                .filter(|x| (-32768..=65535).contains(x))
+                .map(|x| x as u16)
+                .ok_or_else(|| anyhow!("{x} isn't a 16-bit integer"))
+        })
+    }
+    /// A 16-bit value that may be NONE (null or -1).
+    pub fn optional16(&self, key: &str) -> anyhow::Result<u16> {
+        self.with(key, |x| match x {
+            x if is_none(x) => Ok(0xFFFF),
+            x => x
+                .as_u64()
+                .filter(|x| *x < 0x8000)
+                .map(|x| x as u16)
+                .ok_or_else(|| anyhow!("{x} isn't null or 0 through 32767")),
+        })
+    }
+    /// A tick count that may be NONE: a number, or an object with a "ticks"
+    /// key as output with `--tick-rate`. (Its "seconds" are ignored.)
+    pub fn ticks(&self, key: &str) -> anyhow::Result<u16> {
+        self.with(key, |x| {
+            let ticks = match x {
+                x if is_none(x) => return Ok(0xFFFF),
+                Value::Object(x) => x
+                    .get("ticks")
+                    .ok_or_else(|| anyhow!("expected a \"ticks\" key"))?,
+                x => x,
+            };
+            ticks
+                .as_u64()
+                .filter(|x| *x < 0x8000)
+                .map(|x| x as u16)
+                .ok_or_else(|| anyhow!("{ticks} isn't a valid tick count"))
+        })
+    }
+    /// A reference to something in the given name table, that may be NONE.
+    pub fn name16(&self, key: &str, db: &NameDb) -> anyhow::Result<u16> {
+        self.with(key, |x| match x {
+            x if is_none(x) => Ok(0xFFFF),
+            x => resolve_name(x, db)?
+                .try_into()
+                .ok()
+                .filter(|x| *x < 0x8000)
+                .ok_or_else(|| anyhow!("{x} is out of range")),
+        })
+    }
+    /// Like `name16`, but 32 bits wide.
+    pub fn name32(&self, key: &str, db: &NameDb) -> anyhow::Result<u32> {
+        self.with(key, |x| match x {
+            x if is_none(x) => Ok(0xFFFF_FFFF),
+            x => resolve_name(x, db)?
+                .try_into()
+                .map_err(|_| anyhow!("{x} is out of range")),
+        })
+    }
+    /// A list of things from the given name table, as a 32-bit bitfield. The
+    /// bitfield itself can be given instead, as a number, as MML gives it.
+    pub fn bits32(&self, key: &str, db: &NameDb) -> anyhow::Result<u32> {
+        self.with(key, |x| {
+            if x.is_number() {
+                return bitfield(x);
+            }
+            let Value::Array(x) = x else { return Err(anyhow!("expected an array")) };
+            let mut ret = 0u32;
+            for element in x {
+                let bit = resolve_name(element, db)?;
+                if bit >= 32 {
+                    return Err(anyhow!(
+                        "bit {bit} doesn't fit in a 32-bit bitfield"
+                    ));
+                }
+                ret |= 1 << bit;
+            }
+            Ok(ret)
+        })
+    }
+    /// A 16.16 fixed point number.
+    pub fn fx_16_16(&self, key: &str) -> anyhow::Result<u32> {
+        self.with(key, |x| {
+            x.as_f64()
+                .and_then(|x| {
+                    fixed_point(x, 65536.0, i32::MIN.into(), i32::MAX.into())
+                })
+                .map(|x| x as i32 as u32)
+                .ok_or_else(|| anyhow!("{x} doesn't fit in 16.16 fixed point"))
+        })
+    }
+    /// A 6.10 fixed point number: a world distance, speed or acceleration.
+    pub fn fx_6_10(&self, key: &str) -> anyhow::Result<u16> {
+        self.with(key, |x| {
+            x.as_f64()
+                .and_then(|x| {
+                    fixed_point(x, 1024.0, i16::MIN.into(), i16::MAX.into())
+                })
+                .map(|x| x as i16 as u16)
+                .ok_or_else(|| anyhow!("{x} doesn't fit in 6.10 fixed point"))
+        })
+    }
+    /// Like `fx_6_10`, but null (or -1) is NONE. (A negative value would be
+    /// read back as NONE anyway.)
+    pub fn optional_fx_6_10(&self, key: &str) -> anyhow::Result<u16> {
+        match self.get(key)? {
+            x if is_none(x) => Ok(0xFFFF),
+            _ => self.fx_6_10(key),
+        }
+    }
+    /// An angle in degrees, stored in 512ths of a circle.
+    pub fn angle(&self, key: &str) -> anyhow::Result<u16> {
+        self.with(key, |x| {
+            x.as_f64()
+                .and_then(|x| {
+                    fixed_point(
+                        x,
+                        512.0 / 360.0,
+                        i16::MIN.into(),
+                        i16::MAX.into(),
+                    )
+                })
+                .map(|x| x as i16 as u16)
+                .ok_or_else(|| anyhow!("{x} isn't a valid angle"))
+        })
+    }
+    /// A flags object, with the given flag names in bit order starting from
+    /// bit 0, plus any "_reserved_bits". Flags that aren't there are false,
+    /// which is how the Infinity-only flags are left out for Marathon 2. As
+    /// with `bits32`, the flags can be given as a number instead.
+    pub fn flags(&self, key: &str, names: &[&str]) -> anyhow::Result<u32> {
+        if self.get(key)?.is_number() {
+            return self.with(key, bitfield);
+        }
+        let flags = self.object(key)?;
+        let mut ret = 0u32;
+        for (bit, name) in names.iter().enumerate() {
+            match flags.map.get(*name) {
+                None | Some(Value::Bool(false)) => (),
+                Some(Value::Bool(true)) => ret |= 1 << bit,
+                Some(x) => {
+                    return Err(anyhow!(
+                        "{}/{name}: expected true or false, not {x}",
+                        flags.path
+                    ))
+                }
+            }
+        }
+        if flags.map.contains_key("_reserved_bits") {
+            ret |= flags.with("_reserved_bits", bitfield)?;
+        }
+        Ok(ret)
+    }
+    /// Like `flags`, but for a 16-bit flags field.
+    pub fn flags16(&self, key: &str, names: &[&str]) -> anyhow::Result<u16> {
+        let bits = self.flags(key, names)?;
+        u16::try_from(bits).map_err(|_| {
+            PhysicsEaterError::UnknownFlagBits {
+                what: format!("{}/{key}", self.path),
+                bits,
+                width: 16,
+            }
+            .into()
+        })
+    }
+    /// A collection and CLUT, from separate "collection" and "clut" keys,
+    /// packed together the way `Shape::read` unpacks them.
+    pub fn shape(&self, namedbs: &NameDbs) -> anyhow::Result<u16> {
+        let collection = self.with("collection", |x| match x {
+            x if is_none(x) => Ok(None),
+            x => Ok(Some(resolve_name(x, &namedbs.collection_names)?)),
+        })?;
+        let clut = self.with("clut", |x| match x {
+            x if is_none(x) => Ok(None),
+            x => x
+                .as_u64()
+                .map(Some)
+                .ok_or_else(|| anyhow!("{x} isn't a valid CLUT")),
+        })?;
+        let narrow = |x: Option<u64>| x.map(|x| x.min(u16::MAX.into()) as u16);
+        Shape::pack_optional(narrow(collection), narrow(clut))
+            .with_context(|| format!("{}/collection", self.path))
+    }
+}
+
+/// Reads a patch, as given to `apply-patch`, from a JSON file.
+pub fn read_patch(path: &Path) -> anyhow::Result<Value> {
+    let patch = std::fs::read(path)
+        .with_context(|| format!("unable to read {path:?}"))?;
+    serde_json::from_slice(&patch)
+        .with_context(|| format!("unable to parse {path:?}"))
+}
+
+/// Overlays `patch` onto `base`, the way a JSON merge patch (RFC 7386) does,
+/// except that every key in the patch has to already be in the base: a
+/// patch can change fields, not add them. An object patching an array
+/// patches the elements with the given indices (or, for the triggers,
+/// "primary" and "secondary"). Null sets a field to null
+/// (which usually means NONE) instead of removing it.
+pub fn merge_patch(
+    base: &mut Value,
+    patch: &Value,
+    path: &str,
+) -> anyhow::Result<()> {
+    let Value::Object(patch) = patch else {
+        *base = patch.clone();
+        return Ok(());
+    };
+    if !matches!(base, Value::Object(_) | Value::Array(_)) {
+        // e.g. an attack that was null, and now isn't
+        *base = Value::Object(patch.clone());
+        return Ok(());
+    }
+    // triggers can be patched by name, too
+    let is_triggers = path.ends_with("/triggers");
+    for (key, patch) in patch {
+        let path = format!("{path}/{key}");
+        let field = match base {
+            Value::Object(base) => base.get_mut(key),
+            Value::Array(base) => key
+                .parse::<usize>()
+                .ok()
+                .or_else(|| {
+                    TRIGGER_NAMES.iter().position(|x| is_triggers && x == key)
+                })
+                .and_then(|x| base.get_mut(x)),
+            _ => unreachable!(),
+        };
+        let Some(field) = field else { return Err(anyhow!("{path} isn't a field that can be patched")) };
+        match field {
+            Value::Object(_) | Value::Array(_) => {
+                merge_patch(field, patch, &path)?
+            }
+            _ => *field = patch.clone(),
+        }
+    }
+    Ok(())
+}
+
+/// Applies `patch` to one record, whose original bytes are `original` and
+/// whose JSON form (as output by the reader) is `base`. `encode` turns the
+/// JSON form back into bytes. The base and the patched record are both
+/// encoded, and only the fields whose encoding changed are copied into the
+/// result; everything else stays exactly as it was in `original`. On failure,
+/// returns everything that was wrong with the patch.
+pub fn patch_record(
+    original: &[u8],
+    base: &Value,
+    patch: &Value,
+    path: &str,
+    encode: impl Fn(&Fields, &mut RecordWriter) -> anyhow::Result<()>,
+) -> Result<Vec<u8>, Vec<anyhow::Error>> {
+    let mut patched = base.clone();
+    merge_patch(&mut patched, patch, path).map_err(|x| vec![x])?;
+    let encode = |value| {
+        let mut out = RecordWriter::default();
+        if let Err(x) =
+            Fields::new(value, path).and_then(|x| encode(&x, &mut out))
+        {
+            out.errors.push(x);
+        }
+        out
+    };
+    let before = encode(base);
+    let after = encode(&patched);
+    if !after.errors.is_empty() {
+        return Err(after.errors);
+    }
+    if !before.errors.is_empty() {
+        // the record was already unencodable, so we can't tell which of its
+        // fields the patch changed
+        return Err(before.errors);
+    }
+    // a bug in an encoder, not in the patch
+    assert_eq!(before.data.len(), original.len(), "{path}: wrong size");
+    assert_eq!(after.data.len(), original.len(), "{path}: wrong size");
+    let mut ret = original.to_vec();
+    for range in after.fields {
+        if before.fields.contains(&range)
+            && before.data[range.clone()] == after.data[range.clone()]
+        {
+            continue;
+        }
+        ret[range.clone()].copy_from_slice(&after.data[range]);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each name field is looked up in its own name table: a monster's
+    /// friends among the monster classes, and the item it carries among the
+    /// items. A bare index is taken as it is.
+    #[test]
+    fn patch_monster_friend_and_carried_item() {
+        let namedbs = NameDbs::new(
+            Some(Path::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/infinity_names"
+            ))),
+            Default::default(),
+        )
+        .unwrap();
+        let read = |record: &[u8]| {
+            m2::MonsterDefinition::read(
+                record,
+                &namedbs,
+                Game::Infinity,
+                0,
+                &Diagnostics::default(),
+            )
+            .unwrap()
+        };
+        let patch = |record: &[u8], patch: Value| {
+            let base = serde_json::to_value(read(record)).unwrap();
+            patch_record(
+                record,
+                &base,
+                &patch,
+                "monster_definitions/0",
+                |x, out| m2::MonsterDefinition::write(x, &namedbs, out),
+            )
+        };
+        let mut original = vec![0; 156];
+        original[50..52].copy_from_slice(&[0xFF, 0xFF]); // carries nothing
+        let patched = patch(
+            &original,
+            serde_json::json!({
+                "friends": ["Trooper"],
+                "carrying_item_type": 3,
+            }),
+        )
+        .unwrap();
+        // the friends bitfield, and the item, and nothing else
+        assert_eq!(patched[20..24], (1u32 << 6).to_be_bytes());
+        assert_eq!(patched[50..52], 3u16.to_be_bytes());
+        assert_eq!(patched[..20], original[..20]);
+        assert_eq!(patched[24..50], original[24..50]);
+        assert_eq!(patched[52..], original[52..]);
+        let monster = read(&patched);
+        assert_eq!(monster.friends, [Name::Named("Trooper".to_string())]);
+        assert_eq!(
+            monster.carrying_item_type,
+            Some(Name::Named("plasma pistol".to_string()))
+        );
+        // a monster class isn't an item
+        let errors = patch(
+            &original,
+            serde_json::json!({"carrying_item_type": "Trooper"}),
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let error = format!("{:#}", errors[0]);
+        assert!(
+            error.contains("monster_definitions/0/carrying_item_type"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The failures a library user might want to tell apart.
+//!
+//! Functions still return `anyhow::Result`, since most of what can go wrong
+//! (I/O errors, and all the ways a file can be subtly corrupt) isn't worth a
+//! variant of its own. But where one of these errors is the cause, it's in
+//! there, and `downcast_ref::<PhysicsEaterError>()` will find it, even
+//! underneath any context that was added on the way up:
+//!
+//! ```no_run
+//! # use physics_eater::*;
+//! # fn example(input: &[u8]) {
+//! let namedbs = NameDbs::default();
+//! let diagnostics = Diagnostics::default();
+//! match parse_physics(input, &namedbs, Game::M2, false, &diagnostics) {
+//!     Ok(_physics) => (),
+//!     Err(x) => match x.downcast_ref::<PhysicsEaterError>() {
+//!         Some(PhysicsEaterError::NotPhysics { .. }) => (),
+//!         _ => eprintln!("corrupted: {x:#}"),
+//!     },
+//! }
+//! # }
+//! ```
+//!
+//! Unknown flag bits in a file being read are only a warning (see
+//! `WarningKind::UnknownFlags`); `UnknownFlagBits` is for a patch that sets
+//! bits a flags field has no room for. The `Display` and `Error` impls are
+//! written out by hand, rather than derived with thiserror, so that the
+//! library needs nothing but anyhow.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Makes a chunk tag printable.
+fn tag(kind: &[u8; 4]) -> String {
+    format!("{:?}", String::from_utf8_lossy(kind))
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PhysicsEaterError {
+    /// The file is neither a WAD nor bare M1 chunks.
+    NotPhysics { reason: String },
+    /// The WAD is valid, but has no subfiles to find chunks in.
+    NoSubfiles,
+    /// A chunk that was specifically asked for isn't there.
+    ChunkNotFound { kind: [u8; 4] },
+    /// None of the chunks that make up physics are there.
+    NoPhysicsChunks,
+    /// A chunk's length isn't a whole number of records, and what's left
+    /// over isn't just padding.
+    BadRecordSize {
+        kind: [u8; 4],
+        what: String,
+        length: usize,
+        record_size: usize,
+    },
+    /// A record was read without consuming all of its bytes, so our idea of
+    /// the record layout is wrong.
+    RecordNotFullyParsed {
+        kind: [u8; 4],
+        what: String,
+        index: usize,
+        record_size: usize,
+        parsed: usize,
+    },
+    /// A table has more records than the caller allowed.
+    TooManyRecords {
+        kind: [u8; 4],
+        what: String,
+        count: usize,
+        max: usize,
+    },
+    /// A patch sets bits past the end of a flags field (with
+    /// `_reserved_bits`), so they can't be written.
+    UnknownFlagBits { what: String, bits: u32, width: u32 },
+    /// The input ended partway through something.
+    Truncated { expected: usize, available: usize },
+    /// Warnings were reported that `--strict` or `--warnings-as-errors` made
+    /// into errors.
+    WarningsAsErrors { count: usize },
+}
+
+impl Display for PhysicsEaterError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        use PhysicsEaterError::*;
+        match self {
+            NotPhysics { reason } => {
+                write!(fmt, "this isn't a physics file: {reason}")
+            }
+            NoSubfiles => write!(fmt, "this WAD has no subfiles"),
+            ChunkNotFound { kind } => {
+                write!(fmt, "unable to find chunk of type {}", tag(kind))
+            }
+            NoPhysicsChunks => write!(
+                fmt,
+                "none of the requested physics chunks were found, \
+                 corrupted/misdetected physics file"
+            ),
+            BadRecordSize {
+                kind,
+                what,
+                length,
+                record_size,
+            } => write!(
+                fmt,
+                "{} chunk: non-integer number of {what}s ({length} bytes is \
+                 {} {record_size}-byte records with {} bytes left over), \
+                 corrupted physics file, or a Marathon 1 physics file being \
+                 read as Marathon 2 (or vice versa)",
+                tag(kind),
+                length / record_size,
+                length % record_size
+            ),
+            RecordNotFullyParsed {
+                kind,
+                what,
+                index,
+                record_size,
+                parsed,
+            } => write!(
+                fmt,
+                "{} chunk: {what} #{index} is {record_size} bytes, but only \
+                 {parsed} were parsed ({} left over)",
+                tag(kind),
+                record_size - parsed
+            ),
+            TooManyRecords {
+                kind,
+                what,
+                count,
+                max,
+            } => write!(
+                fmt,
+                "{} chunk: {count} {what}s is more than the limit of {max}",
+                tag(kind)
+            ),
+            UnknownFlagBits { what, bits, width } => write!(
+                fmt,
+                "{what}: {bits:#X} has bits set past the end of a {width}-bit \
+                 flags field"
+            ),
+            Truncated {
+                expected,
+                available,
+            } => write!(
+                fmt,
+                "truncated: expected {expected} bytes, but only {available} \
+                 were left"
+            ),
+            WarningsAsErrors { count } => write!(
+                fmt,
+                "{count} warning{} treated as error{}",
+                if *count == 1 { " was" } else { "s were" },
+                if *count == 1 { "" } else { "s" }
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PhysicsEaterError {}
@@ -0,0 +1,85 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The guts of physics-eater: readers (and writers) for Marathon WADs, bare
+//! M1 chunks, and the physics definitions inside them.
+//!
+//! Everything to do with serializing physics (the `Serialize` derives, JSON
+//! and the other output formats, MML, patching, summaries) is behind the
+//! `json` feature, which is on by default. Without it, only the parsing is
+//! left, and none of the serialization crates are pulled in. Likewise, the
+//! command line parsing (the `clap` derives on the option types) is behind
+//! the `cli` feature, and decompressing gzipped input behind `gzip`; the
+//! binary needs `cli`, which turns on both of the others.
+
+use std::path::PathBuf;
+
+#[macro_use]
+pub mod util;
+pub use util::*;
+
+pub mod archive;
+pub use archive::*;
+
+#[cfg(feature = "json")]
+pub mod assemble;
+#[cfg(feature = "json")]
+pub use assemble::*;
+#[cfg(feature = "json")]
+pub mod csv_output;
+#[cfg(feature = "json")]
+pub use csv_output::*;
+pub mod diagnostics;
+pub use diagnostics::*;
+#[cfg(feature = "json")]
+pub mod encode;
+#[cfg(feature = "json")]
+pub use encode::*;
+pub mod error;
+pub use error::*;
+pub mod macfile;
+pub use macfile::*;
+#[cfg(feature = "json")]
+pub mod mml;
+#[cfg(feature = "json")]
+pub use mml::*;
+pub mod namedb;
+pub use namedb::*;
+pub mod physics;
+pub use physics::*;
+#[cfg(feature = "json")]
+pub mod query;
+#[cfg(feature = "json")]
+pub use query::*;
+#[cfg(feature = "json")]
+pub mod references;
+#[cfg(feature = "json")]
+pub use references::*;
+pub mod shapes;
+pub use shapes::*;
+#[cfg(feature = "json")]
+pub mod stats;
+#[cfg(feature = "json")]
+pub use stats::*;
+#[cfg(feature = "json")]
+pub mod summary;
+#[cfg(feature = "json")]
+pub use summary::*;
+pub mod wad;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub use wad::*;
@@ -0,0 +1,332 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Unwrapping of the formats classic Mac files get packed into when they
+//! leave HFS: MacBinary, AppleSingle, and AppleDouble.
+
+use super::*;
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+const MACBINARY_HEADER_SIZE: usize = 128;
+const APPLESINGLE_MAGIC: u32 = 0x00051600;
+const APPLEDOUBLE_MAGIC: u32 = 0x00051607;
+const APPLEFILE_HEADER_SIZE: usize = 26;
+const APPLEFILE_ENTRY_SIZE: usize = 12;
+const DATA_FORK_ENTRY_ID: u32 = 1;
+
+/// The kinds of wrapper we know how to see through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacWrapper {
+    /// A MacBinary II (or III) file: a 128-byte header, then the data fork,
+    /// then the resource fork.
+    MacBinary,
+    /// An AppleSingle file: a header and entry table, with the data fork as
+    /// one of the entries.
+    AppleSingle,
+    /// An AppleDouble header file (the `._` sidecar): like AppleSingle, but
+    /// without the data fork, which is in the companion file.
+    AppleDouble,
+}
+
+/// The CRC-16 (XMODEM variant) that MacBinary II uses for its header.
+fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Works out whether the given data (at least the first 128 bytes of a file)
+/// is wrapped in one of the formats we understand. MacBinary I has no
+/// checksum and can't be told apart from other data reliably, so only
+/// MacBinary II and later are recognized.
+pub fn detect_mac_wrapper(head: &[u8]) -> Option<MacWrapper> {
+    if head.len() >= 4 {
+        match be32(head, 0) {
+            APPLESINGLE_MAGIC => return Some(MacWrapper::AppleSingle),
+            APPLEDOUBLE_MAGIC => return Some(MacWrapper::AppleDouble),
+            _ => (),
+        }
+    }
+    if head.len() < MACBINARY_HEADER_SIZE {
+        return None;
+    }
+    let name_length = head[1];
+    if head[0] != 0
+        || head[74] != 0
+        || head[82] != 0
+        || !(1..=63).contains(&name_length)
+        || be16(head, 124) != crc16_xmodem(&head[..124])
+    {
+        return None;
+    }
+    Some(MacWrapper::MacBinary)
+}
+
+/// Returns the data fork of a MacBinary II file.
+fn macbinary_data_fork(data: &[u8]) -> anyhow::Result<&[u8]> {
+    let data_length = be32(data, 83) as usize;
+    // Version II added an optional secondary header, padded out to a
+    // multiple of 128 bytes, between the main header and the data fork.
+    let secondary_length = (be16(data, 120) as usize).next_multiple_of(128);
+    let start = MACBINARY_HEADER_SIZE + secondary_length;
+    data.get(start..start + data_length).ok_or_else(|| {
+        anyhow!(
+            "MacBinary header claims a {data_length}-byte data fork, but the \
+             file is too short to contain it"
+        )
+    })
+}
+
+/// Returns the data fork entry of an AppleSingle file, or None if it has no
+/// data fork.
+fn applesingle_data_fork(data: &[u8]) -> anyhow::Result<Option<&[u8]>> {
+    if data.len() < APPLEFILE_HEADER_SIZE {
+        return Err(anyhow!("truncated AppleSingle/AppleDouble header"));
+    }
+    let entry_count = be16(data, 24) as usize;
+    for n in 0..entry_count {
+        let offset = APPLEFILE_HEADER_SIZE + n * APPLEFILE_ENTRY_SIZE;
+        let entry = data
+            .get(offset..offset + APPLEFILE_ENTRY_SIZE)
+            .ok_or_else(|| anyhow!("truncated AppleSingle entry table"))?;
+        if be32(entry, 0) != DATA_FORK_ENTRY_ID {
+            continue;
+        }
+        let start = be32(entry, 4) as usize;
+        let length = be32(entry, 8) as usize;
+        return data.get(start..start + length).map(Some).ok_or_else(|| {
+            anyhow!(
+                "AppleSingle data fork entry runs past the end of the file"
+            )
+        });
+    }
+    Ok(None)
+}
+
+/// Where the data fork belonging to an AppleDouble sidecar lives: the same
+/// name, without the `._` prefix, in the same directory.
+fn appledouble_companion(path: &Path) -> anyhow::Result<PathBuf> {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    match name.strip_prefix("._") {
+        Some(companion) if !companion.is_empty() => {
+            Ok(path.with_file_name(companion))
+        }
+        _ => Err(anyhow!(
+            "{path:?} is an AppleDouble header, which doesn't contain the \
+             data fork, and its name doesn't start with \"._\" so the \
+             companion file can't be found; give the path of the data file \
+             instead"
+        )),
+    }
+}
+
+/// Sees through a MacBinary, AppleSingle or AppleDouble wrapper, returning
+/// the data fork. For an AppleDouble sidecar, the data fork is read from the
+/// companion file (which may itself need unwrapping), with a note to say so.
+/// Data without a wrapper is returned unchanged.
+pub fn unwrap_mac_file(
+    data: Vec<u8>,
+    path: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<u8>> {
+    match detect_mac_wrapper(&data) {
+        None => Ok(data),
+        Some(MacWrapper::MacBinary) => macbinary_data_fork(&data)
+            .map(<[u8]>::to_vec)
+            .with_context(|| format!("unable to unwrap {path:?}")),
+        Some(MacWrapper::AppleSingle) => applesingle_data_fork(&data)
+            .and_then(|x| {
+                x.map(<[u8]>::to_vec).ok_or_else(|| {
+                    anyhow!("this AppleSingle file has no data fork")
+                })
+            })
+            .with_context(|| format!("unable to unwrap {path:?}")),
+        Some(MacWrapper::AppleDouble) => {
+            let companion = appledouble_companion(path)?;
+            diagnostics.note(
+                WarningKind::AppleDouble,
+                format_args!(
+                    "{path:?} is an AppleDouble header, reading the data fork \
+                     from {companion:?}"
+                ),
+            );
+            read_input(&companion, diagnostics)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"not really a physics file";
+
+    /// A MacBinary II file named "Physics", with `DATA` as its data fork and
+    /// no resource fork.
+    fn macbinary() -> Vec<u8> {
+        let mut file = vec![0; MACBINARY_HEADER_SIZE];
+        file[1] = 7;
+        file[2..9].copy_from_slice(b"Physics");
+        file[65..73].copy_from_slice(b"phyAphys");
+        file[83..87].copy_from_slice(&(DATA.len() as u32).to_be_bytes());
+        file[122] = 129; // the version that wrote it
+        file[123] = 129; // the version needed to read it
+        let crc = crc16_xmodem(&file[..124]);
+        file[124..126].copy_from_slice(&crc.to_be_bytes());
+        file.extend_from_slice(DATA);
+        file.resize(file.len().next_multiple_of(128), 0);
+        file
+    }
+
+    /// An AppleSingle (or AppleDouble) file with a Finder info entry, then
+    /// `DATA` as its data fork if `data_fork` is true.
+    fn applefile(magic: u32, data_fork: bool) -> Vec<u8> {
+        let mut entries = vec![(9u32, vec![0; 32])];
+        if data_fork {
+            entries.push((DATA_FORK_ENTRY_ID, DATA.to_vec()));
+        }
+        let mut file = magic.to_be_bytes().to_vec();
+        file.extend_from_slice(&0x00020000u32.to_be_bytes());
+        file.resize(24, 0);
+        file.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        let mut offset = file.len() + entries.len() * APPLEFILE_ENTRY_SIZE;
+        for (id, data) in &entries {
+            file.extend_from_slice(&id.to_be_bytes());
+            file.extend_from_slice(&(offset as u32).to_be_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in entries {
+            file.extend_from_slice(&data);
+        }
+        file
+    }
+
+    #[test]
+    fn macbinary_is_unwrapped() {
+        let file = macbinary();
+        assert_eq!(detect_mac_wrapper(&file), Some(MacWrapper::MacBinary));
+        let path = Path::new("Physics.bin");
+        assert_eq!(
+            unwrap_mac_file(file, path, &Diagnostics::default()).unwrap(),
+            DATA
+        );
+    }
+
+    #[test]
+    fn macbinary_needs_a_good_crc() {
+        let mut file = macbinary();
+        file[124] ^= 1;
+        assert_eq!(detect_mac_wrapper(&file), None);
+        // so it's left alone
+        let path = Path::new("Physics.bin");
+        assert_eq!(
+            unwrap_mac_file(file.clone(), path, &Diagnostics::default())
+                .unwrap(),
+            file
+        );
+    }
+
+    #[test]
+    fn truncated_macbinary_is_an_error() {
+        let mut file = macbinary();
+        file.truncate(MACBINARY_HEADER_SIZE + 4);
+        assert!(unwrap_mac_file(
+            file,
+            Path::new("Physics.bin"),
+            &Diagnostics::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn applesingle_is_unwrapped() {
+        let file = applefile(APPLESINGLE_MAGIC, true);
+        assert_eq!(detect_mac_wrapper(&file), Some(MacWrapper::AppleSingle));
+        let path = Path::new("Physics.as");
+        assert_eq!(
+            unwrap_mac_file(file, path, &Diagnostics::default()).unwrap(),
+            DATA
+        );
+        let file = applefile(APPLESINGLE_MAGIC, false);
+        assert!(unwrap_mac_file(file, path, &Diagnostics::default()).is_err());
+    }
+
+    #[test]
+    fn appledouble_reads_the_companion() {
+        let dir = std::env::temp_dir()
+            .join(format!("physics-eater-macfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Physics"), DATA).unwrap();
+        let file = applefile(APPLEDOUBLE_MAGIC, false);
+        assert_eq!(detect_mac_wrapper(&file), Some(MacWrapper::AppleDouble));
+        let diagnostics = Diagnostics::default();
+        let unwrapped = unwrap_mac_file(
+            file.clone(),
+            &dir.join("._Physics"),
+            &diagnostics,
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(unwrapped.unwrap(), DATA);
+        // with a note saying where the data fork came from
+        let recorded = diagnostics.recorded();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].level, Level::Note);
+        assert_eq!(recorded[0].code, WarningKind::AppleDouble);
+        assert!(recorded[0].message.contains("AppleDouble header"));
+        assert!(diagnostics.check().is_ok());
+        // without the "._", there's no telling where the data fork is
+        assert!(unwrap_mac_file(
+            file,
+            &dir.join("Physics"),
+            &Diagnostics::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn plain_data_is_left_alone() {
+        assert_eq!(detect_mac_wrapper(DATA), None);
+        assert_eq!(detect_mac_wrapper(&[0; 128]), None);
+        let path = Path::new("Physics");
+        assert_eq!(
+            unwrap_mac_file(DATA.to_vec(), path, &Diagnostics::default())
+                .unwrap(),
+            DATA
+        );
+    }
+}
@@ -17,77 +17,686 @@
 
 use std::path::PathBuf;
 
-#[macro_use]
-mod util;
-use util::*;
+use physics_eater::*;
 
-mod namedb;
-use namedb::*;
-mod physics;
-use physics::*;
-mod wad;
-use wad::*;
+use anyhow::{anyhow, Context};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 
-use clap::{Parser, Subcommand};
+/// The files that a command works on, given after the command.
+#[derive(Args, Debug, Clone)]
+struct Inputs {
+    /// The path to the physics model to work on. The convert, verify,
+    /// summary and stats commands accept more than one. Gzip-compressed
+    /// files are decompressed automatically, and the data fork is extracted
+    /// from MacBinary II, AppleSingle and AppleDouble files. A path of the
+    /// form `zip:ARCHIVE!ENTRY` reads the file ENTRY out of the zip archive
+    /// ARCHIVE.
+    #[arg(value_name = "PHYSICS_PATHS")]
+    physics_paths: Vec<PathBuf>,
+}
 
 #[derive(Subcommand, Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
 enum Command {
     /// Parse the header and directory of a Marathon 2 WAD, and display
     /// information about it.
-    ShowWad {},
+    ShowWad {
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Recompute the checksum of a Marathon 2 WAD (for example, after editing
+    /// it by hand) and write out a corrected copy. The WAD is copied byte for
+    /// byte, so it can't be compressed or wrapped.
+    FixChecksum {
+        /// Where to write the corrected WAD. May be the same as the input.
+        #[arg(long, short)]
+        output: PathBuf,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Write the raw contents of one chunk, from a bare M1 chunk file or an
+    /// M2 WAD, to a file.
+    ExtractChunk {
+        /// The four-character tag of the chunk to extract, e.g. "MNpx".
+        #[arg(value_parser = parse_tag)]
+        tag: [u8; 4],
+        /// Which subfile of an M2 WAD to look in. Ignored for M1 files.
+        #[arg(long, default_value_t = 0)]
+        subfile: usize,
+        /// Where to write the chunk's contents.
+        #[arg(long, short)]
+        output: PathBuf,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Replace the contents of one chunk of an M2 WAD with the contents of a
+    /// file, and write out the resulting WAD with a fixed-up directory and
+    /// checksum.
+    ReplaceChunk {
+        /// The four-character tag of the chunk to replace, e.g. "MNpx".
+        #[arg(value_parser = parse_tag)]
+        tag: [u8; 4],
+        /// A file containing the new contents of the chunk.
+        data_path: PathBuf,
+        /// Which subfile of the WAD the chunk is in.
+        #[arg(long, default_value_t = 0)]
+        subfile: usize,
+        /// Where to write the new WAD. May be the same as the input.
+        #[arg(long, short)]
+        output: PathBuf,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Change some fields of an M2 physics WAD, and write out the result.
+    /// The patch is JSON like the convert commands output, but with only the
+    /// fields to change, e.g. `{"monster_definitions": {"7": {"vitality":
+    /// 99}}}`. Definitions can be given by index or by name. Every other
+    /// byte is left exactly as it was.
+    ApplyPatch {
+        /// The JSON file containing the patch.
+        patch: PathBuf,
+        /// Where to write the patched WAD. May be the same as the input.
+        #[arg(long, short, required_unless_present = "check")]
+        output: Option<PathBuf>,
+        /// Only check the patch: encode it, report every problem with it
+        /// (names that don't resolve, values that don't fit their fields,
+        /// fields that don't exist...), and write nothing.
+        #[arg(long, conflicts_with = "output")]
+        check: bool,
+        /// A directory containing name tables, as for the convert commands.
+        #[arg(long)]
+        namedb: Option<PathBuf>,
+        /// Which engine the physics file is meant for.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Apply the physics in Aleph One MML, such as a scenario's or what
+    /// `--format mml` writes, to an M2 physics WAD, and write out the
+    /// result. MML only gives the fields it changes, so everything else comes
+    /// from the base WAD; as with apply-patch, fields the MML doesn't change
+    /// keep their exact bytes. Doesn't take a physics path.
+    ImportMml {
+        /// The MML file to import.
+        mml: PathBuf,
+        /// The physics WAD to apply the MML to.
+        #[arg(long)]
+        base: PathBuf,
+        /// Where to write the resulting WAD. May be the same as the base.
+        #[arg(long, short, required_unless_present = "check")]
+        output: Option<PathBuf>,
+        /// Only check the MML, reporting every problem with it, and write
+        /// nothing.
+        #[arg(long, conflicts_with = "output")]
+        check: bool,
+        /// A directory containing name tables, as for the convert commands.
+        #[arg(long)]
+        namedb: Option<PathBuf>,
+        /// Which engine the physics file is meant for.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+    },
+    /// Fully parse one or more physics files, M1 or M2, without outputting
+    /// anything, and report which ones parsed cleanly. Exits with an error
+    /// if any didn't.
+    Verify {
+        /// Also check that each WAD ends where its header says, warning
+        /// about bytes appended after the directory.
+        #[arg(long)]
+        check_extent: bool,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Output a JSON Schema describing the JSON that convert-m1-physics or
+    /// convert-m2-physics outputs. Doesn't take a physics path.
+    ExportSchema {
+        #[arg(value_enum)]
+        kind: SchemaKind,
+    },
+    /// Put a directory written by `--explode` back together, and output the
+    /// resulting JSON. Doesn't take a physics path.
+    Assemble {
+        /// The directory to assemble.
+        dir: PathBuf,
+        /// Write the JSON to this file instead of stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print Marathon's WAD checksum (a standard CRC-32) of a file, taken
+    /// as it is: nothing is decompressed or unwrapped, and if it's a WAD, its
+    /// checksum field isn't zeroed first. Doesn't take a physics path.
+    Checksum {
+        /// The file to checksum.
+        file: PathBuf,
+    },
+    /// Output a completion script for the given shell. Doesn't take a
+    /// physics path.
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
     /// Parse some bare M1 chunks (like a Marathon 1 physics file) and display
     /// information about them.
-    ShowChunks {},
-    /// Convert a Marathon 1 physics file into JSON on stdout.
-    ConvertM1Physics {
-        /// Path to a directory containing files like "monster_names.txt",
-        /// "projectile_names.txt", etc. These files contain one name per line
-        /// (with blank lines indicating gaps in the naming).
+    ShowChunks {
+        /// Output a JSON array with the kind and length of each chunk,
+        /// instead of a debug dump.
+        #[arg(long)]
+        json: bool,
+        /// Include the contents of each chunk, base64-encoded, in the JSON
+        /// output. Meant for picking apart unknown chunk types; the output
+        /// can get large.
+        #[arg(long, requires = "json")]
+        dump_data: bool,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Read a Marathon 2 or Infinity shapes file and show what's in each
+    /// collection: its type, and how many CLUTs, sequences, frames and
+    /// bitmaps it has.
+    ShowShapes {
+        /// Output a JSON array of the collections that are present, instead
+        /// of a line for each collection.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Print a readable summary of one or more physics files, M1 or M2: an
+    /// aligned table of the most interesting fields of each kind of
+    /// definition. Colored when writing to a terminal, unless NO_COLOR is
+    /// set.
+    Summary {
+        /// A directory containing name tables, as for the convert commands.
+        #[arg(long)]
+        namedb: Option<PathBuf>,
+        /// Which engine M2 physics files are meant for.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+        /// Instead of the tables, print one tab-separated line per
+        /// definition: its table, its index, and the fields given by
+        /// --columns. (With more than one file, each line starts with the
+        /// file's path.)
+        #[arg(long)]
+        tsv: bool,
+        /// The fields to print with --tsv, e.g. "name,vitality,speed". Dots
+        /// reach into nested fields, e.g. "damage.base".
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "name",
+            requires = "tsv"
+        )]
+        columns: Vec<String>,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Output statistics about one or more physics files, M1 or M2, as JSON
+    /// on stdout: how many of each kind of definition there are, how many
+    /// monsters have each kind of attack, which collections are used, etc.
+    Stats {
+        /// A directory containing name tables, as for the convert commands.
+        #[arg(long)]
+        namedb: Option<PathBuf>,
+        /// Which engine M2 physics files are meant for.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Parse a physics file, M1 or M2, and print just the value at a JSON
+    /// pointer into the JSON the convert commands output, e.g.
+    /// `/monster_definitions/7/speed`.
+    Query {
+        /// The JSON pointer to look up. Definitions are addressed by index.
+        pointer: String,
+        /// A directory containing name tables, as for the convert commands.
         #[arg(long)]
         namedb: Option<PathBuf>,
+        /// Which engine M2 physics files are meant for.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Print just the player physics (the walking and running models) of a
+    /// physics file, M1 or M2, as JSON on stdout. Nothing else in the file
+    /// is parsed.
+    PlayerPhysics {
+        /// Give velocities and accelerations in world units per second (and
+        /// per second per second) instead of per tick, at the given number of
+        /// ticks per second (30, Marathon's rate, if not given). Angles and
+        /// distances are left as they are.
+        #[arg(
+            long,
+            value_name = "TICKS_PER_SECOND",
+            num_args = 0..=1,
+            default_missing_value = "30",
+            value_parser = parse_tick_rate,
+        )]
+        tick_rate: Option<f64>,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Compare the player physics of two physics files, M1 or M2, and print
+    /// each field that differs, with its old and new values and the change,
+    /// e.g. `running.maximum_forward_velocity: 0.125 -> 0.15 (+0.025)`.
+    /// Takes exactly two physics paths, the old one first.
+    DiffPlayerPhysics {
+        /// Compare velocities and accelerations per second instead of per
+        /// tick, as for player-physics.
+        #[arg(
+            long,
+            value_name = "TICKS_PER_SECOND",
+            num_args = 0..=1,
+            default_missing_value = "30",
+            value_parser = parse_tick_rate,
+        )]
+        tick_rate: Option<f64>,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Convert a Marathon 1 physics file into JSON on stdout. The physics
+    /// can be bare chunks, as Marathon keeps them, or packaged in a WAD.
+    ConvertM1Physics {
+        #[command(flatten)]
+        options: ConvertOptions,
+        #[command(flatten)]
+        inputs: Inputs,
     },
     /// Convert a Marathon 2 physics file into JSON on stdout.
     ConvertM2Physics {
-        /// Path to a directory containing files like "monster_names.txt",
-        /// "projectile_names.txt", etc. These files contain one name per line
-        /// (with blank lines indicating gaps in the naming).
+        #[command(flatten)]
+        options: ConvertOptions,
+        /// Which engine the physics file is meant for. Marathon 2 doesn't
+        /// understand some flags that Marathon Infinity added, so they are
+        /// left out of the output when converting for Marathon 2.
+        #[arg(long, value_enum, default_value_t = Game::Infinity)]
+        game: Game,
+        /// The WAD that the physics file is an overlay of. Chunks that the
+        /// overlay doesn't have are taken from the parent, as the engine
+        /// does, before anything is converted.
         #[arg(long)]
-        namedb: Option<PathBuf>,
+        parent: Option<PathBuf>,
+        #[command(flatten)]
+        inputs: Inputs,
     },
 }
 
 #[derive(Parser, Debug)]
 #[clap(
+    subcommand_precedence_over_arg = true,
     author = "Solra Bizna <solra@bizna.name>",
     version,
     about = "A tool for turning Marathon physics files into JSON"
 )]
 struct Invocation {
-    /// The path to the physics model to work on.
-    physics_path: PathBuf,
+    /// Physics paths given before the command, the way older versions took
+    /// them. Still accepted, with a warning, but they now go after the
+    /// command.
+    #[arg(hide = true)]
+    physics_paths: Vec<PathBuf>,
+    /// Turn some kinds of malformation into warnings instead of errors: M2
+    /// WAD chunks with a nonzero value in the entry header's "offset" field
+    /// (some third-party tools write one there, and the engine never looks at
+    /// it), and stray bytes after the last complete M1 chunk.
+    #[arg(long, global = true)]
+    lenient: bool,
+    /// Treat every kind of warning as an error. Each one is still reported,
+    /// and then the command fails once it's done reading its input, before
+    /// it writes anything.
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Treat only the given kinds of warning as errors, as with --strict.
+    #[arg(long, global = true, value_enum, value_delimiter = ',')]
+    warnings_as_errors: Vec<WarningKind>,
+    /// Write warnings to stderr as JSON, one object per line, with "level",
+    /// "code", "message" and "location" keys, instead of as text.
+    #[arg(long, global = true)]
+    diagnostics_json: bool,
     /// What command to run.
     #[command(subcommand)]
     command: Command,
 }
 
+impl Command {
+    /// The files given after the command, for the commands that work on
+    /// physics files (or, for show-shapes, a shapes file).
+    fn inputs(&self) -> Option<&Inputs> {
+        match self {
+            Command::ShowWad { inputs }
+            | Command::FixChecksum { inputs, .. }
+            | Command::ExtractChunk { inputs, .. }
+            | Command::ReplaceChunk { inputs, .. }
+            | Command::ApplyPatch { inputs, .. }
+            | Command::Verify { inputs, .. }
+            | Command::ShowChunks { inputs, .. }
+            | Command::ShowShapes { inputs, .. }
+            | Command::Summary { inputs, .. }
+            | Command::Stats { inputs, .. }
+            | Command::Query { inputs, .. }
+            | Command::PlayerPhysics { inputs, .. }
+            | Command::DiffPlayerPhysics { inputs, .. }
+            | Command::ConvertM1Physics { inputs, .. }
+            | Command::ConvertM2Physics { inputs, .. } => Some(inputs),
+            Command::ImportMml { .. }
+            | Command::ExportSchema { .. }
+            | Command::Assemble { .. }
+            | Command::Checksum { .. }
+            | Command::Completions { .. } => None,
+        }
+    }
+}
+
 fn inner_main() -> anyhow::Result<()> {
     let Invocation {
-        physics_path,
+        physics_paths,
+        lenient,
+        strict,
+        warnings_as_errors,
+        diagnostics_json,
         command,
     } = Invocation::parse();
-    match command {
-        Command::ShowWad {} => show_wad(physics_path),
-        Command::ShowChunks {} => show_chunks(physics_path),
-        Command::ConvertM1Physics { namedb } => {
-            let namedbs = NameDbs::new(namedb.as_deref())?;
-            m1::convert_physics(physics_path, namedbs)
+    let mut promoted = warnings_as_errors;
+    if strict {
+        promoted.extend_from_slice(WarningKind::value_variants());
+    }
+    let diagnostics = Diagnostics::new(
+        if diagnostics_json {
+            Rendering::Json
+        } else {
+            Rendering::Text
+        },
+        &promoted,
+    );
+    if !physics_paths.is_empty() {
+        diagnostics.warn(
+            WarningKind::Deprecated,
+            "giving physics paths before the command is deprecated, and won't \
+             work in the next release; give them after it instead",
+        );
+    }
+    let physics_paths = match command.inputs() {
+        Some(inputs) => [physics_paths, inputs.physics_paths.clone()].concat(),
+        None => physics_paths,
+    };
+    let single_path = || match physics_paths.as_slice() {
+        [path] => Ok(path.clone()),
+        [] => Err(anyhow!("no physics path given")),
+        _ => Err(anyhow!("this command only works on one file at a time")),
+    };
+    if physics_paths.is_empty() && command.inputs().is_some() {
+        return Err(anyhow!("no physics path given"));
+    }
+    let result = match command {
+        Command::ShowWad { .. } => {
+            show_wad(single_path()?, lenient, &diagnostics)
         }
-        Command::ConvertM2Physics { namedb } => {
-            let namedbs = NameDbs::new(namedb.as_deref())?;
-            m2::convert_physics(physics_path, namedbs)
+        Command::FixChecksum { output, .. } => {
+            fix_checksum(single_path()?, output, lenient, &diagnostics)
         }
-    }
+        Command::ExtractChunk {
+            tag,
+            subfile,
+            output,
+            ..
+        } => extract_chunk(
+            single_path()?,
+            tag,
+            subfile,
+            output,
+            lenient,
+            &diagnostics,
+        ),
+        Command::ReplaceChunk {
+            tag,
+            data_path,
+            subfile,
+            output,
+            ..
+        } => replace_chunk(
+            single_path()?,
+            tag,
+            data_path,
+            subfile,
+            output,
+            lenient,
+            &diagnostics,
+        ),
+        Command::ApplyPatch {
+            patch,
+            output,
+            check,
+            namedb,
+            game,
+            ..
+        } => {
+            let namedbs = NameDbs::new(namedb.as_deref(), Default::default())?;
+            m2::apply_patch(
+                single_path()?,
+                &read_patch(&patch)?,
+                output,
+                check,
+                &namedbs,
+                game,
+                lenient,
+                &diagnostics,
+            )?;
+            if check {
+                println!("the patch is OK");
+            }
+            Ok(())
+        }
+        Command::ImportMml {
+            mml,
+            base,
+            output,
+            check,
+            namedb,
+            game,
+        } => {
+            if !physics_paths.is_empty() {
+                return Err(anyhow!(
+                    "import-mml doesn't take a physics path (use --base)"
+                ));
+            }
+            let text = std::fs::read_to_string(&mml)
+                .with_context(|| format!("unable to read {mml:?}"))?;
+            let patch = mml_to_patch(&text, &diagnostics)
+                .with_context(|| format!("unable to parse {mml:?}"))?;
+            let namedbs = NameDbs::new(namedb.as_deref(), Default::default())?;
+            m2::apply_patch(
+                base,
+                &patch,
+                output,
+                check,
+                &namedbs,
+                game,
+                lenient,
+                &diagnostics,
+            )?;
+            if check {
+                println!("the MML is OK");
+            }
+            Ok(())
+        }
+        Command::ShowChunks {
+            json, dump_data, ..
+        } => {
+            show_chunks(single_path()?, json, dump_data, lenient, &diagnostics)
+        }
+        Command::ShowShapes { json, .. } => {
+            show_shapes(single_path()?, json, &diagnostics)
+        }
+        Command::ExportSchema { kind } => {
+            if !physics_paths.is_empty() {
+                return Err(anyhow!(
+                    "export-schema doesn't take a physics path"
+                ));
+            }
+            export_schema(kind)
+        }
+        Command::Assemble { dir, output } => {
+            if !physics_paths.is_empty() {
+                return Err(anyhow!("assemble doesn't take a physics path"));
+            }
+            assemble(&dir, output.as_deref(), &diagnostics)
+        }
+        Command::Checksum { file } => {
+            if !physics_paths.is_empty() {
+                return Err(anyhow!("checksum doesn't take a physics path"));
+            }
+            let data = std::fs::read(&file)
+                .with_context(|| format!("unable to read {file:?}"))?;
+            println!("{:08X}", wad_checksum(&data));
+            Ok(())
+        }
+        Command::Completions { shell } => {
+            if !physics_paths.is_empty() {
+                return Err(anyhow!(
+                    "completions doesn't take a physics path"
+                ));
+            }
+            clap_complete::generate(
+                shell,
+                &mut Invocation::command(),
+                "physics-eater",
+                &mut std::io::stdout(),
+            );
+            Ok(())
+        }
+        Command::Verify { check_extent, .. } => {
+            verify_physics(&physics_paths, lenient, check_extent, &diagnostics)
+        }
+        Command::Stats { namedb, game, .. } => {
+            let namedbs = NameDbs::new(namedb.as_deref(), Default::default())?;
+            print_stats(&physics_paths, &namedbs, game, lenient, &diagnostics)
+        }
+        Command::Summary {
+            namedb,
+            game,
+            tsv,
+            columns,
+            ..
+        } => {
+            let namedbs = NameDbs::new(namedb.as_deref(), Default::default())?;
+            summarize_physics(
+                &physics_paths,
+                &namedbs,
+                game,
+                lenient,
+                tsv.then_some(columns.as_slice()),
+                &diagnostics,
+            )
+        }
+        Command::Query {
+            pointer,
+            namedb,
+            game,
+            ..
+        } => {
+            let namedbs = NameDbs::new(namedb.as_deref(), Default::default())?;
+            let value = query_physics(
+                &single_path()?,
+                &pointer,
+                &namedbs,
+                game,
+                lenient,
+                &diagnostics,
+            )?;
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            Ok(())
+        }
+        Command::PlayerPhysics { tick_rate, .. } => {
+            let value = player_physics(
+                &single_path()?,
+                tick_rate,
+                lenient,
+                &diagnostics,
+            )?;
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            Ok(())
+        }
+        Command::DiffPlayerPhysics { tick_rate, .. } => {
+            let [old, new] = physics_paths.as_slice() else {
+                return Err(anyhow!(
+                    "diff-player-physics takes exactly two physics paths"
+                ));
+            };
+            let changes = diff_player_physics(
+                old,
+                new,
+                tick_rate,
+                lenient,
+                &diagnostics,
+            )?;
+            for change in changes {
+                println!(
+                    "{}: {} -> {} ({:+})",
+                    change.field,
+                    change.old,
+                    change.new,
+                    change.delta()
+                );
+            }
+            Ok(())
+        }
+        Command::ConvertM1Physics { options, .. } => {
+            let namedbs =
+                NameDbs::new(options.namedb.as_deref(), options.name_style())?;
+            if options.list_names {
+                for line in namedbs.report() {
+                    diagnostics.note(WarningKind::NameSource, line);
+                }
+            }
+            for (physics_path, options) in options.per_input(&physics_paths)? {
+                m1::convert_physics(
+                    physics_path,
+                    namedbs.clone(),
+                    &options,
+                    lenient,
+                    &diagnostics,
+                )?;
+            }
+            Ok(())
+        }
+        Command::ConvertM2Physics {
+            options,
+            game,
+            parent,
+            ..
+        } => {
+            let namedbs =
+                NameDbs::new(options.namedb.as_deref(), options.name_style())?;
+            let parent = parent
+                .map(|x| {
+                    Wad::open(&x, lenient, &diagnostics)
+                        .with_context(|| format!("unable to read {x:?}"))
+                })
+                .transpose()?;
+            if options.list_names {
+                for line in namedbs.report() {
+                    diagnostics.note(WarningKind::NameSource, line);
+                }
+            }
+            for (physics_path, options) in options.per_input(&physics_paths)? {
+                m2::convert_physics(
+                    physics_path,
+                    namedbs.clone(),
+                    &options,
+                    game,
+                    parent.as_ref(),
+                    lenient,
+                    &diagnostics,
+                )?;
+            }
+            Ok(())
+        }
+    };
+    result?;
+    // anything that was warned about after the last check
+    diagnostics.check()?;
+    Ok(())
 }
 
 fn main() {
@@ -0,0 +1,597 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Aleph One's names for things, and its MML format. Most of our field
+//! names are the same as Aleph One's, but where Bungie's names were
+//! misspelled or misleading (sequences that are called shapes,
+//! "kamakazi"...), ours were fixed up, which makes it harder to
+//! cross-reference the two. `--mml-names` puts Aleph One's names back, and
+//! `--format mml` uses them too. `import-mml` goes the other way, turning
+//! MML into a patch.
+
+use std::io::Write;
+
+use anyhow::anyhow;
+use serde_json::{Map, Value};
+
+use crate::{Diagnostics, WarningKind};
+
+/// The definition tables, with the element that holds each of them in MML,
+/// and the element each definition gets inside it.
+const MML_TABLES: [(&str, &str, &str); 4] = [
+    ("monster_definitions", "monsters", "monster"),
+    ("effect_definitions", "effects", "effect"),
+    ("projectile_definitions", "projectiles", "projectile"),
+    ("weapon_definitions", "weapons", "weapon"),
+];
+
+/// Our name for a field, and Aleph One's.
+type Renames = &'static [(&'static str, &'static str)];
+
+/// Fields whose names depend on what they're in: the key of the object
+/// they're in, and our name and Aleph One's name for each field.
+const NAMES_BY_PARENT: &[(&[&str], Renames)] = &[
+    (
+        &["melee_attack", "ranged_attack"],
+        &[
+            ("projectile_type", "type"),
+            ("attack_sequence", "attack_shape"),
+        ],
+    ),
+    (&["shrapnel_damage", "damage"], &[("damage_type", "type")]),
+    (
+        &["flags"],
+        &[
+            // monsters
+            ("omniscient", "is_omniscent"),
+            ("flies", "flys"),
+            ("cannot_skip", "cannot_be_dropped"),
+            ("kamikaze", "is_kamakazi"),
+            ("berserker", "is_berserker"),
+            ("enlarged", "is_enlarged"),
+            ("delayed_hard_death", "has_delayed_hard_death"),
+            ("nuclear_hard_death", "has_nuclear_hard_death"),
+            ("cannot_fire_backwards", "cant_fire_backwards"),
+            ("tiny", "is_tiny"),
+            ("not_afraid_of_water", "is_not_afraid_of_water"),
+            ("not_afraid_of_sewage", "is_not_afraid_of_sewage"),
+            ("not_afraid_of_lava", "is_not_afraid_of_lava"),
+            ("not_afraid_of_goo", "is_not_afraid_of_goo"),
+            // projectiles
+            ("alien", "alien_projectile"),
+            ("melee", "melee_projectile"),
+        ],
+    ),
+];
+
+/// Fields whose names are the same wherever they are. (Every "sequence" is
+/// a "shape" to Aleph One, as it was to Bungie.)
+const NAMES: Renames = &[
+    ("hit_sequence", "hit_shapes"),
+    ("hard_dying_sequence", "hard_dying_shape"),
+    ("soft_dying_sequence", "soft_dying_shape"),
+    ("hard_dead_sequence", "hard_dead_shapes"),
+    ("soft_dead_sequence", "soft_dead_shapes"),
+    ("stationary_sequence", "stationary_shape"),
+    ("moving_sequence", "moving_shape"),
+    ("teleport_in_sequence", "teleport_in_shape"),
+    ("teleport_out_sequence", "teleport_out_shape"),
+    ("sequence", "shape"),
+    ("idle_sequence", "idle_shape"),
+    ("firing_sequence", "firing_shape"),
+    ("reloading_sequence", "reloading_shape"),
+    ("charging_sequence", "charging_shape"),
+    ("charged_sequence", "charged_shape"),
+];
+
+/// Aleph One's name for the given field of an object under `parent`, if
+/// it's different from ours.
+pub fn mml_name(parent: &str, key: &str) -> Option<&'static str> {
+    NAMES_BY_PARENT
+        .iter()
+        .filter(|(parents, _)| parents.contains(&parent))
+        .flat_map(|(_, names)| names.iter())
+        .chain(NAMES)
+        .find(|(ours, _)| *ours == key)
+        .map(|(_, theirs)| *theirs)
+}
+
+/// Our name for a field that Aleph One calls `theirs`, in an object under
+/// `parent`, if it's different.
+pub fn our_name(parent: &str, theirs: &str) -> Option<&'static str> {
+    NAMES_BY_PARENT
+        .iter()
+        .filter(|(parents, _)| parents.contains(&parent))
+        .flat_map(|(_, names)| names.iter())
+        .chain(NAMES)
+        .find(|(_, x)| *x == theirs)
+        .map(|(ours, _)| *ours)
+}
+
+/// Renames every field of serialized physics (or a table, or a single
+/// definition) that Aleph One calls something else, keeping the order of
+/// the fields.
+pub fn use_mml_names(value: &mut Value) {
+    rename(value, "");
+}
+
+fn rename(value: &mut Value, parent: &str) {
+    match value {
+        Value::Object(map) => {
+            let renamed: Map<String, Value> = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    rename(&mut value, &key);
+                    let key = match mml_name(parent, &key) {
+                        Some(x) => x.to_string(),
+                        None => key,
+                    };
+                    (key, value)
+                })
+                .collect();
+            *map = renamed;
+        }
+        // the elements of a table, or of the triggers, are under the same
+        // parent as the array itself
+        Value::Array(list) => list.iter_mut().for_each(|x| rename(x, parent)),
+        _ => (),
+    }
+}
+
+/// A scalar as an attribute value. Null is NONE, which is -1 in MML. A
+/// name can't be written, since Aleph One only takes indices.
+fn attribute(key: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::Null => Ok("-1".to_string()),
+        Value::String(x) => {
+            Err(anyhow!("{key}: {x:?} is a name, and MML needs an index"))
+        }
+        x => Ok(x.to_string()),
+    }
+}
+
+/// A flags object as the number Aleph One wants: the flags are in bit
+/// order starting from bit 0, followed by any "_reserved_bits", as the
+/// decoders output them.
+fn flag_bits(key: &str, flags: &Map<String, Value>) -> anyhow::Result<u64> {
+    let mut ret = 0;
+    let names = flags.iter().filter(|(name, _)| *name != "_reserved_bits");
+    for (bit, (name, value)) in names.enumerate() {
+        match value {
+            Value::Bool(false) => (),
+            Value::Bool(true) => ret |= 1 << bit,
+            x => {
+                return Err(anyhow!(
+                    "{key}/{name}: expected true or false, not {x}"
+                ))
+            }
+        }
+    }
+    if let Some(x) = flags.get("_reserved_bits") {
+        ret |= x.as_u64().ok_or_else(|| {
+            anyhow!("{key}/_reserved_bits: {x} isn't a valid bitfield")
+        })?;
+    }
+    Ok(ret)
+}
+
+/// A bitfield list (immunities, friends...) as the bits it sets.
+fn list_bits(key: &str, list: &[Value]) -> anyhow::Result<u64> {
+    list.iter().try_fold(0, |ret, x| match x.as_u64() {
+        Some(bit @ 0..=31) => Ok(ret | 1 << bit),
+        _ => Err(anyhow!("{key}: {x} isn't a bit number")),
+    })
+}
+
+/// Writes one object as an element. Its scalar fields become attributes,
+/// and so do its flags and bitfield lists, as numbers. Other objects become
+/// child elements, and the triggers become `<trigger>` elements.
+fn write_element(
+    out: &mut impl Write,
+    name: &str,
+    index: Option<usize>,
+    value: &Value,
+    depth: usize,
+) -> anyhow::Result<()> {
+    let indent = "  ".repeat(depth);
+    let Value::Object(map) = value else { return Err(anyhow!("<{name}> isn't an object")) };
+    write!(out, "{indent}<{name}")?;
+    if let Some(index) = index {
+        write!(out, " index=\"{index}\"")?;
+    }
+    // an attack that isn't there is one with no projectile
+    let no_attack = serde_json::json!({"type": null});
+    let mut children = vec![];
+    for (key, value) in map {
+        let value = match value {
+            Value::Null if key.ends_with("_attack") => {
+                children.push((key, &no_attack));
+                continue;
+            }
+            Value::Object(flags) if key == "flags" => {
+                flag_bits(key, flags)?.to_string()
+            }
+            Value::Array(list) if key != "triggers" => {
+                list_bits(key, list)?.to_string()
+            }
+            Value::Object(_) | Value::Array(_) => {
+                children.push((key, value));
+                continue;
+            }
+            x => attribute(key, x)?,
+        };
+        write!(out, " {key}=\"{value}\"")?;
+    }
+    if children.is_empty() {
+        writeln!(out, "/>")?;
+        return Ok(());
+    }
+    writeln!(out, ">")?;
+    for (key, value) in children {
+        match value {
+            Value::Array(list) => {
+                for (index, trigger) in list.iter().enumerate() {
+                    write_element(
+                        out,
+                        "trigger",
+                        Some(index),
+                        trigger,
+                        depth + 1,
+                    )?;
+                }
+            }
+            _ => write_element(out, key, None, value, depth + 1)?,
+        }
+    }
+    writeln!(out, "{indent}</{name}>")?;
+    Ok(())
+}
+
+/// Writes serialized physics (of either kind, with bare indices rather than
+/// names) as Aleph One's MML: a `<marathon>` element containing
+/// `<monsters>`, `<effects>`, `<projectiles>` and `<weapons>`, with an
+/// element for each definition, and `<physics>`, with a `<constants>`
+/// element for each physics model. Each has its index, and Aleph One's
+/// names for everything.
+pub fn write_mml(physics: &Value, mut out: impl Write) -> anyhow::Result<()> {
+    let mut physics = physics.clone();
+    use_mml_names(&mut physics);
+    let Value::Object(tables) = &physics else { return Err(anyhow!("MML output needs a whole physics file")) };
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<marathon>")?;
+    for (key, wrapper, element) in MML_TABLES {
+        let Some(Value::Array(records)) = tables.get(key) else { continue };
+        writeln!(out, "  <{wrapper}>")?;
+        for (index, record) in records.iter().enumerate() {
+            write_element(&mut out, element, Some(index), record, 2)?;
+        }
+        writeln!(out, "  </{wrapper}>")?;
+    }
+    if let Some(models) = tables.get("physics") {
+        let extra = match models.get("extra") {
+            Some(Value::Array(extra)) => extra.as_slice(),
+            _ => &[],
+        };
+        let models = [&models["walking"], &models["running"]]
+            .into_iter()
+            .chain(extra);
+        writeln!(out, "  <physics>")?;
+        for (index, model) in models.enumerate() {
+            write_element(&mut out, "constants", Some(index), model, 2)?;
+        }
+        writeln!(out, "  </physics>")?;
+    }
+    writeln!(out, "</marathon>")?;
+    Ok(())
+}
+
+/// An attribute value as JSON: a boolean, a number, or otherwise a name.
+/// -1 stays -1, which the encoders take to mean NONE.
+fn parse_attribute(text: &str) -> Value {
+    match text {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        x => {
+            if let Ok(x) = x.parse::<i64>() {
+                x.into()
+            } else if let Some(x) =
+                x.parse::<f64>().ok().and_then(serde_json::Number::from_f64)
+            {
+                Value::Number(x)
+            } else {
+                Value::String(x.to_string())
+            }
+        }
+    }
+}
+
+/// The `index` attribute of an element, which has to be there.
+fn element_index(node: roxmltree::Node) -> anyhow::Result<usize> {
+    let name = node.tag_name().name();
+    let index = node.attribute("index").ok_or_else(|| {
+        anyhow!("line {}: <{name}> has no index", line_of(node))
+    })?;
+    index.parse().map_err(|_| {
+        anyhow!(
+            "line {}: <{name}> has an index of {index:?}, which isn't a \
+             number",
+            line_of(node)
+        )
+    })
+}
+
+fn line_of(node: roxmltree::Node) -> u32 {
+    node.document().text_pos_at(node.range().start).row
+}
+
+/// Reads a definition's element back into an object, with our names for
+/// everything. `parent` is our name for the element itself. An `index`
+/// attribute is kept, since it's up to the caller whether it's a field.
+/// Flags and bitfield lists stay numbers, which the encoders take as they
+/// are.
+fn read_element(
+    node: roxmltree::Node,
+    parent: &str,
+) -> anyhow::Result<Map<String, Value>> {
+    let ours =
+        |theirs: &str| our_name(parent, theirs).unwrap_or(theirs).to_string();
+    let mut map = Map::new();
+    for attribute in node.attributes() {
+        map.insert(ours(attribute.name()), parse_attribute(attribute.value()));
+    }
+    let mut triggers = Map::new();
+    for child in node.children().filter(|x| x.is_element()) {
+        let name = child.tag_name().name();
+        if name == "trigger" {
+            let mut trigger = read_element(child, "triggers")?;
+            trigger.remove("index");
+            triggers.insert(element_index(child)?.to_string(), trigger.into());
+            continue;
+        }
+        let key = ours(name);
+        let value = read_element(child, &key)?;
+        // an attack with no projectile is one that isn't there, which is how
+        // `write_element` writes it
+        let value = match value.get("projectile_type") {
+            Some(x) if key.ends_with("_attack") && x.as_i64() == Some(-1) => {
+                Value::Null
+            }
+            _ => value.into(),
+        };
+        map.insert(key, value);
+    }
+    if !triggers.is_empty() {
+        map.insert("triggers".to_string(), triggers.into());
+    }
+    Ok(map)
+}
+
+/// Turns Aleph One's MML into a patch that `apply-patch` would accept: each
+/// definition's element, inside `<monsters>`, `<effects>`, `<projectiles>`
+/// or `<weapons>`, and each `<constants>` element inside `<physics>`, with
+/// Aleph One's names turned back into ours, patches the definition or
+/// physics model with that index. Everything else in a scenario's MML
+/// (`<stringset>`, `<must_be_exterminated>`...) is skipped with a warning.
+pub fn mml_to_patch(
+    text: &str,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Value> {
+    let document = roxmltree::Document::parse(text)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "marathon" {
+        return Err(anyhow!(
+            "expected a <marathon> element, not <{}>",
+            root.tag_name().name()
+        ));
+    }
+    let skip = |node: roxmltree::Node| {
+        diagnostics.at(format_args!("line {}", line_of(node))).warn(
+            WarningKind::NotPhysicsMml,
+            format_args!(
+                "skipping <{}>, which isn't physics",
+                node.tag_name().name()
+            ),
+        );
+    };
+    let mut patch = Map::new();
+    for wrapper in root.children().filter(|x| x.is_element()) {
+        let name = wrapper.tag_name().name();
+        let (table, element) =
+            match MML_TABLES.iter().find(|(_, x, _)| *x == name) {
+                Some((table, _, element)) => (*table, *element),
+                None if name == "physics" => ("physics", "constants"),
+                None => {
+                    skip(wrapper);
+                    continue;
+                }
+            };
+        for node in wrapper.children().filter(|x| x.is_element()) {
+            if node.tag_name().name() != element {
+                skip(node);
+                continue;
+            }
+            let index = element_index(node)?;
+            let mut record = read_element(node, element)?;
+            record.remove("index");
+            let Value::Object(table) = patch
+                .entry(table)
+                .or_insert_with(|| Value::Object(Map::new()))
+            else {
+                unreachable!()
+            };
+            if table.insert(index.to_string(), record.into()).is_some() {
+                return Err(anyhow!(
+                    "line {}: there's more than one <{element}> with index \
+                     {index} in <{name}>",
+                    line_of(node)
+                ));
+            }
+        }
+    }
+    diagnostics.check()?;
+    Ok(Value::Object(patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_depend_on_the_parent() {
+        assert_eq!(mml_name("flags", "flies"), Some("flys"));
+        assert_eq!(mml_name("flags", "alien"), Some("alien_projectile"));
+        assert_eq!(mml_name("monster_definitions", "flies"), None);
+        assert_eq!(mml_name("melee_attack", "projectile_type"), Some("type"));
+        assert_eq!(
+            mml_name("ranged_attack", "attack_sequence"),
+            Some("attack_shape")
+        );
+        assert_eq!(mml_name("damage", "damage_type"), Some("type"));
+        assert_eq!(mml_name("shrapnel_damage", "damage_type"), Some("type"));
+        assert_eq!(mml_name("damage", "projectile_type"), None);
+        assert_eq!(mml_name("melee_attack", "damage_type"), None);
+        // the same everywhere
+        assert_eq!(mml_name("", "hit_sequence"), Some("hit_shapes"));
+        assert_eq!(mml_name("flags", "hit_sequence"), Some("hit_shapes"));
+        assert_eq!(our_name("damage", "type"), Some("damage_type"));
+        assert_eq!(our_name("melee_attack", "type"), Some("projectile_type"));
+        assert_eq!(our_name("", "type"), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let by_parent = NAMES_BY_PARENT.iter().flat_map(|(parents, names)| {
+            parents.iter().flat_map(move |parent| {
+                names.iter().map(move |(ours, _)| (*parent, *ours))
+            })
+        });
+        let everywhere = NAMES.iter().map(|(ours, _)| ("", *ours));
+        for (parent, ours) in by_parent.chain(everywhere) {
+            let theirs = mml_name(parent, ours).unwrap();
+            assert_ne!(theirs, ours);
+            assert_eq!(our_name(parent, theirs), Some(ours), "{parent}");
+        }
+    }
+
+    #[test]
+    fn renames_nested_fields() {
+        let mut value = serde_json::json!({
+            "monster_definitions": [{
+                "flags": {"flies": true, "tiny": false},
+                "hit_sequence": 1,
+                "melee_attack": {"projectile_type": 2, "attack_sequence": 3},
+                "flies": 4,
+            }],
+            "projectile_definitions": [{
+                "flags": {"alien": true},
+                "damage": {"damage_type": 5},
+            }],
+        });
+        use_mml_names(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "monster_definitions": [{
+                    "flags": {"flys": true, "is_tiny": false},
+                    "hit_shapes": 1,
+                    "melee_attack": {"type": 2, "attack_shape": 3},
+                    "flies": 4,
+                }],
+                "projectile_definitions": [{
+                    "flags": {"alien_projectile": true},
+                    "damage": {"type": 5},
+                }],
+            })
+        );
+        let keys: Vec<_> = value["monster_definitions"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(keys, ["flags", "hit_shapes", "melee_attack", "flies"]);
+    }
+
+    #[test]
+    fn writes_aleph_one_layout() {
+        let physics = serde_json::json!({
+            "monster_definitions": [{
+                "collection": 12,
+                "immunities": [4, 24],
+                "flags": {"omniscient": false, "flies": true, "tiny": true},
+                "melee_attack": {"projectile_type": 3, "attack_sequence": 5},
+                "ranged_attack": null,
+                "hit_sequence": null,
+            }],
+            "effect_definitions": [],
+            "projectile_definitions": [{
+                "flags": {"guided": true, "_reserved_bits": 256},
+                "damage": {
+                    "damage_type": 2,
+                    "flags": {"alien_damage": true},
+                    "scale": 1.5,
+                },
+            }],
+            "weapon_definitions": [{
+                "item_type": 7,
+                "triggers": [{"rounds_per_magazine": 8}, {"dx": 0.25}],
+            }],
+            "physics": {
+                "walking": {"maximum_forward_velocity": 0.0714},
+                "running": {"maximum_forward_velocity": 0.125},
+            },
+        });
+        let mut out = vec![];
+        write_mml(&physics, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<marathon>
+  <monsters>
+    <monster index="0" collection="12" immunities="16777232" flags="6" hit_shapes="-1">
+      <melee_attack type="3" attack_shape="5"/>
+      <ranged_attack type="-1"/>
+    </monster>
+  </monsters>
+  <effects>
+  </effects>
+  <projectiles>
+    <projectile index="0" flags="257">
+      <damage type="2" flags="1" scale="1.5"/>
+    </projectile>
+  </projectiles>
+  <weapons>
+    <weapon index="0" item_type="7">
+      <trigger index="0" rounds_per_magazine="8"/>
+      <trigger index="1" dx="0.25"/>
+    </weapon>
+  </weapons>
+  <physics>
+    <constants index="0" maximum_forward_velocity="0.0714"/>
+    <constants index="1" maximum_forward_velocity="0.125"/>
+  </physics>
+</marathon>
+"#
+        );
+        // a name, rather than an index, can't be written
+        let named = serde_json::json!({
+            "effect_definitions": [{"collection": "Fighter"}],
+        });
+        let error = write_mml(&named, vec![]).unwrap_err();
+        assert!(error.to_string().contains("MML needs an index"), "{error}");
+    }
+}
@@ -17,26 +17,277 @@
 
 // rustc didn't want me to name this file `リムル.rs`. :(
 
+use super::*;
+
+#[cfg(feature = "json")]
+use std::borrow::Cow;
 use std::{
+    collections::{BTreeMap, HashMap},
     fs::File,
     io::{BufRead, BufReader},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
 };
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+/// Something identified through a namedb: its name if it has one, otherwise
+/// its index. Some `NameStyle`s turn it into an object instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Name {
+    /// The name from the namedb.
+    Named(String),
+    /// A bare index, because there was no name.
+    Index(usize),
+    /// An index with no name, as `{"index": 7, "name": null}`.
+    Unnamed(usize),
+    /// One set bit of a bitfield list, as `{"bit": 5, "name": "flame"}`.
+    Bit { bit: u32, name: Option<String> },
+    /// A definition's own index, with no name, as `null`.
+    Null(usize),
+}
+
+impl Name {
+    /// Returns true if this is a bare index, i.e. there was no name.
+    pub fn is_number(&self) -> bool {
+        matches!(self, Name::Index(_))
+    }
+}
+
+#[cfg(feature = "json")]
+impl Serialize for Name {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        match self {
+            Name::Named(name) => serializer.serialize_str(name),
+            Name::Index(index) => serializer.serialize_u64(*index as u64),
+            Name::Unnamed(index) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("index", index)?;
+                map.serialize_entry("name", &None::<String>)?;
+                map.end()
+            }
+            Name::Bit { bit, name } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("bit", bit)?;
+                map.serialize_entry("name", name)?;
+                map.end()
+            }
+            Name::Null(_) => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonSchema for Name {
+    fn schema_name() -> Cow<'static, str> {
+        "Name".into()
+    }
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A name from the namedb, or an index if there is no name (an object with `--unnamed object` or `--bit-objects`, or null for a definition's name with `--null-unnamed`)",
+            "anyOf": [
+                {"type": "string"},
+                {"type": "integer", "minimum": 0},
+                {"type": "object"},
+                {"type": "null"},
+            ],
+        })
+    }
+}
+
+/// How to identify something that has no name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum UnnamedStyle {
+    /// As its bare index, e.g. `7`. Can't be told apart from a deliberate
+    /// index.
+    #[default]
+    Number,
+    /// As an object with a null name, e.g. `{"index": 7, "name": null}`.
+    Object,
+}
+
+/// Options controlling how things are identified in the output.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+pub struct NameStyle {
+    /// How to output things that have no name in the namedb.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, default_value_t = UnnamedStyle::Number)
+    )]
+    pub unnamed: UnnamedStyle,
+    /// Output each entry of a bitfield list (immunities, weaknesses, friends,
+    /// enemies) as an object with its bit number and name, e.g.
+    /// `{"bit": 5, "name": "flame"}`, in order of bit. Every entry has the
+    /// same shape whether it's named or not (`"name"` is null if it isn't).
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub bit_objects: bool,
+    /// Always output a definition's "name" key, as null if it has no name,
+    /// instead of leaving the key out. Every definition then has the same
+    /// keys whether or not the namedb has an entry for it.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub null_unnamed: bool,
+    /// Ignore the namedb (and the built-in names) entirely, and output every
+    /// reference as its bare index. Gives the same output no matter whose
+    /// name tables were used, which is handy for diffing.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, conflicts_with_all = ["namedb", "unnamed"])
+    )]
+    pub raw_ids: bool,
+    /// Group each collection and CLUT together under a "shape" key, the way
+    /// they're packed together in the file, instead of outputting them as
+    /// separate keys.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub group_shapes: bool,
+    /// Output each weapon's triggers as an object with "primary" and
+    /// "secondary" keys, instead of as an array of two. A patch can give
+    /// triggers either way. Can't be used with --mml-names.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "mml_names"))]
+    pub named_triggers: bool,
+    /// Output each duration in ticks as an object that gives it in seconds
+    /// as well, e.g. `{"ticks": 15, "seconds": 0.5}`, at the given number
+    /// of ticks per second (30, Marathon's rate, if not given).
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "TICKS_PER_SECOND",
+            num_args = 0..=1,
+            default_missing_value = "30",
+            value_parser = parse_tick_rate,
+        )
+    )]
+    pub tick_rate: Option<f64>,
+}
+
+/// Parses a `--tick-rate` value, which must be a positive number.
+pub fn parse_tick_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x.is_finite() && x > 0.0 => Ok(x),
+        _ => Err(format!(
+            "{value:?} isn't a positive number of ticks per second"
+        )),
+    }
+}
 
+/// How names are compared by `NameDb::resolve`.
+fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Where a NameDb's names came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum NameSource {
+    /// Nowhere; there are no names.
+    #[default]
+    Nothing,
+    /// The given file.
+    File(PathBuf),
+    /// The built-in list.
+    BuiltIn,
+    /// The given `STR#` chunk (counting from 0) of the physics file.
+    StringList(usize),
+}
+
+/// The tag of a chunk holding a classic Mac string list: a 16-bit count,
+/// then that many Pascal strings (a length byte, then Mac Roman text).
+pub const STRING_LIST_TAG: [u8; 4] = *b"STR#";
+
+/// The highest index a name file can give a name to. Nothing we name can
+/// have an index past this (a 16-bit index from 0x8000 up is NONE), so a
+/// bigger one is a typo, and mustn't make us allocate room for that many
+/// names.
+const MAXIMUM_NAME_INDEX: usize = 0x7FFF;
+
+/// Reads a `STR#` string list.
+pub fn read_string_list(data: &[u8]) -> anyhow::Result<Vec<String>> {
+    let (count, mut rest) = match data {
+        [a, b, rest @ ..] => (u16::from_be_bytes([*a, *b]), rest),
+        _ => return Err(anyhow!("string list is too short to have a count")),
+    };
+    let mut ret = Vec::with_capacity(count as usize);
+    for n in 0..count {
+        let Some((&length, after)) = rest.split_first() else {
+            return Err(anyhow!(
+                "string list claims {count} strings, but ends after {n}"
+            ));
+        };
+        let string = after.get(..length as usize).ok_or_else(|| {
+            anyhow!("string #{n} of the string list runs past its end")
+        })?;
+        ret.push(mac_roman_to_utf8(string));
+        rest = &after[length as usize..];
+    }
+    Ok(ret)
+}
+
+/// Parses the value of `--names-from-chunk`: a name table, optionally
+/// followed by a colon and which `STR#` chunk to use (default 0).
+pub fn parse_table_and_chunk(value: &str) -> Result<(String, usize), String> {
+    let (table, chunk) = match value.split_once(':') {
+        Some((table, chunk)) => (
+            table,
+            chunk
+                .parse()
+                .map_err(|_| format!("{chunk:?} isn't a chunk number"))?,
+        ),
+        None => (value, 0),
+    };
+    let table = table.trim_end_matches(".txt");
+    if !NAME_FILES
+        .iter()
+        .any(|x| x.trim_end_matches(".txt") == table)
+    {
+        let known: Vec<&str> = NAME_FILES
+            .iter()
+            .map(|x| x.trim_end_matches(".txt"))
+            .collect();
+        return Err(format!(
+            "{table:?} isn't a name table, should be one of: {}",
+            known.join(", ")
+        ));
+    }
+    Ok((table.to_string(), chunk))
+}
+
+/// One table of names. The names never change once loaded, so they (and
+/// the index built from them) are shared between clones, and cloning a
+/// NameDb, e.g. to give each thread its own NameDbs, is cheap.
 #[derive(Clone, Default)]
 pub struct NameDb {
-    names: Vec<Option<String>>,
+    names: Arc<[Option<String>]>,
+    style: NameStyle,
+    source: NameSource,
+    /// The indices of each (normalized) name, for `resolve`. Built the first
+    /// time it's needed, since most NameDbs are only ever used to identify.
+    indices: Arc<OnceLock<HashMap<String, Vec<usize>>>>,
 }
 
 impl NameDb {
     pub fn new(base_path: &Path, my_name: &str) -> anyhow::Result<NameDb> {
+        Ok(NameDb::load(base_path, my_name)?.unwrap_or_default())
+    }
+    /// Like `new`, but returns None if the file doesn't exist, instead of an
+    /// empty NameDb.
+    pub fn load(
+        base_path: &Path,
+        my_name: &str,
+    ) -> anyhow::Result<Option<NameDb>> {
         let target_path = base_path.join(my_name);
         let f = match File::open(&target_path) {
             Ok(f) => f,
             Err(x) if x.kind() == std::io::ErrorKind::NotFound => {
-                return Ok(NameDb::default())
+                return Ok(None)
             }
             Err(x) => {
                 return Err(x).with_context(|| {
@@ -45,33 +296,163 @@ impl NameDb {
             }
         };
         let f = BufReader::new(f);
-        let names = f
-            .lines()
-            .map(|line| {
-                let line = line?;
-                let line = line.trim();
-                if line.is_empty() {
-                    Ok(None)
-                } else {
-                    Ok(Some(line.to_string()))
+        let mut names: Vec<Option<String>> = vec![];
+        let mut index = 0;
+        for (line_number, line) in f.lines().enumerate() {
+            let line = line?;
+            let mut line = line.trim();
+            // "7: Hunter" sets index 7, and the lines after it carry on from
+            // index 8
+            if let Some((explicit, rest)) = line.split_once(':') {
+                if let Ok(explicit) = explicit.trim().parse::<usize>() {
+                    // checked here, even for a gap, so that counting on from
+                    // it can't overflow
+                    if explicit > MAXIMUM_NAME_INDEX {
+                        return Err(anyhow!(
+                            "{target_path:?} line {}: index {explicit} is too \
+                             big, the limit is {MAXIMUM_NAME_INDEX}",
+                            line_number + 1
+                        ));
+                    }
+                    index = explicit;
+                    line = rest.trim();
                 }
-            })
-            .collect::<anyhow::Result<Vec<Option<String>>>>()?;
-        Ok(NameDb { names })
+            }
+            if !line.is_empty() {
+                if index > MAXIMUM_NAME_INDEX {
+                    return Err(anyhow!(
+                        "{target_path:?} line {}: index {index} is too big, \
+                         the limit is {MAXIMUM_NAME_INDEX}",
+                        line_number + 1
+                    ));
+                }
+                if names.len() <= index {
+                    names.resize(index + 1, None);
+                }
+                if let Some(existing) = &names[index] {
+                    return Err(anyhow!(
+                        "{target_path:?} line {}: index {index} was already \
+                         given the name {existing:?}",
+                        line_number + 1
+                    ));
+                }
+                names[index] = Some(line.to_string());
+            }
+            index += 1;
+        }
+        Ok(Some(NameDb {
+            names: names.into(),
+            source: NameSource::File(target_path),
+            ..Default::default()
+        }))
     }
-    pub fn identify<T>(&self, index: T) -> serde_json::Value
+    /// Makes a NameDb out of a `STR#` string list. As with a file, empty
+    /// (or all-whitespace) strings are gaps.
+    pub fn from_string_list(
+        data: &[u8],
+        chunk: usize,
+    ) -> anyhow::Result<NameDb> {
+        let names = read_string_list(data)?
+            .into_iter()
+            .map(|x| Some(x.trim().to_string()).filter(|x| !x.is_empty()))
+            .collect();
+        Ok(NameDb {
+            names,
+            source: NameSource::StringList(chunk),
+            ..Default::default()
+        })
+    }
+    /// Where the names came from.
+    pub fn source(&self) -> &NameSource {
+        &self.source
+    }
+    /// All the names, with None for gaps.
+    pub fn names(&self) -> &[Option<String>] {
+        &self.names
+    }
+    /// How many names there are, not counting gaps.
+    pub fn count(&self) -> usize {
+        self.names.iter().filter(|x| x.is_some()).count()
+    }
+    /// Returns the name at the given index, if there is one and names aren't
+    /// being ignored.
+    fn name(&self, index: usize) -> Option<&String> {
+        if self.style.raw_ids {
+            return None;
+        }
+        self.names.get(index).and_then(Option::as_ref)
+    }
+    pub fn identify<T>(&self, index: T) -> Name
     where
         usize: TryFrom<T>,
     {
         let Ok(index): Result<usize, _> = index.try_into() else { unreachable!() };
-        match self.names.get(index).and_then(Option::as_ref) {
+        match self.name(index) {
             Some(str) => {
                 assert!(!str.is_empty());
-                serde_json::Value::String(str.to_string())
+                Name::Named(str.to_string())
             }
-            None => serde_json::Value::Number(index.into()),
+            None if self.style.raw_ids => Name::Index(index),
+            None => match self.style.unnamed {
+                UnnamedStyle::Number => Name::Index(index),
+                UnnamedStyle::Object => Name::Unnamed(index),
+            },
         }
     }
+    /// Looks up the index of a name, the reverse of `identify`. Leading and
+    /// trailing whitespace is ignored, and so is case, so " hunter " finds
+    /// "Hunter". (`identify` still outputs names exactly as they are in the
+    /// file.) This means that two names differing only in case can't be told
+    /// apart, so looking up either of them is an error. The error for a name
+    /// that isn't there says which file was searched, since a name that's
+    /// valid in one table is often given for a field that uses another.
+    pub fn resolve(&self, name: &str) -> anyhow::Result<usize> {
+        let indices = self.indices.get_or_init(|| {
+            let mut indices: HashMap<String, Vec<usize>> = HashMap::new();
+            for (index, x) in self.names.iter().enumerate() {
+                if let Some(x) = x {
+                    indices.entry(normalize_name(x)).or_default().push(index);
+                }
+            }
+            indices
+        });
+        match indices.get(&normalize_name(name)).map(Vec::as_slice) {
+            Some(&[index]) => Ok(index),
+            None | Some(&[]) => Err(match &self.source {
+                NameSource::Nothing => anyhow!(
+                    "unknown name {name:?} (no names of this kind were loaded)"
+                ),
+                NameSource::File(path) => {
+                    anyhow!("unknown name {name:?} (not in {path:?})")
+                }
+                _ => anyhow!("unknown name {name:?}"),
+            }),
+            Some(&[a, b, ..]) => Err(anyhow!(
+                "{name:?} is ambiguous: it could be {:?} (#{a}) or {:?} \
+                 (#{b})",
+                self.names[a].as_deref().unwrap_or_default(),
+                self.names[b].as_deref().unwrap_or_default()
+            )),
+        }
+    }
+    /// Identifies a definition by its own index, for its "name" field. The
+    /// same as `identify`, except that with `--null-unnamed` an unnamed
+    /// definition is `Name::Null` rather than its bare index (which the
+    /// "name" field leaves out).
+    pub fn identify_definition(&self, index: usize) -> Name {
+        match self.identify(index) {
+            Name::Index(index) if self.style.null_unnamed => Name::Null(index),
+            x => x,
+        }
+    }
+    /// Identifies one set bit of a bitfield list.
+    pub fn identify_bit(&self, bit: u32) -> Name {
+        if !self.style.bit_objects {
+            return self.identify(bit);
+        }
+        let name = self.name(bit as usize).cloned();
+        Name::Bit { bit, name }
+    }
 }
 
 #[derive(Clone)]
@@ -86,37 +467,158 @@ pub struct NameDbs {
     pub collection_names: NameDb,
     pub sound_names: NameDb,
     pub weapon_class_names: NameDb,
+    pub style: NameStyle,
 }
 
+/// The name file for each NameDb, in the same order as `NameDbs::all`.
+const NAME_FILES: [&str; 10] = [
+    "monster_class_names.txt",
+    "monster_names.txt",
+    "projectile_names.txt",
+    "weapon_names.txt",
+    "item_names.txt",
+    "effect_names.txt",
+    "damage_type_names.txt",
+    "collection_names.txt",
+    "sound_names.txt",
+    "weapon_class_names.txt",
+];
+
 impl NameDbs {
-    pub fn new(namedb_path: Option<&Path>) -> anyhow::Result<NameDbs> {
-        match namedb_path {
-            None => Ok(NameDbs::default()),
-            Some(namedb_path) => Ok(NameDbs {
-                monster_class_names: NameDb::new(
-                    namedb_path,
-                    "monster_class_names.txt",
-                )?,
-                monster_names: NameDb::new(namedb_path, "monster_names.txt")?,
-                projectile_names: NameDb::new(
-                    namedb_path,
-                    "projectile_names.txt",
-                )?,
-                weapon_names: NameDb::new(namedb_path, "weapon_names.txt")?,
-                item_names: NameDb::new(namedb_path, "item_names.txt")?,
-                effect_names: NameDb::new(namedb_path, "effect_names.txt")?,
-                damage_type_names: NameDb::new(
-                    namedb_path,
-                    "damage_type_names.txt",
-                )?,
-                collection_names: NameDb::new(
-                    namedb_path,
-                    "collection_names.txt",
-                )?,
-                sound_names: NameDb::new(namedb_path, "sound_names.txt")?,
-                ..Default::default()
-            }),
+    /// Loads whichever name files are present in the given directory. Any
+    /// that are missing are left empty, except for the weapon class names,
+    /// which fall back to the built-in list.
+    pub fn new(
+        namedb_path: Option<&Path>,
+        style: NameStyle,
+    ) -> anyhow::Result<NameDbs> {
+        let mut ret = NameDbs::default();
+        if let Some(namedb_path) = namedb_path {
+            for (db, file_name) in ret.all_mut().into_iter().zip(NAME_FILES) {
+                if let Some(loaded) = NameDb::load(namedb_path, file_name)? {
+                    *db = loaded;
+                }
+            }
+        }
+        for db in ret.all_mut() {
+            db.style = style;
         }
+        ret.style = style;
+        Ok(ret)
+    }
+    /// Describes, for each name file, whether it was found and how many
+    /// names it had.
+    pub fn report(&self) -> Vec<String> {
+        self.all()
+            .into_iter()
+            .zip(NAME_FILES)
+            .map(|(db, file_name)| match db.source() {
+                NameSource::Nothing => format!("{file_name}: not found"),
+                NameSource::File(path) => format!(
+                    "{file_name}: {} names, from {}",
+                    db.count(),
+                    path.display()
+                ),
+                NameSource::BuiltIn => format!(
+                    "{file_name}: not found, using {} built-in names",
+                    db.count()
+                ),
+                NameSource::StringList(chunk) => format!(
+                    "{file_name}: {} names, from STR# chunk #{chunk}",
+                    db.count()
+                ),
+            })
+            .collect()
+    }
+    /// Every name table that has any names in it, keyed by the name of its
+    /// file (without the ".txt").
+    pub fn tables(&self) -> BTreeMap<&'static str, &[Option<String>]> {
+        self.all()
+            .into_iter()
+            .zip(NAME_FILES)
+            .filter(|(db, _)| db.count() > 0)
+            .map(|(db, file_name)| {
+                (file_name.trim_end_matches(".txt"), db.names())
+            })
+            .collect()
+    }
+    /// Replaces the given name table (named like its file, without the
+    /// ".txt") with names from the given `STR#` chunk.
+    pub fn load_string_list(
+        &mut self,
+        table: &str,
+        chunks: &[Chunk],
+        chunk: usize,
+    ) -> anyhow::Result<()> {
+        let lists = Chunk::find_all(chunks, STRING_LIST_TAG);
+        let data = lists.get(chunk).ok_or_else(|| {
+            anyhow!(
+                "can't load {table} from STR# chunk #{chunk}, the file only \
+                 has {} STR# chunk(s)",
+                lists.len()
+            )
+        })?;
+        let mut loaded = NameDb::from_string_list(data, chunk)
+            .with_context(|| format!("unable to read STR# chunk #{chunk}"))?;
+        loaded.style = self.style;
+        *self
+            .table_mut(table)
+            .ok_or_else(|| anyhow!("{table:?} isn't a name table"))? = loaded;
+        Ok(())
+    }
+    /// Returns the name table with the given name (like its file, without
+    /// the ".txt").
+    pub fn table(&self, table: &str) -> Option<&NameDb> {
+        self.all()
+            .into_iter()
+            .zip(NAME_FILES)
+            .find(|(_, file_name)| file_name.trim_end_matches(".txt") == table)
+            .map(|(db, _)| db)
+    }
+    fn table_mut(&mut self, table: &str) -> Option<&mut NameDb> {
+        self.all_mut()
+            .into_iter()
+            .zip(NAME_FILES)
+            .find(|(_, file_name)| file_name.trim_end_matches(".txt") == table)
+            .map(|(db, _)| db)
+    }
+    /// A copy of these name tables that identifies things in the given
+    /// style instead.
+    pub fn with_style(&self, style: NameStyle) -> NameDbs {
+        let mut ret = self.clone();
+        for db in ret.all_mut() {
+            db.style = style;
+        }
+        ret.style = style;
+        ret
+    }
+    fn all(&self) -> [&NameDb; 10] {
+        [
+            &self.monster_class_names,
+            &self.monster_names,
+            &self.projectile_names,
+            &self.weapon_names,
+            &self.item_names,
+            &self.effect_names,
+            &self.damage_type_names,
+            &self.collection_names,
+            &self.sound_names,
+            &self.weapon_class_names,
+        ]
+    }
+    fn all_mut(&mut self) -> [&mut NameDb; 10] {
+        [
+            &mut self.monster_class_names,
+            &mut self.monster_names,
+            &mut self.projectile_names,
+            &mut self.weapon_names,
+            &mut self.item_names,
+            &mut self.effect_names,
+            &mut self.damage_type_names,
+            &mut self.collection_names,
+            &mut self.sound_names,
+            &mut self.weapon_class_names,
+        ]
     }
 }
 
@@ -132,15 +634,90 @@ impl Default for NameDbs {
             damage_type_names: Default::default(),
             collection_names: Default::default(),
             sound_names: Default::default(),
+            style: Default::default(),
             weapon_class_names: NameDb {
-                names: vec![
+                names: [
                     Some("melee".to_string()),
                     Some("normal".to_string()),
                     Some("dual function".to_string()),
                     Some("dual wield".to_string()),
                     Some("multipurpose".to_string()),
-                ],
+                ]
+                .into(),
+                source: NameSource::BuiltIn,
+                ..Default::default()
             },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads a name file with the given contents, from a directory of its
+    /// own.
+    fn load(test: &str, contents: &str) -> anyhow::Result<NameDb> {
+        let dir = std::env::temp_dir()
+            .join(format!("physics-eater-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("names.txt"), contents).unwrap();
+        let ret = NameDb::new(&dir, "names.txt");
+        std::fs::remove_dir_all(&dir).unwrap();
+        ret
+    }
+
+    #[test]
+    fn explicit_indices() {
+        let db = load("explicit", "Tick\n3: Hunter\nTrooper\n\n7:Juggernaut")
+            .unwrap();
+        let names: Vec<_> = db.names().iter().map(Option::as_deref).collect();
+        assert_eq!(
+            names,
+            [
+                Some("Tick"),
+                None,
+                None,
+                Some("Hunter"),
+                Some("Trooper"),
+                None,
+                None,
+                Some("Juggernaut"),
+            ]
+        );
+        assert_eq!(db.count(), 4);
+    }
+
+    #[test]
+    fn duplicate_index_is_an_error() {
+        let error = load("duplicate", "Tick\nHunter\n1: Trooper")
+            .err()
+            .expect("a duplicate index was accepted");
+        assert!(
+            error.to_string().ends_with(
+                "line 3: index 1 was already given the name \"Hunter\""
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn huge_index_is_an_error() {
+        let error = load("huge", "4000000000: x")
+            .err()
+            .expect("a huge index was accepted");
+        assert!(
+            error.to_string().ends_with(
+                "line 1: index 4000000000 is too big, the limit is 32767"
+            ),
+            "{error}"
+        );
+        // even with no name, so that the next line's index doesn't overflow
+        let error = load("huge_gap", "18446744073709551615:\nx")
+            .err()
+            .expect("a huge index was accepted");
+        assert!(error.to_string().contains("line 1: index"), "{error}");
+        let db = load("limit", "32767: x").unwrap();
+        assert_eq!(db.names().len(), 32768);
+    }
+}
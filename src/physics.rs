@@ -17,20 +17,1545 @@
 
 use super::*;
 
-use std::io::{Read, Seek, SeekFrom};
+#[cfg(feature = "json")]
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{stdout, BufWriter, IsTerminal, Write},
+    path::Path,
+};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{Cursor, Read, Seek, SeekFrom},
+    ops::{Deref, DerefMut},
+};
+
+use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+#[cfg(feature = "json")]
+use serde::Serialize;
+#[cfg(feature = "json")]
+use serde_json::ser::PrettyFormatter;
 
 pub mod m1;
 pub mod m2;
 
-pub fn is_m1_physics(mut input: impl Read + Seek) -> anyhow::Result<bool> {
-    let mut buf = [0; 4];
-    input.read_exact(&mut buf)?;
-    input.seek(SeekFrom::Current(-4))?;
-    Ok(buf == m1::MONSTER_PHYSICS_TAG
-        || buf == m1::EFFECT_PHYSICS_TAG
-        || buf == m1::PROJECTILE_PHYSICS_TAG
-        || buf == m1::PHYSICS_PHYSICS_TAG
-        || buf == m1::WEAPON_PHYSICS_TAG)
+/// A collection and color table ("CLUT"), packed together in one 16-bit
+/// field at the start of monster, effect and projectile definitions.
+/// Serializes as separate "collection" and "clut" keys, or as a single
+/// "shape" object with `--group-shapes`. A field with the 0x8000 bit set
+/// means "no shape"; then both halves are None, never just one of them, and
+/// a grouped shape is null rather than an object of nulls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub collection: Option<Name>,
+    pub clut: Option<u16>,
+    grouped: bool,
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct ShapeFields<'a> {
+    collection: &'a Option<Name>,
+    clut: &'a Option<u16>,
+}
+
+impl Shape {
+    pub fn read(input: impl Read, namedbs: &NameDbs) -> anyhow::Result<Shape> {
+        let collection_and_clut = read_optional_16(input)?;
+        Ok(Shape {
+            collection: collection_and_clut
+                .map(|x| namedbs.collection_names.identify(x % 32)),
+            clut: collection_and_clut.map(|x| x / 32),
+            grouped: namedbs.style.group_shapes,
+        })
+    }
+    /// Packs a collection index and CLUT back together, the inverse of how
+    /// `read` splits them.
+    pub fn pack(collection: u16, clut: u16) -> anyhow::Result<u16> {
+        if collection >= 32 {
+            return Err(anyhow!("collection {collection} is out of range"));
+        }
+        if clut >= 1024 {
+            return Err(anyhow!("CLUT {clut} is out of range"));
+        }
+        Ok(clut * 32 + collection)
+    }
+    /// Like `pack`, but for halves that came from a `Shape`. "No shape"
+    /// packs to NONE, which `read` turns back into "no shape", not into
+    /// collection 0 CLUT 0. Having only one of the halves is an error.
+    pub fn pack_optional(
+        collection: Option<u16>,
+        clut: Option<u16>,
+    ) -> anyhow::Result<u16> {
+        match (collection, clut) {
+            (None, None) => Ok(0xFFFF),
+            (Some(collection), Some(clut)) => Shape::pack(collection, clut),
+            _ => Err(anyhow!(
+                "a shape needs both a collection and a CLUT, or neither"
+            )),
+        }
+    }
+    /// Returns true if this is the "no shape" marker.
+    pub fn is_none(&self) -> bool {
+        self.collection.is_none() && self.clut.is_none()
+    }
+}
+
+#[cfg(feature = "json")]
+impl Serialize for Shape {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        if self.grouped {
+            let fields = ShapeFields {
+                collection: &self.collection,
+                clut: &self.clut,
+            };
+            map.serialize_entry(
+                "shape",
+                &(!self.is_none()).then_some(fields),
+            )?;
+        } else {
+            map.serialize_entry("collection", &self.collection)?;
+            map.serialize_entry("clut", &self.clut)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonSchema for Shape {
+    fn schema_name() -> Cow<'static, str> {
+        "Shape".into()
+    }
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let collection = generator.subschema_for::<Option<Name>>();
+        let clut = generator.subschema_for::<Option<u16>>();
+        json_schema!({
+            "type": "object",
+            "description": "\"collection\" and \"clut\", or a \"shape\" object containing both with `--group-shapes`",
+            "properties": {
+                "collection": collection,
+                "clut": clut,
+                "shape": {
+                    "type": ["object", "null"],
+                    "properties": {
+                        "collection": collection,
+                        "clut": clut,
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// A duration in ticks. Serializes as the bare tick count, or with
+/// `--tick-rate`, as an object that also gives the duration in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ticks {
+    pub ticks: u16,
+    tick_rate: Option<f64>,
+}
+
+impl Ticks {
+    /// Reads a tick count that may be NONE.
+    pub fn read_optional(
+        input: impl Read,
+        namedbs: &NameDbs,
+    ) -> anyhow::Result<Option<Ticks>> {
+        Ok(read_optional_16(input)?.map(|ticks| Ticks {
+            ticks,
+            tick_rate: namedbs.style.tick_rate,
+        }))
+    }
+    /// The duration in seconds, if we were given a tick rate.
+    pub fn seconds(&self) -> Option<f64> {
+        self.tick_rate.map(|rate| self.ticks as f64 / rate)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Serialize for Ticks {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let Some(seconds) = self.seconds() else { return self.ticks.serialize(serializer) };
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("ticks", &self.ticks)?;
+        map.serialize_entry("seconds", &seconds)?;
+        map.end()
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonSchema for Ticks {
+    fn schema_name() -> Cow<'static, str> {
+        "Ticks".into()
+    }
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A duration in ticks, or an object giving it in both ticks and seconds with `--tick-rate`",
+            "anyOf": [
+                {"type": "integer", "minimum": 0, "maximum": 65535},
+                {
+                    "type": "object",
+                    "properties": {
+                        "ticks": {"type": "integer", "minimum": 0, "maximum": 65535},
+                        "seconds": {"type": "number"},
+                    },
+                },
+            ],
+        })
+    }
+}
+
+/// What a weapon's two triggers are called with `--named-triggers`.
+pub const TRIGGER_NAMES: [&str; 2] = ["primary", "secondary"];
+
+/// A weapon's two triggers, primary then secondary. Serializes as an array,
+/// or with `--named-triggers`, as an object with "primary" and "secondary"
+/// keys. Derefs to the array.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Triggers<T> {
+    pub triggers: [T; 2],
+    named: bool,
+}
+
+impl<T> Triggers<T> {
+    pub fn new(triggers: [T; 2], namedbs: &NameDbs) -> Triggers<T> {
+        Triggers {
+            triggers,
+            named: namedbs.style.named_triggers,
+        }
+    }
+}
+
+impl<T> Deref for Triggers<T> {
+    type Target = [T; 2];
+    fn deref(&self) -> &[T; 2] {
+        &self.triggers
+    }
+}
+
+impl<T> DerefMut for Triggers<T> {
+    fn deref_mut(&mut self) -> &mut [T; 2] {
+        &mut self.triggers
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: Serialize> Serialize for Triggers<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        if !self.named {
+            return self.triggers.serialize(serializer);
+        }
+        let mut map = serializer.serialize_map(Some(2))?;
+        for (name, trigger) in TRIGGER_NAMES.iter().zip(&self.triggers) {
+            map.serialize_entry(name, trigger)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: JsonSchema> JsonSchema for Triggers<T> {
+    fn schema_name() -> Cow<'static, str> {
+        "Triggers".into()
+    }
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let trigger = generator.subschema_for::<T>();
+        json_schema!({
+            "description": "The primary and secondary triggers, as an array, or as an object with \"primary\" and \"secondary\" keys with `--named-triggers`",
+            "anyOf": [
+                {
+                    "type": "array",
+                    "items": trigger,
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "primary": trigger,
+                        "secondary": trigger,
+                    },
+                    "required": ["primary", "secondary"],
+                },
+            ],
+        })
+    }
+}
+
+/// Which engine a Marathon 2-format physics file is meant for. Marathon
+/// Infinity understands a few flag bits that Marathon 2 does not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Game {
+    M2,
+    #[default]
+    Infinity,
+}
+
+/// What kind of file something looks like, as far as `detect_physics_kind`
+/// can tell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhysicsKind {
+    /// Bare Marathon 1 chunks.
+    M1,
+    /// A Marathon 2 (or Infinity) WAD.
+    M2,
+    /// Neither, for the given reason.
+    Unknown(String),
+}
+
+/// Works out whether the input is bare M1 chunks or an M2 WAD, by checking
+/// whether the start of it makes sense as either. An M1 chunk header has to
+/// start with a printable four-character tag (not necessarily one we know),
+/// and the chunk it describes has to fit in the file; a WAD header has to
+/// have a version we know of and a directory inside the file. A WAD version
+/// always starts with a zero byte, which is never printable, so the two can't
+/// be confused. Leaves the input where it was.
+pub fn detect_physics_kind(
+    mut input: impl Read + Seek,
+) -> anyhow::Result<PhysicsKind> {
+    let start = input.stream_position()?;
+    let file_length = input.seek(SeekFrom::End(0))? - start;
+    input.seek(SeekFrom::Start(start))?;
+    let mut head = Vec::new();
+    (&mut input).take(128).read_to_end(&mut head)?;
+    input.seek(SeekFrom::Start(start))?;
+    let m1_problem = match Chunk::m1_header_problem(&head, file_length) {
+        None => return Ok(PhysicsKind::M1),
+        Some(x) => x,
+    };
+    let m2_problem = match wad_header_problem(&head, file_length) {
+        None => return Ok(PhysicsKind::M2),
+        Some(x) => x,
+    };
+    Ok(PhysicsKind::Unknown(format!(
+        "not M1 physics ({m1_problem}), and not an M2 WAD ({m2_problem})"
+    )))
+}
+
+pub fn is_m1_physics(input: impl Read + Seek) -> anyhow::Result<bool> {
+    Ok(detect_physics_kind(input)? == PhysicsKind::M1)
+}
+
+/// The parsed contents of a physics file of either kind.
+#[cfg_attr(feature = "json", derive(Serialize))]
+#[cfg_attr(feature = "json", serde(untagged))]
+pub enum AnyPhysics {
+    M1(m1::Physics),
+    M2(m2::Physics),
+}
+
+/// Parses a complete physics file that's already in memory, without touching
+/// the filesystem. Whether it's Marathon 1 physics or a Marathon 2 WAD (read
+/// as Infinity physics) is detected from its header (see
+/// `detect_physics_kind`); anything that's neither fails with
+/// `PhysicsEaterError::NotPhysics`. Every table that is present is read.
+/// Warnings are dropped; use `parse_physics` to get them.
+pub fn parse_physics_from_bytes(
+    input: &[u8],
+    namedbs: &NameDbs,
+) -> anyhow::Result<AnyPhysics> {
+    parse_physics(
+        input,
+        namedbs,
+        Game::Infinity,
+        false,
+        &Diagnostics::default(),
+    )
+}
+
+/// Like `parse_physics_from_bytes`, but with control over which game M2
+/// physics are for and whether to be lenient about malformations, and with
+/// warnings going to `diagnostics`. Fails if any of the warnings are being
+/// treated as errors.
+pub fn parse_physics(
+    input: &[u8],
+    namedbs: &NameDbs,
+    game: Game,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<AnyPhysics> {
+    let physics =
+        read_any_physics(input, namedbs, &[], game, lenient, diagnostics)?;
+    diagnostics.check()?;
+    Ok(physics)
+}
+
+/// `parse_physics`, minus failing for warnings that were made errors, and
+/// reading only the tables in `only` (or all of them, if it's empty).
+fn read_any_physics(
+    input: &[u8],
+    namedbs: &NameDbs,
+    only: &[Table],
+    game: Game,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<AnyPhysics> {
+    match detect_physics_kind(Cursor::new(input))? {
+        PhysicsKind::M1 => {
+            let chunks = Chunk::read_m1_chunks(input, lenient, diagnostics)?;
+            Ok(AnyPhysics::M1(m1::Physics::read(
+                &chunks,
+                namedbs,
+                only,
+                None,
+                diagnostics,
+            )?))
+        }
+        PhysicsKind::M2 => {
+            let wad = Wad::read_wad_from_bytes(input, lenient, diagnostics)?;
+            let chunks =
+                wad.files.first().ok_or(PhysicsEaterError::NoSubfiles)?;
+            let has_m2_physics = chunks
+                .iter()
+                .any(|chunk| m2::PHYSICS_TAGS.contains(&chunk.kind));
+            if !has_m2_physics {
+                if let Some(chunks) = m1::find_physics_subfile(&wad) {
+                    return Ok(AnyPhysics::M1(m1::Physics::read(
+                        chunks,
+                        namedbs,
+                        only,
+                        None,
+                        diagnostics,
+                    )?));
+                }
+            }
+            Ok(AnyPhysics::M2(m2::Physics::read(
+                chunks,
+                namedbs,
+                only,
+                game,
+                None,
+                diagnostics,
+            )?))
+        }
+        PhysicsKind::Unknown(reason) => {
+            Err(PhysicsEaterError::NotPhysics { reason }.into())
+        }
+    }
+}
+
+/// Fully parses each of the given physics files, M1 or M2, and prints
+/// whether each one parsed. Returns an error if any didn't. With
+/// `check_extent`, also warns about any WAD that doesn't end where its
+/// header says it does (see `Wad::check_extent`).
+pub fn verify_physics(
+    paths: &[PathBuf],
+    lenient: bool,
+    check_extent: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let namedbs = NameDbs::default();
+    let mut failures = 0;
+    for path in paths {
+        let diagnostics = diagnostics.at(path.display());
+        let result = read_input(path, &diagnostics).and_then(|x| {
+            parse_physics(
+                &x,
+                &namedbs,
+                Game::Infinity,
+                lenient,
+                &diagnostics,
+            )?;
+            if check_extent && !is_m1_physics(Cursor::new(&x))? {
+                // Any warnings about the contents were already given by
+                // parsing them.
+                Wad::read_wad_from_bytes(
+                    &x,
+                    lenient,
+                    &Diagnostics::default(),
+                )?
+                .check_extent(x.len() as u64, &diagnostics);
+            }
+            Ok(())
+        });
+        match result {
+            Ok(_) => println!("OK   {}", path.display()),
+            Err(x) => {
+                println!("FAIL {}: {x:#}", path.display());
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!(
+            "{failures} of {} physics files failed to verify",
+            paths.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+/// The player physics fields that are a distance per tick, and the ones that
+/// are a distance per tick per tick.
+const PLAYER_VELOCITY_FIELDS: &[&str] = &[
+    "maximum_forward_velocity",
+    "maximum_backward_velocity",
+    "maximum_perpendicular_velocity",
+    "terminal_velocity",
+];
+#[cfg(feature = "json")]
+const PLAYER_ACCELERATION_FIELDS: &[&str] = &[
+    "acceleration",
+    "deceleration",
+    "airborne_deceleration",
+    "gravitational_acceleration",
+    "climbing_acceleration",
+    "external_deceleration",
+];
+
+#[cfg(feature = "json")]
+/// Reads just the player physics, the walking and running models, out of a
+/// physics file, M1 or M2, in the same serialized form that the convert
+/// commands output. Nothing else in the file is parsed. With a tick rate,
+/// velocities and accelerations are given per second instead of per tick,
+/// and the result says what rate was used; angles and distances are left as
+/// they are.
+pub fn player_physics(
+    path: &Path,
+    tick_rate: Option<f64>,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<serde_json::Value> {
+    use serde_json::{Map, Value};
+    let diagnostics = &diagnostics.at(path.display());
+    let physics = read_any_physics(
+        &read_input(path, diagnostics)?,
+        &NameDbs::default(),
+        &[Table::Physics],
+        Game::Infinity,
+        lenient,
+        diagnostics,
+    )
+    .with_context(|| format!("unable to parse {path:?}"))?;
+    diagnostics.check()?;
+    let Some(Value::Object(mut models)) = serde_json::to_value(physics)?
+        .get_mut("physics")
+        .map(Value::take)
+    else {
+        return Err(anyhow!("{path:?} has no player physics"));
+    };
+    models.remove("extra");
+    let Some(tick_rate) = tick_rate else { return Ok(Value::Object(models)) };
+    let mut ret = Map::new();
+    ret.insert("tick_rate".to_string(), tick_rate.into());
+    for (key, mut model) in models {
+        for (fields, scale) in [
+            (PLAYER_VELOCITY_FIELDS, tick_rate),
+            (PLAYER_ACCELERATION_FIELDS, tick_rate * tick_rate),
+        ] {
+            for field in fields {
+                if let Some(value) = model.get_mut(*field) {
+                    let Some(number) = value.as_f64() else {
+                        return Err(anyhow!(
+                            "{path:?}: {key} {field} is {value}, not a number"
+                        ));
+                    };
+                    *value = (number * scale).into();
+                }
+            }
+        }
+        ret.insert(key, model);
+    }
+    Ok(Value::Object(ret))
+}
+
+#[cfg(feature = "json")]
+/// One player physics field that differs between two physics files.
+pub struct PlayerPhysicsChange {
+    /// The model and the field, e.g. `running.maximum_forward_velocity`.
+    pub field: String,
+    pub old: f64,
+    pub new: f64,
+    /// The smallest change the field can have: one unit in the last place
+    /// of its 16.16 fixed point value, in the units it's given in (per
+    /// second, or per second per second, with a tick rate).
+    pub step: f64,
+}
+
+#[cfg(feature = "json")]
+impl PlayerPhysicsChange {
+    /// How much the field changed by, rounded to a whole number of `step`s.
+    /// Both values went through `f32` on the way, so the plain difference
+    /// can be a little way off.
+    pub fn delta(&self) -> f64 {
+        ((self.new - self.old) / self.step).round() * self.step
+    }
 }
 
-// Neat. The copyright notice was longer than the file.
+#[cfg(feature = "json")]
+/// Compares the player physics of two physics files, M1 or M2, as read by
+/// `player_physics`, and returns every field that differs, walking then
+/// running. Fields that only one of them has (M2 has a splash height, M1
+/// doesn't) are skipped.
+pub fn diff_player_physics(
+    old_path: &Path,
+    new_path: &Path,
+    tick_rate: Option<f64>,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<PlayerPhysicsChange>> {
+    use serde_json::Value;
+    let old = player_physics(old_path, tick_rate, lenient, diagnostics)?;
+    let new = player_physics(new_path, tick_rate, lenient, diagnostics)?;
+    let tick_rate = tick_rate.unwrap_or(1.0);
+    let mut changes = vec![];
+    for model in ["walking", "running"] {
+        let (Some(Value::Object(old)), Some(new)) =
+            (old.get(model), new.get(model))
+        else {
+            continue;
+        };
+        for (field, old) in old {
+            let (Some(old), Some(new)) =
+                (old.as_f64(), new.get(field).and_then(Value::as_f64))
+            else {
+                continue;
+            };
+            if old != new {
+                let scale = match field.as_str() {
+                    x if PLAYER_VELOCITY_FIELDS.contains(&x) => tick_rate,
+                    x if PLAYER_ACCELERATION_FIELDS.contains(&x) => {
+                        tick_rate * tick_rate
+                    }
+                    _ => 1.0,
+                };
+                changes.push(PlayerPhysicsChange {
+                    field: format!("{model}.{field}"),
+                    old,
+                    new,
+                    step: scale / 65536.0,
+                });
+            }
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(feature = "json")]
+/// Where a chunk is in the file, for `--offsets`.
+#[derive(Serialize)]
+pub struct ChunkLayout {
+    pub tag: String,
+    pub offset: u64,
+    pub length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_count: Option<usize>,
+}
+
+#[cfg(feature = "json")]
+impl ChunkLayout {
+    /// Describes every chunk. `record_counts` gives the number of records
+    /// that were read out of the chunks with the given tags.
+    pub fn describe(
+        chunks: &[Chunk],
+        record_counts: &[([u8; 4], Option<usize>)],
+    ) -> Vec<ChunkLayout> {
+        chunks
+            .iter()
+            .map(|chunk| {
+                let record_count = record_counts
+                    .iter()
+                    .find(|(tag, _)| *tag == chunk.kind)
+                    .and_then(|(_, count)| *count)
+                    .filter(|count| *count != 0);
+                ChunkLayout {
+                    tag: String::from_utf8_lossy(&chunk.kind).into_owned(),
+                    offset: chunk.data_offset,
+                    length: chunk.data.len(),
+                    record_size: record_count
+                        .map(|count| chunk.data.len() / count),
+                    record_count,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "json")]
+/// Converted physics, with extra information about where they came from:
+/// the layout of the chunks (`--offsets`) and the name tables used to name
+/// things (`--embed-namedb`).
+#[derive(Serialize)]
+pub struct Annotated<'a, T> {
+    #[serde(rename = "_layout", skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Vec<ChunkLayout>>,
+    #[serde(rename = "_namedb", skip_serializing_if = "Option::is_none")]
+    pub namedb: Option<BTreeMap<&'static str, &'a [Option<String>]>>,
+    #[serde(flatten)]
+    pub physics: &'a T,
+}
+
+#[cfg(feature = "json")]
+/// Which kind of physics file to export a JSON Schema for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SchemaKind {
+    M1,
+    M2,
+}
+
+#[cfg(feature = "json")]
+/// Writes a JSON Schema describing the output of a convert subcommand to
+/// stdout.
+pub fn export_schema(kind: SchemaKind) -> anyhow::Result<()> {
+    let schema = match kind {
+        SchemaKind::M1 => schemars::schema_for!(m1::Physics),
+        SchemaKind::M2 => schemars::schema_for!(m2::Physics),
+    };
+    let mut out = stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &schema)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// One of the definition tables in a physics file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Table {
+    Monsters,
+    Effects,
+    Projectiles,
+    Weapons,
+    Physics,
+}
+
+#[cfg(feature = "json")]
+/// Options shared by all the convert subcommands.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+pub struct ConvertOptions {
+    /// Path to a directory containing files like "monster_names.txt",
+    /// "projectile_names.txt", etc. These files contain one name per line
+    /// (with blank lines indicating gaps in the naming). A line like
+    /// "7: Hunter" gives a name to a specific index, and the lines after it
+    /// carry on from there. Weapon classes have built-in names, which
+    /// "weapon_class_names.txt" overrides if present.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub namedb: Option<PathBuf>,
+    /// Print which name files were found in the namedb directory, and how
+    /// many names each had, to stderr.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub list_names: bool,
+    /// Take the names for one name table (e.g. "monster_names") from a
+    /// `STR#` string list chunk in the physics file itself, instead of from
+    /// the namedb directory. Follow the table with ":N" to use the Nth `STR#`
+    /// chunk (counting from 0) instead of the first. May be given more than
+    /// once.
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "TABLE[:N]",
+            value_parser = parse_table_and_chunk,
+        )
+    )]
+    pub names_from_chunk: Vec<(String, usize)>,
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub names: NameStyle,
+    /// Only parse and output the given table. May be given more than once.
+    /// If not given, all tables are output.
+    #[cfg_attr(feature = "cli", arg(long, value_enum))]
+    pub only: Vec<Table>,
+    /// When only one table is selected with `--only`, output that table by
+    /// itself instead of wrapping it in an object.
+    #[cfg_attr(feature = "cli", arg(long, requires = "only"))]
+    pub bare: bool,
+    /// The format to write the output in.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, default_value_t = Format::Json)
+    )]
+    pub format: Format,
+    /// Write the output to this file instead of stdout.
+    #[cfg_attr(feature = "cli", arg(long, short))]
+    pub output: Option<PathBuf>,
+    /// Write the output for each input into this directory, named after the
+    /// input (e.g. "Foo.phyA" becomes "Foo.json"). Required when converting
+    /// more than one file.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "output"))]
+    pub output_dir: Option<PathBuf>,
+    /// How many spaces to indent each level of JSON output by.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, default_value_t = 2, conflicts_with = "tabs")
+    )]
+    pub indent: usize,
+    /// Indent JSON output with tabs instead of spaces.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tabs: bool,
+    /// Add a "_layout" key listing where each chunk is in the file, and the
+    /// size and count of the records in each table. Record #n of a table
+    /// starts at `offset + n * record_size`.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "bare"))]
+    pub offsets: bool,
+    /// Add a "_namedb" key containing every name table that was used, so
+    /// that whoever reads the output can tell which names the indices map
+    /// to.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "bare"))]
+    pub embed_namedb: bool,
+    /// Sort the keys of every object, and sort friend/enemy and
+    /// immunity/weakness lists by name (unnamed entries last, by index).
+    /// With --bit-objects, those lists stay sorted by bit instead. Useful for
+    /// diffing the output of two revisions of a physics file.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub sort_keys: bool,
+    /// Use Aleph One's names for fields whose names here differ from its
+    /// (mostly Bungie's originals, misspellings and all), e.g. "is_kamakazi"
+    /// instead of "kamikaze" and "hit_shapes" instead of "hit_sequence".
+    /// The output can't be assembled or used as a patch. Can't be used with
+    /// `--group-shapes`, since Aleph One calls sequences "shape" too.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "group_shapes"))]
+    pub mml_names: bool,
+    /// Hoist the fields of nested damage, attack and trigger definitions
+    /// into the definition that holds them, with dotted keys, e.g.
+    /// "shrapnel_damage.base" and "triggers.0.rounds_per_magazine" instead
+    /// of objects within objects. Like --mml-names, the output can't be
+    /// assembled or used as a patch.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "mml_names"))]
+    pub flatten: bool,
+    /// Instead of one output file, write each definition to its own file in
+    /// this directory, e.g. "monsters/Hunter.json", named after the
+    /// definition (or its index, if it has no name). Each file gets an
+    /// "index" key saying where the definition goes. Files already in the
+    /// directory are overwritten, but never deleted.
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            conflicts_with_all = ["output", "bare", "offsets", "embed_namedb"],
+        )
+    )]
+    pub explode: Option<PathBuf>,
+    /// Path to a Marathon 2 or Infinity shapes file. Every sequence index is
+    /// checked against the number of sequences in its collection, and a
+    /// warning is printed for each one that doesn't exist.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub shapes: Option<PathBuf>,
+    /// Check every reference from one definition to another (impact and
+    /// detonation effects, attack and trigger projectiles, projectile
+    /// promotions) against the table it points into, and print a warning for
+    /// each one past the end of that table.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub check_refs: bool,
+    /// Refuse to read a table with more than this many records. The largest
+    /// tables in real physics files have a few dozen; a corrupt chunk length
+    /// can make for millions.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub max_records: Option<usize>,
+    /// List every chunk in the physics file on stderr, with its size and
+    /// whether it was used or ignored. Only the definition tables (and any
+    /// `STR#` chunks given to `--names-from-chunk`) are used; anything else
+    /// a mod has put in the file is ignored.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub report_chunks: bool,
+}
+
+#[cfg(feature = "json")]
+/// One definition, with its index, for `--explode`.
+#[derive(Serialize)]
+pub struct Indexed<'a, T> {
+    pub index: usize,
+    #[serde(flatten)]
+    pub definition: &'a T,
+}
+
+#[cfg(feature = "json")]
+/// Turns a name into something safe to use as a file name.
+fn sanitize_file_name(name: &str) -> String {
+    let ret: String = name
+        .chars()
+        .map(|x| match x {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            x if x.is_control() => '_',
+            x => x,
+        })
+        .collect();
+    match ret.strip_prefix('.') {
+        Some(rest) => format!("_{rest}"),
+        None => ret,
+    }
+}
+
+#[cfg(feature = "json")]
+/// Writes each definition of a table to its own file in `dir`, for
+/// `--explode`. Files are named after the definitions' names, or their
+/// indices if they have none. If two definitions have the same name, the
+/// later ones get their index added to their file names.
+pub fn explode_table<T: Serialize>(
+    dir: &Path,
+    definitions: &[T],
+    name: impl Fn(&T) -> &Name,
+    options: &ConvertOptions,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("unable to create {dir:?}"))?;
+    let mut taken = HashSet::new();
+    for (index, definition) in definitions.iter().enumerate() {
+        let stem = match name(definition) {
+            Name::Named(name) => sanitize_file_name(name),
+            _ => index.to_string(),
+        };
+        let stem = if taken.insert(stem.to_lowercase()) {
+            stem
+        } else {
+            format!("{stem} #{index}")
+        };
+        write_exploded(dir, &stem, &Indexed { index, definition }, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+/// Writes one file for `--explode`.
+pub fn write_exploded(
+    dir: &Path,
+    stem: &str,
+    value: &impl Serialize,
+    options: &ConvertOptions,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("unable to create {dir:?}"))?;
+    // not `set_extension`, since names can have dots in them
+    let output = dir.join(format!("{stem}.{}", options.format.extension()));
+    let options = ConvertOptions {
+        output: Some(output),
+        ..options.clone()
+    };
+    write_output(value, &options)
+}
+
+#[cfg(feature = "json")]
+/// What each definition table is called when it's split out into its own
+/// directory (by `--explode`) or file (by `--format csv`), and its key in
+/// the whole physics.
+pub const TABLE_FILE_NAMES: [(&str, &str); 4] = [
+    ("monsters", "monster_definitions"),
+    ("effects", "effect_definitions"),
+    ("projectiles", "projectile_definitions"),
+    ("weapons", "weapon_definitions"),
+];
+
+#[cfg(feature = "json")]
+/// An output format for converted physics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Format {
+    /// Pretty-printed JSON.
+    Json,
+    /// MessagePack, with structs encoded as maps so that field names are
+    /// kept.
+    Msgpack,
+    /// CBOR.
+    Cbor,
+    /// One CSV file per table, e.g. "monsters.csv", written into the
+    /// directory given by `--output-dir`. Nested fields become dotted
+    /// columns, and lists are joined with semicolons.
+    Csv,
+    /// Aleph One's MML (XML), with an element for each definition and
+    /// physics model, and Aleph One's names for the fields. Every reference
+    /// is written as its index, whatever the name options say, and flags and
+    /// bitfield lists as numbers.
+    Mml,
+}
+
+#[cfg(feature = "json")]
+impl Format {
+    /// The file extension to use for output in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Msgpack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Csv => "csv",
+            Format::Mml => "mml",
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl ConvertOptions {
+    /// How to identify things in the output. MML always gets bare indices,
+    /// since that's all Aleph One understands.
+    pub fn name_style(&self) -> NameStyle {
+        if self.format == Format::Mml {
+            NameStyle {
+                raw_ids: true,
+                ..Default::default()
+            }
+        } else {
+            self.names
+        }
+    }
+    /// Pairs up each input path with the options to convert it with. With
+    /// `--output-dir`, each gets its own `--output` inside that directory.
+    pub fn per_input(
+        &self,
+        paths: &[PathBuf],
+    ) -> anyhow::Result<Vec<(PathBuf, ConvertOptions)>> {
+        if self.explode.is_some() && paths.len() > 1 {
+            return Err(anyhow!("--explode only works on one file at a time"));
+        }
+        if self.format == Format::Csv {
+            return self.per_input_csv(paths);
+        }
+        if self.format == Format::Mml
+            && (self.bare
+                || self.explode.is_some()
+                || self.names.group_shapes
+                || self.names.named_triggers
+                || self.flatten)
+        {
+            return Err(anyhow!(
+                "--format mml only writes whole physics files, and can't be \
+                 used with --bare, --explode, --group-shapes, \
+                 --named-triggers or --flatten"
+            ));
+        }
+        let Some(output_dir) = self.output_dir.as_ref() else {
+            return match paths {
+                [path] => Ok(vec![(path.clone(), self.clone())]),
+                _ => Err(anyhow!(
+                    "converting more than one file requires --output-dir"
+                )),
+            };
+        };
+        paths
+            .iter()
+            .map(|path| {
+                let stem = path.file_stem().ok_or_else(|| {
+                    anyhow!("can't make an output name for {path:?}")
+                })?;
+                let mut output = output_dir.join(stem);
+                output.set_extension(self.format.extension());
+                Ok((
+                    path.clone(),
+                    ConvertOptions {
+                        output: Some(output),
+                        ..self.clone()
+                    },
+                ))
+            })
+            .collect()
+    }
+    /// `per_input` for `--format csv`, where the output is a directory: the
+    /// `--output-dir` itself for one input, or a directory in it named after
+    /// each input for more than one.
+    fn per_input_csv(
+        &self,
+        paths: &[PathBuf],
+    ) -> anyhow::Result<Vec<(PathBuf, ConvertOptions)>> {
+        if self.bare || self.explode.is_some() {
+            return Err(anyhow!(
+                "--format csv always writes one file per table, so it can't \
+                 be used with --bare or --explode"
+            ));
+        }
+        let Some(output_dir) = self.output_dir.as_ref() else { return Err(anyhow!("--format csv requires --output-dir")) };
+        paths
+            .iter()
+            .map(|path| {
+                let output = match paths {
+                    [_] => output_dir.clone(),
+                    _ => {
+                        output_dir.join(path.file_stem().ok_or_else(|| {
+                            anyhow!("can't make an output name for {path:?}")
+                        })?)
+                    }
+                };
+                Ok((
+                    path.clone(),
+                    ConvertOptions {
+                        output: Some(output),
+                        ..self.clone()
+                    },
+                ))
+            })
+            .collect()
+    }
+    /// Checks sequence indices against the shapes file, if one was given.
+    pub fn check_shapes(
+        &self,
+        physics: &impl Serialize,
+        namedbs: &NameDbs,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<()> {
+        let Some(path) = &self.shapes else { return Ok(()) };
+        let shapes = Shapes::open(path, diagnostics)?;
+        check_sequences(
+            &serde_json::to_value(physics)?,
+            &shapes,
+            namedbs,
+            diagnostics,
+        );
+        Ok(())
+    }
+    /// Checks references between definitions, if `--check-refs` was given.
+    pub fn check_references(
+        &self,
+        physics: &impl Serialize,
+        namedbs: &NameDbs,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<()> {
+        if self.check_refs {
+            check_references(
+                &serde_json::to_value(physics)?,
+                namedbs,
+                diagnostics,
+            );
+        }
+        Ok(())
+    }
+    /// Loads any name tables that `--names-from-chunk` says to take from the
+    /// physics file's own `STR#` chunks, with a note for each with
+    /// `--list-names`.
+    pub fn load_chunk_names(
+        &self,
+        namedbs: &mut NameDbs,
+        chunks: &[Chunk],
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<()> {
+        for (table, chunk) in &self.names_from_chunk {
+            namedbs.load_string_list(table, chunks, *chunk)?;
+            if self.list_names {
+                diagnostics.note(
+                    WarningKind::NameSource,
+                    format_args!(
+                        "{table}.txt: {} names, from STR# chunk #{chunk}",
+                        namedbs.table(table).map_or(0, NameDb::count)
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+    /// With `--report-chunks`, gives a note for every chunk of the physics
+    /// file, with its size and whether the conversion used it: the first
+    /// chunk with each of the tags in `record_counts` (as for
+    /// `ChunkLayout::describe`) that records were read from, and the `STR#`
+    /// chunks that `--names-from-chunk` took names from.
+    pub fn report_chunks(
+        &self,
+        chunks: &[Chunk],
+        record_counts: &[([u8; 4], Option<usize>)],
+        diagnostics: &Diagnostics,
+    ) {
+        if !self.report_chunks {
+            return;
+        }
+        let mut seen = vec![];
+        let mut string_lists = 0;
+        for chunk in chunks {
+            let tag = String::from_utf8_lossy(&chunk.kind);
+            let used = if chunk.kind == STRING_LIST_TAG {
+                string_lists += 1;
+                let tables: Vec<&str> = self
+                    .names_from_chunk
+                    .iter()
+                    .filter(|(_, index)| *index == string_lists - 1)
+                    .map(|(table, _)| table.as_str())
+                    .collect();
+                (!tables.is_empty())
+                    .then(|| format!("names for {}", tables.join(", ")))
+            } else if seen.contains(&chunk.kind) {
+                None
+            } else {
+                seen.push(chunk.kind);
+                record_counts
+                    .iter()
+                    .find(|(tag, _)| *tag == chunk.kind)
+                    .and_then(|(_, count)| *count)
+                    .map(|count| format!("{count} records"))
+            };
+            let message = match used {
+                Some(what) => {
+                    format!(
+                        "{tag} {:8} bytes  used ({what})",
+                        chunk.data.len()
+                    )
+                }
+                None => format!("{tag} {:8} bytes  ignored", chunk.data.len()),
+            };
+            diagnostics.note(WarningKind::ChunkReport, message);
+        }
+    }
+    /// Returns the table to output by itself, if `--bare` was given.
+    pub fn bare_table(&self) -> anyhow::Result<Option<Table>> {
+        if !self.bare {
+            return Ok(None);
+        }
+        match self.only.split_first() {
+            Some((&first, rest)) if rest.iter().all(|x| *x == first) => {
+                Ok(Some(first))
+            }
+            _ => Err(anyhow!("--bare requires exactly one --only table")),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+/// Writes converted output to stdout, or to the `--output` file, in the
+/// requested format.
+pub fn write_output(
+    value: &impl Serialize,
+    options: &ConvertOptions,
+) -> anyhow::Result<()> {
+    if options.sort_keys || options.mml_names || options.flatten {
+        // We go by way of the JSON text, rather than `to_value`, so that
+        // f32s come back out with the same digits.
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&serde_json::to_vec(value)?)?;
+        if options.mml_names {
+            use_mml_names(&mut value);
+        }
+        if options.flatten {
+            flatten_nested_definitions(&mut value);
+        }
+        if options.sort_keys {
+            value.sort_all_objects();
+            sort_name_lists(&mut value);
+        }
+        write_output_unsorted(&value, options)
+    } else {
+        write_output_unsorted(value, options)
+    }
+}
+
+#[cfg(feature = "json")]
+/// Sorts every list of identified bits (names first, alphabetically, then
+/// unnamed indices in order). `--bit-objects` lists are sorted by bit, so
+/// that renaming a class doesn't move it.
+fn sort_name_lists(value: &mut serde_json::Value) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (key.as_str(), value) {
+                    (
+                        "friends" | "enemies" | "immunities" | "weaknesses",
+                        Value::Array(list),
+                    ) if list.iter().all(|x| x.get("bit").is_some()) => {
+                        list.sort_by_key(|x| x["bit"].as_u64())
+                    }
+                    (
+                        "friends" | "enemies" | "immunities" | "weaknesses",
+                        Value::Array(list),
+                    ) => list.sort_by_key(|x| {
+                        // a name, a bare index, or an `--unnamed object`
+                        let name = x.as_str().or_else(|| x["name"].as_str());
+                        let index = x
+                            .as_u64()
+                            .or_else(|| x["index"].as_u64())
+                            .unwrap_or(0);
+                        (name.is_none(), name.map(str::to_owned), index)
+                    }),
+                    (_, value) => sort_name_lists(value),
+                }
+            }
+        }
+        Value::Array(list) => list.iter_mut().for_each(sort_name_lists),
+        _ => (),
+    }
+}
+
+#[cfg(feature = "json")]
+/// The keys that hold a damage or attack definition, or an array of trigger
+/// definitions.
+const NESTED_DEFINITION_KEYS: [&str; 5] = [
+    "shrapnel_damage",
+    "melee_attack",
+    "ranged_attack",
+    "damage",
+    "triggers",
+];
+
+#[cfg(feature = "json")]
+/// For `--flatten`: replaces every nested damage, attack or trigger
+/// definition with its fields, hoisted into the definition that holds it.
+fn flatten_nested_definitions(value: &mut serde_json::Value) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            let mut flattened = serde_json::Map::new();
+            for (key, mut value) in std::mem::take(map) {
+                if NESTED_DEFINITION_KEYS.contains(&key.as_str()) {
+                    hoist(&key, value, &mut flattened);
+                } else {
+                    flatten_nested_definitions(&mut value);
+                    flattened.insert(key, value);
+                }
+            }
+            *map = flattened;
+        }
+        Value::Array(list) => {
+            list.iter_mut().for_each(flatten_nested_definitions)
+        }
+        _ => (),
+    }
+}
+
+#[cfg(feature = "json")]
+/// Inserts `value` into `out` under `key`, or, if it's an object or an
+/// array of objects, each of its fields (or elements) under a dotted key,
+/// recursively. Names (with `--unnamed object` or `--bit-objects`) stay as
+/// they are.
+fn hoist(
+    key: &str,
+    value: serde_json::Value,
+    out: &mut serde_json::Map<String, serde_json::Value>,
+) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) if !is_name_object(&map) => {
+            for (field, value) in map {
+                hoist(&format!("{key}.{field}"), value, out);
+            }
+        }
+        Value::Array(list)
+            if !list.is_empty()
+                && list.iter().all(|x| {
+                    x.as_object().is_some_and(|x| !is_name_object(x))
+                }) =>
+        {
+            for (index, value) in list.into_iter().enumerate() {
+                hoist(&format!("{key}.{index}"), value, out);
+            }
+        }
+        value => {
+            out.insert(key.to_string(), value);
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn write_output_unsorted(
+    value: &impl Serialize,
+    options: &ConvertOptions,
+) -> anyhow::Result<()> {
+    if options.format == Format::Csv {
+        // `per_input` made sure there's an output directory
+        let dir = options.output.as_deref().unwrap_or(Path::new("."));
+        return write_csv_tables(&serde_json::to_value(value)?, dir);
+    }
+    let mut out: Box<dyn Write> = match options.output.as_ref() {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("unable to create {path:?}"))?,
+        )),
+        None => {
+            if !matches!(options.format, Format::Json | Format::Mml)
+                && stdout().is_terminal()
+            {
+                return Err(anyhow!("refusing to write binary output to a terminal, use --output"));
+            }
+            Box::new(stdout().lock())
+        }
+    };
+    match options.format {
+        Format::Json => {
+            let indent = if options.tabs {
+                b"\t".to_vec()
+            } else {
+                vec![b' '; options.indent]
+            };
+            let formatter = PrettyFormatter::with_indent(&indent);
+            let mut serializer =
+                serde_json::Serializer::with_formatter(&mut out, formatter);
+            value.serialize(&mut serializer)?
+        }
+        Format::Msgpack => rmp_serde::encode::write_named(&mut out, value)?,
+        Format::Cbor => ciborium::into_writer(value, &mut out)?,
+        Format::Mml => {
+            // by way of the JSON text, as in `write_output`
+            let value = serde_json::from_slice(&serde_json::to_vec(value)?)?;
+            write_mml(&value, &mut out)?
+        }
+        Format::Csv => unreachable!(),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Runs five independent jobs and returns their results in order. With the
+/// `parallel` feature, they run on rayon's thread pool. (Any warnings they
+/// print may then come out in any order, but the results never do.)
+pub fn join5<A: Send, B: Send, C: Send, D: Send, E: Send>(
+    a: impl FnOnce() -> A + Send,
+    b: impl FnOnce() -> B + Send,
+    c: impl FnOnce() -> C + Send,
+    d: impl FnOnce() -> D + Send,
+    e: impl FnOnce() -> E + Send,
+) -> (A, B, C, D, E) {
+    #[cfg(feature = "parallel")]
+    {
+        let ((a, b), ((c, d), e)) = rayon::join(
+            || rayon::join(a, b),
+            || rayon::join(|| rayon::join(c, d), e),
+        );
+        (a, b, c, d, e)
+    }
+    #[cfg(not(feature = "parallel"))]
+    (a(), b(), c(), d(), e())
+}
+
+/// Reads the given table, unless it was filtered out by `only`. An empty
+/// `only` lets every table through.
+pub fn read_table<T>(
+    only: &[Table],
+    table: Table,
+    chunks: &[Chunk],
+    kind: [u8; 4],
+    diagnostics: &Diagnostics,
+    read: impl FnOnce(&[u8]) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<T>> {
+    if only.is_empty() || only.contains(&table) {
+        read_optional_table(chunks, kind, diagnostics, read)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reads the table stored in the chunk of the given kind. If there is no such
+/// chunk, prints a warning and returns `None` instead of failing; some physics
+/// files legitimately omit tables.
+pub fn read_optional_table<T>(
+    chunks: &[Chunk],
+    kind: [u8; 4],
+    diagnostics: &Diagnostics,
+    read: impl FnOnce(&[u8]) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<T>> {
+    match Chunk::find_optional(chunks, kind) {
+        Some(data) => read(data).map(Some),
+        None => {
+            diagnostics.warn(
+                WarningKind::MissingChunk,
+                format_args!(
+                    "no {:?} chunk found, omitting that table",
+                    String::from_utf8_lossy(&kind)
+                ),
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// The name of the field a record's reader was reading when it failed, as
+/// added to the error with `.context(Field("speed"))`. Nested structures
+/// (attacks, triggers...) are named as a whole.
+#[derive(Debug)]
+pub struct Field(pub &'static str);
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "field {:?}", self.0)
+    }
+}
+
+/// Splits a chunk into fixed-size records and reads each one. Every record
+/// must be consumed exactly by `read`; if it isn't, our idea of the record
+/// layout is wrong and every field after the mismatch would be garbage. If
+/// there are more than `max_records` records, none of them are read. An
+/// error from `read` is wrapped with where in the chunk it happened, and
+/// which record it was in; the field it was in comes from `read` (see
+/// `Field`).
+pub fn read_records<T>(
+    input: &[u8],
+    kind: [u8; 4],
+    record_size: usize,
+    what: &str,
+    max_records: Option<usize>,
+    diagnostics: &Diagnostics,
+    mut read: impl FnMut(
+        &mut CountingReader<&[u8]>,
+        usize,
+        &Diagnostics,
+    ) -> anyhow::Result<T>,
+) -> anyhow::Result<Vec<T>> {
+    let tag = kind;
+    let kind = String::from_utf8_lossy(&kind);
+    let diagnostics = diagnostics.at(format_args!("{kind:?} chunk"));
+    let remainder = input.len() % record_size;
+    let (input, padding) = input.split_at(input.len() - remainder);
+    if padding.iter().any(|x| *x != 0) {
+        return Err(PhysicsEaterError::BadRecordSize {
+            kind: tag,
+            what: what.to_string(),
+            length: input.len() + remainder,
+            record_size,
+        }
+        .into());
+    } else if remainder != 0 {
+        // Some tools pad chunks out to an alignment boundary.
+        diagnostics.warn(
+            WarningKind::TrailingBytes,
+            format_args!(
+                "ignoring {remainder} trailing zero bytes after the last \
+                 {what}"
+            ),
+        );
+    }
+    let count = input.len() / record_size;
+    if let Some(max) = max_records.filter(|max| count > *max) {
+        return Err(PhysicsEaterError::TooManyRecords {
+            kind: tag,
+            what: what.to_string(),
+            count,
+            max,
+        }
+        .into());
+    }
+    input
+        .chunks_exact(record_size)
+        .enumerate()
+        .map(|(i, record)| {
+            let mut record = CountingReader::new(record);
+            let diagnostics = diagnostics.at(format_args!("{what} #{i}"));
+            let ret = read(&mut record, i, &diagnostics).with_context(|| {
+                let parsed = record.bytes_read();
+                format!(
+                    "{kind:?} chunk: at offset 0x{:X} (byte {parsed} of the record) while reading {what} #{i}",
+                    i * record_size + parsed,
+                )
+            })?;
+            let parsed = record.bytes_read();
+            if parsed != record_size {
+                return Err(PhysicsEaterError::RecordNotFullyParsed {
+                    kind: tag,
+                    what: what.to_string(),
+                    index: i,
+                    record_size,
+                    parsed,
+                }
+                .into());
+            }
+            Ok(ret)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+/// For the record layout tests in `m1` and `m2`: numbers each 16-bit word of
+/// a record from 1, so that a field read from the wrong offset shows up as
+/// the wrong number.
+fn numbered_record(size: usize) -> Vec<u8> {
+    (1..=size as u16 / 2).flat_map(u16::to_be_bytes).collect()
+}
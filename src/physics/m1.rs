@@ -17,11 +17,13 @@
 
 use super::*;
 
-use std::{fs::File, io::Read};
+use std::io::{Read, Seek};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use schemars::JsonSchema;
+#[cfg(feature = "json")]
 use serde::Serialize;
-use serde_json::Value;
 
 pub const MONSTER_PHYSICS_TAG: [u8; 4] = *b"mons";
 pub const EFFECT_PHYSICS_TAG: [u8; 4] = *b"effe";
@@ -29,42 +31,92 @@ pub const PROJECTILE_PHYSICS_TAG: [u8; 4] = *b"proj";
 pub const PHYSICS_PHYSICS_TAG: [u8; 4] = *b"phys";
 pub const WEAPON_PHYSICS_TAG: [u8; 4] = *b"weap";
 
-#[derive(Serialize)]
-struct MonsterFlags {
-    omniscient: bool,
-    flies: bool,
-    is_alien: bool,
-    major: bool,
-    minor: bool,
-    cannot_skip: bool,
-    floats: bool,
-    cannot_attack: bool,
-    uses_sniper_ledges: bool,
-    is_invisible: bool,
-    is_subtly_invisible: bool,
-    kamikaze: bool, // misspelled grievously in the original source
-    berserker: bool,
-    enlarged: bool,
-    delayed_hard_death: bool,
-    fires_symmetrically: bool,
-    nuclear_hard_death: bool,
-    cannot_fire_backwards: bool,
-    can_die_in_flames: bool,
-    waits_with_clear_shot: bool,
-    tiny: bool,
-    attacks_immediately: bool,
-    not_afraid_of_water: bool,
-    not_afraid_of_sewage: bool,
-    not_afraid_of_lava: bool,
-    not_afraid_of_goo: bool,
-    can_teleport_under_media: bool,
-    chooses_weapons_randomly: bool,
+/// Every tag that M1 physics are kept under.
+pub const PHYSICS_TAGS: [[u8; 4]; 5] = [
+    MONSTER_PHYSICS_TAG,
+    EFFECT_PHYSICS_TAG,
+    PROJECTILE_PHYSICS_TAG,
+    PHYSICS_PHYSICS_TAG,
+    WEAPON_PHYSICS_TAG,
+];
+
+/// Returns the chunks of the first subfile of a WAD that has any M1 physics
+/// in it, for M1 physics that were packaged in a WAD instead of as bare
+/// chunks.
+pub fn find_physics_subfile(wad: &Wad) -> Option<&[Chunk]> {
+    wad.files
+        .iter()
+        .find(|chunks| {
+            chunks
+                .iter()
+                .any(|chunk| PHYSICS_TAGS.contains(&chunk.kind))
+        })
+        .map(Vec::as_slice)
+}
+
+/// Reads the chunks of M1 physics, whether they're bare chunks, as Marathon
+/// keeps them, or packaged in a subfile of a WAD.
+pub fn read_chunks(
+    mut input: impl Read + Seek,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<Chunk>> {
+    match detect_physics_kind(&mut input)? {
+        PhysicsKind::M1 => Chunk::read_m1_chunks(input, lenient, diagnostics),
+        PhysicsKind::M2 => {
+            let wad = Wad::read_wad(input, lenient, diagnostics)?;
+            let chunks = find_physics_subfile(&wad).ok_or_else(|| {
+                anyhow!("this WAD doesn't contain any M1 physics")
+            })?;
+            Ok(chunks.to_vec())
+        }
+        PhysicsKind::Unknown(reason) => {
+            Err(PhysicsEaterError::NotPhysics { reason }.into())
+        }
+    }
+}
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct MonsterFlags {
+    pub omniscient: bool,
+    pub flies: bool,
+    pub is_alien: bool,
+    pub major: bool,
+    pub minor: bool,
+    pub cannot_skip: bool,
+    pub floats: bool,
+    pub cannot_attack: bool,
+    pub uses_sniper_ledges: bool,
+    pub is_invisible: bool,
+    pub is_subtly_invisible: bool,
+    pub kamikaze: bool, // misspelled grievously in the original source
+    pub berserker: bool,
+    pub enlarged: bool,
+    pub delayed_hard_death: bool,
+    pub fires_symmetrically: bool,
+    pub nuclear_hard_death: bool,
+    pub cannot_fire_backwards: bool,
+    pub can_die_in_flames: bool,
+    pub waits_with_clear_shot: bool,
+    pub tiny: bool,
+    pub attacks_immediately: bool,
+    pub not_afraid_of_water: bool,
+    pub not_afraid_of_sewage: bool,
+    pub not_afraid_of_lava: bool,
+    pub not_afraid_of_goo: bool,
+    pub can_teleport_under_media: bool,
+    pub chooses_weapons_randomly: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u32,
 }
 
 impl MonsterFlags {
-    pub fn read(input: impl Read) -> anyhow::Result<MonsterFlags> {
+    pub fn read(
+        input: impl Read,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<MonsterFlags> {
         Ok(decode_flags!(
-            read32(input)? => MonsterFlags {
+            diagnostics, read32(input)? => MonsterFlags {
                 omniscient,
                 flies,
                 is_alien,
@@ -98,36 +150,42 @@ impl MonsterFlags {
     }
 }
 
-#[derive(Serialize)]
-struct DamageDefinitionFlags {
-    alien_damage: bool,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct DamageDefinitionFlags {
+    pub alien_damage: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
 }
 
 impl DamageDefinitionFlags {
-    pub fn read(input: impl Read) -> anyhow::Result<DamageDefinitionFlags> {
+    pub fn read(
+        input: impl Read,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<DamageDefinitionFlags> {
         Ok(
-            decode_flags!(read16(input)? => DamageDefinitionFlags { alien_damage }),
+            decode_flags!(diagnostics, read16(input)? => DamageDefinitionFlags { alien_damage }),
         )
     }
 }
 
-#[derive(Serialize)]
-struct DamageDefinition {
-    damage_type: Option<Value>,
-    flags: DamageDefinitionFlags,
-    base: i16,
-    random: i16,
-    scale: f32,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct DamageDefinition {
+    pub damage_type: Option<Name>,
+    pub flags: DamageDefinitionFlags,
+    pub base: i16,
+    pub random: i16,
+    pub scale: f32,
 }
 
 impl DamageDefinition {
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<DamageDefinition> {
         let damage_type = read_optional_16(&mut input)?
             .map(|x| namedbs.damage_type_names.identify(x));
-        let flags = DamageDefinitionFlags::read(&mut input)?;
+        let flags = DamageDefinitionFlags::read(&mut input, diagnostics)?;
         let base = read16(&mut input)? as i16;
         let random = read16(&mut input)? as i16;
         let scale = read_fx_16_16(&mut input)?;
@@ -141,9 +199,9 @@ impl DamageDefinition {
     }
 }
 
-#[derive(Serialize)]
-struct AttackDefinition {
-    pub projectile_type: Value,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct AttackDefinition {
+    pub projectile_type: Name,
     pub repetitions: Option<u16>,
     pub error: f32,
     pub range: f32,
@@ -180,33 +238,40 @@ impl AttackDefinition {
     }
 }
 
-#[derive(Serialize)]
-struct MonsterDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    pub name: Value,
-    pub collection: Option<Value>,
-    pub clut: Option<u16>,
-    pub vitality: Option<u16>,
-    pub immunities: Vec<Value>,
-    pub weaknesses: Vec<Value>,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct MonsterDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    // a plain hit point count; NONE has no special meaning here
+    pub vitality: i16,
+    pub immunities: Vec<Name>,
+    pub weaknesses: Vec<Name>,
     pub flags: MonsterFlags,
-    pub class: Option<Value>,
-    pub friends: Vec<Value>,
-    pub enemies: Vec<Value>,
-    pub activation_sound: Option<Value>,
-    pub conversation_sound: Option<Value>,
-    pub flaming_sound: Option<Value>,
-    pub random_sound: Option<Value>,
-    pub random_sound_mask: Option<u16>,
-    pub carrying_item_type: Option<Value>,
+    pub class: Option<Name>,
+    pub friends: Vec<Name>,
+    pub enemies: Vec<Name>,
+    pub activation_sound: Option<Name>,
+    pub conversation_sound: Option<Name>,
+    pub flaming_sound: Option<Name>,
+    pub random_sound: Option<Name>,
+    // ANDed with a random number to decide whether to make the random
+    // sound; a mask, so every bit pattern means something, and there's no
+    // NONE
+    pub random_sound_mask: u16,
+    pub carrying_item_type: Option<Name>,
     pub radius: f32,
     pub height: f32,
     pub preferred_hover_height: f32,
     pub minimum_ledge_delta: f32,
     pub maximum_ledge_delta: f32,
     pub external_velocity_scale: f32,
-    pub impact_effect: Option<Value>,
-    pub melee_impact_effect: Option<Value>,
+    pub impact_effect: Option<Name>,
+    pub melee_impact_effect: Option<Name>,
     pub half_visual_arc: f32,
     pub half_vertical_visual_arc: f32,
     pub visual_range: f32,
@@ -215,7 +280,9 @@ struct MonsterDefinition {
     pub speed: f32,
     pub gravity: f32,
     pub terminal_velocity: f32,
-    pub door_retry_mask: Option<u16>,
+    // ANDed with a random number to decide whether to try a door again;
+    // a mask, like random_sound_mask, with no NONE
+    pub door_retry_mask: u16,
     pub shrapnel_radius: Option<f32>,
     pub shrapnel_damage: DamageDefinition,
     // these are marked as shape descriptors in the code, but they're actually
@@ -236,144 +303,195 @@ impl MonsterDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<MonsterDefinition>> {
         const SIZE_OF_MONSTER_DEFINITION: usize = 138;
-        if input.len() % SIZE_OF_MONSTER_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of monster definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_MONSTER_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| MonsterDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            MONSTER_PHYSICS_TAG,
+            SIZE_OF_MONSTER_DEFINITION,
+            "monster definition",
+            max_records,
+            diagnostics,
+            |x, i, d| MonsterDefinition::read(x, namedbs, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<MonsterDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
         Ok(MonsterDefinition {
-            name: namedbs.monster_names.identify(index),
-            collection,
-            clut,
-            vitality: read_optional_16(&mut input)?,
-            immunities: read_generic_bitfield32(&mut input)?
+            name: namedbs.monster_names.identify_definition(index),
+            shape,
+            vitality: read16(&mut input).context(Field("vitality"))? as i16,
+            immunities: read_generic_bitfield32(&mut input)
+                .context(Field("immunities"))?
                 .into_iter()
-                .map(|x| namedbs.damage_type_names.identify(x))
+                .map(|x| namedbs.damage_type_names.identify_bit(x))
                 .collect(),
-            weaknesses: read_generic_bitfield32(&mut input)?
+            weaknesses: read_generic_bitfield32(&mut input)
+                .context(Field("weaknesses"))?
                 .into_iter()
-                .map(|x| namedbs.damage_type_names.identify(x))
+                .map(|x| namedbs.damage_type_names.identify_bit(x))
                 .collect(),
-            flags: MonsterFlags::read(&mut input)?,
-            class: read_optional_32(&mut input)?
+            flags: MonsterFlags::read(&mut input, diagnostics)
+                .context(Field("flags"))?,
+            class: read_optional_32(&mut input)
+                .context(Field("class"))?
                 .map(|x| namedbs.monster_class_names.identify(x)),
-            friends: read_generic_bitfield32(&mut input)?
+            friends: read_generic_bitfield32(&mut input)
+                .context(Field("friends"))?
                 .into_iter()
-                .map(|x| namedbs.monster_class_names.identify(x))
+                .map(|x| namedbs.monster_class_names.identify_bit(x))
                 .collect(),
-            enemies: read_generic_bitfield32(&mut input)?
+            enemies: read_generic_bitfield32(&mut input)
+                .context(Field("enemies"))?
                 .into_iter()
-                .map(|x| namedbs.monster_class_names.identify(x))
+                .map(|x| namedbs.monster_class_names.identify_bit(x))
                 .collect(),
-            activation_sound: read_optional_16(&mut input)?
+            activation_sound: read_optional_16(&mut input)
+                .context(Field("activation_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            conversation_sound: read_optional_16(&mut input)?
+            conversation_sound: read_optional_16(&mut input)
+                .context(Field("conversation_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            flaming_sound: read_optional_16(&mut input)?
+            flaming_sound: read_optional_16(&mut input)
+                .context(Field("flaming_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            random_sound: read_optional_16(&mut input)?
+            random_sound: read_optional_16(&mut input)
+                .context(Field("random_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            random_sound_mask: read_optional_16(&mut input)?,
-            carrying_item_type: read_optional_16(&mut input)?
+            random_sound_mask: read16(&mut input)
+                .context(Field("random_sound_mask"))?,
+            carrying_item_type: read_optional_16(&mut input)
+                .context(Field("carrying_item_type"))?
                 .map(|x| namedbs.item_names.identify(x)),
-            radius: read_world_distance(&mut input)?,
-            height: read_world_distance(&mut input)?,
-            preferred_hover_height: read_world_distance(&mut input)?,
-            minimum_ledge_delta: read_world_distance(&mut input)?,
-            maximum_ledge_delta: read_world_distance(&mut input)?,
-            external_velocity_scale: read_fx_16_16(&mut input)?,
-            impact_effect: read_optional_16(&mut input)?
+            radius: read_world_distance(&mut input)
+                .context(Field("radius"))?,
+            height: read_world_distance(&mut input)
+                .context(Field("height"))?,
+            preferred_hover_height: read_world_distance(&mut input)
+                .context(Field("preferred_hover_height"))?,
+            minimum_ledge_delta: read_world_distance(&mut input)
+                .context(Field("minimum_ledge_delta"))?,
+            maximum_ledge_delta: read_world_distance(&mut input)
+                .context(Field("maximum_ledge_delta"))?,
+            external_velocity_scale: read_fx_16_16(&mut input)
+                .context(Field("external_velocity_scale"))?,
+            impact_effect: read_optional_16(&mut input)
+                .context(Field("impact_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            melee_impact_effect: read_optional_16(&mut input)?
+            melee_impact_effect: read_optional_16(&mut input)
+                .context(Field("melee_impact_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            half_visual_arc: read_angle(&mut input)?,
-            half_vertical_visual_arc: read_angle(&mut input)?,
-            visual_range: read_world_distance(&mut input)?,
-            dark_visual_range: read_world_distance(&mut input)?,
-            intelligence: read_optional_16(&mut input)?,
-            speed: read_world_speed(&mut input)?,
-            gravity: read_world_accel(&mut input)?,
-            terminal_velocity: read_world_speed(&mut input)?,
-            door_retry_mask: read_optional_16(&mut input)?,
-            shrapnel_radius: read_optional_fx_6_10(&mut input)?,
-            shrapnel_damage: DamageDefinition::read(&mut input, namedbs)?,
-            hit_sequence: read_optional_16(&mut input)?,
-            hard_dying_sequence: read_optional_16(&mut input)?,
-            soft_dying_sequence: read_optional_16(&mut input)?,
-            hard_dead_sequence: read_optional_16(&mut input)?,
-            soft_dead_sequence: read_optional_16(&mut input)?,
-            stationary_sequence: read_optional_16(&mut input)?,
-            moving_sequence: read_optional_16(&mut input)?,
-            attack_frequency: read_optional_16(&mut input)?,
-            melee_attack: AttackDefinition::read(&mut input, namedbs)?,
-            ranged_attack: AttackDefinition::read(&mut input, namedbs)?,
+            half_visual_arc: read_angle(&mut input)
+                .context(Field("half_visual_arc"))?,
+            half_vertical_visual_arc: read_angle(&mut input)
+                .context(Field("half_vertical_visual_arc"))?,
+            visual_range: read_world_distance(&mut input)
+                .context(Field("visual_range"))?,
+            dark_visual_range: read_world_distance(&mut input)
+                .context(Field("dark_visual_range"))?,
+            intelligence: read_optional_16(&mut input)
+                .context(Field("intelligence"))?,
+            speed: read_world_speed(&mut input).context(Field("speed"))?,
+            gravity: read_world_accel(&mut input).context(Field("gravity"))?,
+            terminal_velocity: read_world_speed(&mut input)
+                .context(Field("terminal_velocity"))?,
+            door_retry_mask: read16(&mut input)
+                .context(Field("door_retry_mask"))?,
+            shrapnel_radius: read_optional_fx_6_10(&mut input)
+                .context(Field("shrapnel_radius"))?,
+            shrapnel_damage: DamageDefinition::read(
+                &mut input,
+                namedbs,
+                diagnostics,
+            )
+            .context(Field("shrapnel_damage"))?,
+            hit_sequence: read_optional_16(&mut input)
+                .context(Field("hit_sequence"))?,
+            hard_dying_sequence: read_optional_16(&mut input)
+                .context(Field("hard_dying_sequence"))?,
+            soft_dying_sequence: read_optional_16(&mut input)
+                .context(Field("soft_dying_sequence"))?,
+            hard_dead_sequence: read_optional_16(&mut input)
+                .context(Field("hard_dead_sequence"))?,
+            soft_dead_sequence: read_optional_16(&mut input)
+                .context(Field("soft_dead_sequence"))?,
+            stationary_sequence: read_optional_16(&mut input)
+                .context(Field("stationary_sequence"))?,
+            moving_sequence: read_optional_16(&mut input)
+                .context(Field("moving_sequence"))?,
+            attack_frequency: read_optional_16(&mut input)
+                .context(Field("attack_frequency"))?,
+            melee_attack: AttackDefinition::read(&mut input, namedbs)
+                .context(Field("melee_attack"))?,
+            ranged_attack: AttackDefinition::read(&mut input, namedbs)
+                .context(Field("ranged_attack"))?,
         })
     }
 }
 
-#[derive(Serialize)]
-struct EffectFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct EffectFlags {
     pub end_when_animation_loops: bool,
     pub end_when_transfer_animation_loops: bool,
     pub sound_only: bool,
     pub make_twin_visible: bool, // ????
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
 }
 
-#[derive(Serialize)]
-struct EffectDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    collection: Option<Value>,
-    clut: Option<u16>,
-    sequence: Option<u16>,
-    flags: EffectFlags,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct EffectDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    pub sequence: Option<u16>,
+    pub flags: EffectFlags,
 }
 
 impl EffectDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<EffectDefinition>> {
         const SIZE_OF_EFFECT_DEFINITION: usize = 6;
-        if input.len() % SIZE_OF_EFFECT_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of effect definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_EFFECT_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| EffectDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            EFFECT_PHYSICS_TAG,
+            SIZE_OF_EFFECT_DEFINITION,
+            "effect definition",
+            max_records,
+            diagnostics,
+            |x, i, d| EffectDefinition::read(x, namedbs, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<EffectDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
         Ok(EffectDefinition {
-            name: namedbs.effect_names.identify(index),
-            collection,
-            clut,
-            sequence: read_optional_16(&mut input)?,
-            flags: decode_flags!(read16(&mut input)? => EffectFlags {
+            name: namedbs.effect_names.identify_definition(index),
+            shape,
+            sequence: read_optional_16(&mut input)
+                .context(Field("sequence"))?,
+            flags: decode_flags!(diagnostics, read16(&mut input).context(Field("flags"))? => EffectFlags {
                 end_when_animation_loops,
                 end_when_transfer_animation_loops,
                 sound_only,
@@ -383,8 +501,8 @@ impl EffectDefinition {
     }
 }
 
-#[derive(Serialize)]
-struct ProjectileFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct ProjectileFlags {
     pub guided: bool,
     pub stop_when_animation_loops: bool,
     pub persistent: bool,
@@ -399,69 +517,82 @@ struct ProjectileFlags {
     pub usually_pass_transparent_side: bool,
     pub sometimes_pass_transparent_side: bool,
     pub doubly_affected_by_gravity: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
 }
 
-#[derive(Serialize)]
-struct ProjectileDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    collection: Option<Value>,
-    clut: Option<u16>,
-    sequence: Option<u16>,
-    detonation_effect: Option<Value>,
-    contrail_effect: Option<Value>,
-    ticks_between_contrails: Option<Value>,
-    maximum_contrails: Option<Value>,
-    radius: f32,
-    area_of_effect: f32,
-    damage: DamageDefinition,
-    flags: ProjectileFlags,
-    speed: f32,
-    maximum_range: f32,
-    flyby_sound: Option<Value>,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct ProjectileDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    pub sequence: Option<u16>,
+    pub detonation_effect: Option<Name>,
+    pub contrail_effect: Option<Name>,
+    pub ticks_between_contrails: Option<Ticks>,
+    /// NONE means no limit.
+    pub maximum_contrails: Option<u16>,
+    pub radius: f32,
+    pub area_of_effect: f32,
+    pub damage: DamageDefinition,
+    pub flags: ProjectileFlags,
+    pub speed: f32,
+    pub maximum_range: f32,
+    pub flyby_sound: Option<Name>,
 }
 
 impl ProjectileDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<ProjectileDefinition>> {
         const SIZE_OF_PROJECTILE_DEFINITION: usize = 36;
-        if input.len() % SIZE_OF_PROJECTILE_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of projectile definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_PROJECTILE_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| ProjectileDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            PROJECTILE_PHYSICS_TAG,
+            SIZE_OF_PROJECTILE_DEFINITION,
+            "projectile definition",
+            max_records,
+            diagnostics,
+            |x, i, d| ProjectileDefinition::read(x, namedbs, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<ProjectileDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
         Ok(ProjectileDefinition {
-            name: namedbs.projectile_names.identify(index),
-            collection,
-            clut,
-            sequence: read_optional_16(&mut input)?,
-            detonation_effect: read_optional_16(&mut input)?
+            name: namedbs.projectile_names.identify_definition(index),
+            shape,
+            sequence: read_optional_16(&mut input)
+                .context(Field("sequence"))?,
+            detonation_effect: read_optional_16(&mut input)
+                .context(Field("detonation_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            contrail_effect: read_optional_16(&mut input)?
+            contrail_effect: read_optional_16(&mut input)
+                .context(Field("contrail_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            ticks_between_contrails: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            maximum_contrails: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            radius: read_world_distance(&mut input)?,
-            area_of_effect: read_world_distance(&mut input)?,
-            damage: DamageDefinition::read(&mut input, namedbs)?,
-            flags: decode_flags!(read16(&mut input)? => ProjectileFlags {
+            ticks_between_contrails: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("ticks_between_contrails"))?,
+            maximum_contrails: read_optional_16(&mut input)
+                .context(Field("maximum_contrails"))?,
+            radius: read_world_distance(&mut input)
+                .context(Field("radius"))?,
+            area_of_effect: read_world_distance(&mut input)
+                .context(Field("area_of_effect"))?,
+            damage: DamageDefinition::read(&mut input, namedbs, diagnostics)
+                .context(Field("damage"))?,
+            flags: decode_flags!(diagnostics, read16(&mut input).context(Field("flags"))? => ProjectileFlags {
                 guided,
                 stop_when_animation_loops,
                 persistent,
@@ -480,171 +611,250 @@ impl ProjectileDefinition {
                 // don't know how many of these are valid,
                 // but the list definitely stops here
             }),
-            speed: read_world_speed(&mut input)?,
-            maximum_range: read_world_distance(&mut input)?,
-            flyby_sound: read_optional_16(&mut input)?
+            speed: read_world_speed(&mut input).context(Field("speed"))?,
+            maximum_range: read_world_distance(&mut input)
+                .context(Field("maximum_range"))?,
+            flyby_sound: read_optional_16(&mut input)
+                .context(Field("flyby_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
         })
     }
 }
 
+#[cfg(feature = "json")]
 fn is_false(x: &bool) -> bool {
     !*x
 }
 
-#[derive(Serialize)]
-struct WeaponFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct WeaponFlags {
     pub is_automatic: bool,
-    #[serde(skip_serializing_if = "is_false")]
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_false"))]
     pub unknown: bool,
     pub disappears_after_use: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
 }
 
-#[derive(Serialize, Default)]
-struct TriggerDefinition {
+#[derive(Default)]
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct TriggerDefinition {
     pub rounds_per_magazine: Option<u16>,
-    pub ammunition_type: Option<Value>,
-    pub ticks_per_round: Option<u16>,
-    pub recovery_ticks: Option<u16>,
-    pub charging_ticks: Option<u16>,
+    pub ammunition_type: Option<Name>,
+    pub ticks_per_round: Option<Ticks>,
+    pub recovery_ticks: Option<Ticks>,
+    pub charging_ticks: Option<Ticks>,
     pub recoil_magnitude: f32,
-    pub firing_sound: Option<Value>,
-    pub click_sound: Option<Value>,
-    pub charging_sound: Option<Value>,
-    pub shell_casing_sound: Option<Value>,
-    pub reloading_sound: Option<Value>,
+    pub firing_sound: Option<Name>,
+    pub click_sound: Option<Name>,
+    pub charging_sound: Option<Name>,
+    pub shell_casing_sound: Option<Name>,
+    pub reloading_sound: Option<Name>,
     pub sound_activation_range: f32,
-    pub projectile_type: Option<Value>,
+    pub projectile_type: Option<Name>,
     pub theta_error: f32,
     pub dx: f32,
     pub dz: f32,
     pub burst_count: Option<u16>,
 }
 
-#[derive(Serialize)]
-struct WeaponDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    item_type: Option<Value>,
-    weapon_class: Option<Value>,
-    flags: WeaponFlags,
-    firing_light_intensity: f32,
-    firing_intensity_decay_ticks: Option<u16>,
-    idle_height: f32,
-    bob_amplitude: f32,
-    kick_height: f32,
-    reload_height: f32,
-    idle_width: f32,
-    horizontal_amplitude: f32,
-    collection: Option<u16>,
-    idle_sequence: Option<u16>,
-    firing_sequence: Option<u16>,
-    reloading_sequence: Option<u16>,
-    #[serde(skip)]
-    _unused: u16,
-    charging_sequence: Option<u16>,
-    charged_sequence: Option<u16>,
-    ready_ticks: Option<u16>,
-    await_reload_ticks: Option<u16>,
-    triggers: [TriggerDefinition; 2],
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct WeaponDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    pub item_type: Option<Name>,
+    pub weapon_class: Option<Name>,
+    pub flags: WeaponFlags,
+    pub firing_light_intensity: f32,
+    pub firing_intensity_decay_ticks: Option<Ticks>,
+    pub idle_height: f32,
+    pub bob_amplitude: f32,
+    pub kick_height: f32,
+    pub reload_height: f32,
+    pub idle_width: f32,
+    pub horizontal_amplitude: f32,
+    pub collection: Option<u16>,
+    pub idle_sequence: Option<u16>,
+    pub firing_sequence: Option<u16>,
+    pub reloading_sequence: Option<u16>,
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub _unused: u16,
+    pub charging_sequence: Option<u16>,
+    pub charged_sequence: Option<u16>,
+    pub ready_ticks: Option<Ticks>,
+    pub await_reload_ticks: Option<Ticks>,
+    pub triggers: Triggers<TriggerDefinition>,
 }
 
 impl WeaponDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<WeaponDefinition>> {
         const SIZE_OF_WEAPON_DEFINITION: usize = 120;
-        if input.len() % SIZE_OF_WEAPON_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of weapon definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_WEAPON_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| WeaponDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            WEAPON_PHYSICS_TAG,
+            SIZE_OF_WEAPON_DEFINITION,
+            "weapon definition",
+            max_records,
+            diagnostics,
+            |x, i, d| WeaponDefinition::read(x, namedbs, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<WeaponDefinition> {
-        let name = namedbs.weapon_names.identify(index);
-        let item_type = read_optional_16(&mut input)?
+        let name = namedbs.weapon_names.identify_definition(index);
+        let item_type = read_optional_16(&mut input)
+            .context(Field("item_type"))?
             .map(|x| namedbs.item_names.identify(x));
-        let weapon_class = read_optional_16(&mut input)?
+        let weapon_class = read_optional_16(&mut input)
+            .context(Field("weapon_class"))?
             .map(|x| namedbs.weapon_class_names.identify(x));
-        let flags = decode_flags!(read16(&mut input)? => WeaponFlags {
+        let flags = decode_flags!(diagnostics, read16(&mut input).context(Field("flags"))? => WeaponFlags {
             is_automatic,
             unknown,
             disappears_after_use,
         });
         let mut triggers =
             [TriggerDefinition::default(), TriggerDefinition::default()];
-        triggers[0].ammunition_type = read_optional_16(&mut input)?
+        triggers[0].ammunition_type = read_optional_16(&mut input)
+            .context(Field("triggers/0/ammunition_type"))?
             .map(|x| namedbs.item_names.identify(x));
-        triggers[0].rounds_per_magazine = read_optional_16(&mut input)?;
-        triggers[1].ammunition_type = read_optional_16(&mut input)?
+        triggers[0].rounds_per_magazine = read_optional_16(&mut input)
+            .context(Field("triggers/0/rounds_per_magazine"))?;
+        triggers[1].ammunition_type = read_optional_16(&mut input)
+            .context(Field("triggers/1/ammunition_type"))?
             .map(|x| namedbs.item_names.identify(x));
-        triggers[1].rounds_per_magazine = read_optional_16(&mut input)?;
-        let firing_light_intensity = read_fx_16_16(&mut input)?;
-        let firing_intensity_decay_ticks = read_optional_16(&mut input)?;
-        let idle_height = read_fx_16_16(&mut input)?;
-        let bob_amplitude = read_fx_16_16(&mut input)?;
-        let kick_height = read_fx_16_16(&mut input)?;
-        let reload_height = read_fx_16_16(&mut input)?;
-        let idle_width = read_fx_16_16(&mut input)?;
-        let horizontal_amplitude = read_fx_16_16(&mut input)?;
-        let collection = read_optional_16(&mut input)?;
-        let idle_sequence = read_optional_16(&mut input)?;
-        let firing_sequence = read_optional_16(&mut input)?;
-        let reloading_sequence = read_optional_16(&mut input)?;
-        let _unused = read16(&mut input)?;
-        let charging_sequence = read_optional_16(&mut input)?;
-        let charged_sequence = read_optional_16(&mut input)?;
-        triggers[0].ticks_per_round = read_optional_16(&mut input)?;
-        triggers[1].ticks_per_round = read_optional_16(&mut input)?;
-        let await_reload_ticks = read_optional_16(&mut input)?;
-        let ready_ticks = read_optional_16(&mut input)?;
-        triggers[0].recovery_ticks = read_optional_16(&mut input)?;
-        triggers[1].recovery_ticks = read_optional_16(&mut input)?;
-        triggers[0].charging_ticks = read_optional_16(&mut input)?;
-        triggers[1].charging_ticks = read_optional_16(&mut input)?;
-        triggers[0].recoil_magnitude = read_world_distance(&mut input)?;
-        triggers[1].recoil_magnitude = read_world_distance(&mut input)?;
-        triggers[0].firing_sound = read_optional_16(&mut input)?
+        triggers[1].rounds_per_magazine = read_optional_16(&mut input)
+            .context(Field("triggers/1/rounds_per_magazine"))?;
+        let firing_light_intensity = read_fx_16_16(&mut input)
+            .context(Field("firing_light_intensity"))?;
+        let firing_intensity_decay_ticks =
+            Ticks::read_optional(&mut input, namedbs)
+                .context(Field("firing_intensity_decay_ticks"))?;
+        let idle_height =
+            read_fx_16_16(&mut input).context(Field("idle_height"))?;
+        let bob_amplitude =
+            read_fx_16_16(&mut input).context(Field("bob_amplitude"))?;
+        let kick_height =
+            read_fx_16_16(&mut input).context(Field("kick_height"))?;
+        let reload_height =
+            read_fx_16_16(&mut input).context(Field("reload_height"))?;
+        let idle_width =
+            read_fx_16_16(&mut input).context(Field("idle_width"))?;
+        let horizontal_amplitude = read_fx_16_16(&mut input)
+            .context(Field("horizontal_amplitude"))?;
+        let collection =
+            read_optional_16(&mut input).context(Field("collection"))?;
+        let idle_sequence =
+            read_optional_16(&mut input).context(Field("idle_sequence"))?;
+        let firing_sequence =
+            read_optional_16(&mut input).context(Field("firing_sequence"))?;
+        let reloading_sequence = read_optional_16(&mut input)
+            .context(Field("reloading_sequence"))?;
+        let _unused = read16(&mut input).context(Field("_unused"))?;
+        let charging_sequence = read_optional_16(&mut input)
+            .context(Field("charging_sequence"))?;
+        let charged_sequence =
+            read_optional_16(&mut input).context(Field("charged_sequence"))?;
+        triggers[0].ticks_per_round =
+            Ticks::read_optional(&mut input, namedbs)
+                .context(Field("triggers/0/ticks_per_round"))?;
+        triggers[1].ticks_per_round =
+            Ticks::read_optional(&mut input, namedbs)
+                .context(Field("triggers/1/ticks_per_round"))?;
+        let await_reload_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("await_reload_ticks"))?;
+        let ready_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("ready_ticks"))?;
+        triggers[0].recovery_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("triggers/0/recovery_ticks"))?;
+        triggers[1].recovery_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("triggers/1/recovery_ticks"))?;
+        triggers[0].charging_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("triggers/0/charging_ticks"))?;
+        triggers[1].charging_ticks = Ticks::read_optional(&mut input, namedbs)
+            .context(Field("triggers/1/charging_ticks"))?;
+        triggers[0].recoil_magnitude = read_world_distance(&mut input)
+            .context(Field("triggers/0/recoil_magnitude"))?;
+        triggers[1].recoil_magnitude = read_world_distance(&mut input)
+            .context(Field("triggers/1/recoil_magnitude"))?;
+        triggers[0].firing_sound = read_optional_16(&mut input)
+            .context(Field("triggers/0/firing_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[1].firing_sound = read_optional_16(&mut input)?
+        triggers[1].firing_sound = read_optional_16(&mut input)
+            .context(Field("triggers/1/firing_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[0].click_sound = read_optional_16(&mut input)?
+        triggers[0].click_sound = read_optional_16(&mut input)
+            .context(Field("triggers/0/click_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[1].click_sound = read_optional_16(&mut input)?
+        triggers[1].click_sound = read_optional_16(&mut input)
+            .context(Field("triggers/1/click_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[0].reloading_sound = read_optional_16(&mut input)?
+        // The layout is the one Aleph One reads M1 weapons with, in
+        // `unpack_m1_weapon_definition` (GameWorld/weapons.cpp), which has
+        // the per-trigger fields interleaved, primary then secondary.
+        // Unlike the sounds around them, M1 has only one reloading sound and
+        // one charging sound per weapon, not one per trigger. This isn't a
+        // gap in our reading: with a single field each, the record adds up to
+        // exactly 120 bytes, which `read_records` checks, and there's nowhere
+        // for a second pair to be. We put them on the primary trigger, and
+        // also copy the charging sound to the secondary trigger, since a
+        // weapon that charges may charge from either trigger. The secondary
+        // trigger's reloading sound is left empty.
+        triggers[0].reloading_sound = read_optional_16(&mut input)
+            .context(Field("triggers/0/reloading_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
         triggers[1].reloading_sound = None;
-        triggers[0].charging_sound = read_optional_16(&mut input)?
+        triggers[0].charging_sound = read_optional_16(&mut input)
+            .context(Field("triggers/0/charging_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
         triggers[1].charging_sound = triggers[0].charging_sound.clone();
-        triggers[0].shell_casing_sound = read_optional_16(&mut input)?
+        triggers[0].shell_casing_sound = read_optional_16(&mut input)
+            .context(Field("triggers/0/shell_casing_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[1].shell_casing_sound = read_optional_16(&mut input)?
+        triggers[1].shell_casing_sound = read_optional_16(&mut input)
+            .context(Field("triggers/1/shell_casing_sound"))?
             .map(|x| namedbs.sound_names.identify(x));
-        triggers[0].sound_activation_range = read_world_distance(&mut input)?;
-        triggers[1].sound_activation_range = read_world_distance(&mut input)?;
-        triggers[0].projectile_type = read_optional_16(&mut input)?
+        triggers[0].sound_activation_range =
+            read_world_distance(&mut input)
+                .context(Field("triggers/0/sound_activation_range"))?;
+        triggers[1].sound_activation_range =
+            read_world_distance(&mut input)
+                .context(Field("triggers/1/sound_activation_range"))?;
+        triggers[0].projectile_type = read_optional_16(&mut input)
+            .context(Field("triggers/0/projectile_type"))?
             .map(|x| namedbs.projectile_names.identify(x));
-        triggers[1].projectile_type = read_optional_16(&mut input)?
+        triggers[1].projectile_type = read_optional_16(&mut input)
+            .context(Field("triggers/1/projectile_type"))?
             .map(|x| namedbs.projectile_names.identify(x));
-        triggers[0].theta_error = read_angle(&mut input)?;
-        triggers[1].theta_error = read_angle(&mut input)?;
-        triggers[0].dx = read_world_distance(&mut input)?;
-        triggers[0].dz = read_world_distance(&mut input)?;
-        triggers[1].dx = read_world_distance(&mut input)?;
-        triggers[1].dz = read_world_distance(&mut input)?;
-        triggers[0].burst_count = read_optional_16(&mut input)?;
-        triggers[1].burst_count = read_optional_16(&mut input)?;
-        let _unused2 = read16(&mut input)?;
+        triggers[0].theta_error =
+            read_angle(&mut input).context(Field("triggers/0/theta_error"))?;
+        triggers[1].theta_error =
+            read_angle(&mut input).context(Field("triggers/1/theta_error"))?;
+        triggers[0].dx =
+            read_world_distance(&mut input).context(Field("triggers/0/dx"))?;
+        triggers[0].dz =
+            read_world_distance(&mut input).context(Field("triggers/0/dz"))?;
+        triggers[1].dx =
+            read_world_distance(&mut input).context(Field("triggers/1/dx"))?;
+        triggers[1].dz =
+            read_world_distance(&mut input).context(Field("triggers/1/dz"))?;
+        triggers[0].burst_count = read_optional_16(&mut input)
+            .context(Field("triggers/0/burst_count"))?;
+        triggers[1].burst_count = read_optional_16(&mut input)
+            .context(Field("triggers/1/burst_count"))?;
+        let _unused2 = read16(&mut input).context(Field("_unused2"))?;
         Ok(WeaponDefinition {
             name,
             item_type,
@@ -667,13 +877,13 @@ impl WeaponDefinition {
             charged_sequence,
             ready_ticks,
             await_reload_ticks,
-            triggers,
+            triggers: Triggers::new(triggers, namedbs),
         })
     }
 }
 
-#[derive(Serialize)]
-struct PhysicsDefinition {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct PhysicsDefinition {
     pub maximum_forward_velocity: f32,
     pub maximum_backward_velocity: f32,
     pub maximum_perpendicular_velocity: f32,
@@ -707,84 +917,441 @@ impl PhysicsDefinition {
         _namedb: &NameDbs,
     ) -> anyhow::Result<PhysicsDefinition> {
         Ok(PhysicsDefinition {
-            maximum_forward_velocity: read_fx_16_16(&mut input)?,
-            maximum_backward_velocity: read_fx_16_16(&mut input)?,
-            maximum_perpendicular_velocity: read_fx_16_16(&mut input)?,
-            acceleration: read_fx_16_16(&mut input)?,
-            deceleration: read_fx_16_16(&mut input)?,
-            airborne_deceleration: read_fx_16_16(&mut input)?,
-            gravitational_acceleration: read_fx_16_16(&mut input)?,
-            climbing_acceleration: read_fx_16_16(&mut input)?,
-            terminal_velocity: read_fx_16_16(&mut input)?,
-            external_deceleration: read_fx_16_16(&mut input)?,
-            angular_acceleration: read_fx_16_16(&mut input)?,
-            angular_deceleration: read_fx_16_16(&mut input)?,
-            maximum_angular_velocity: read_fx_16_16(&mut input)?,
-            angular_recentering_velocity: read_fx_16_16(&mut input)?,
-            fast_angular_velocity: read_fx_16_16(&mut input)?,
-            fast_angular_maximum: read_fx_16_16(&mut input)?,
-            maximum_elevation: read_fx_16_16(&mut input)?,
-            external_angular_deceleration: read_fx_16_16(&mut input)?,
-            step_delta: read_fx_16_16(&mut input)?,
-            step_amplitude: read_fx_16_16(&mut input)?,
-            radius: read_fx_16_16(&mut input)?,
-            height: read_fx_16_16(&mut input)?,
-            dead_height: read_fx_16_16(&mut input)?,
-            camera_height: read_fx_16_16(&mut input)?,
-            half_camera_separation: read_fx_16_16(&mut input)?,
+            maximum_forward_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_forward_velocity"))?,
+            maximum_backward_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_backward_velocity"))?,
+            maximum_perpendicular_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_perpendicular_velocity"))?,
+            acceleration: read_fx_16_16(&mut input)
+                .context(Field("acceleration"))?,
+            deceleration: read_fx_16_16(&mut input)
+                .context(Field("deceleration"))?,
+            airborne_deceleration: read_fx_16_16(&mut input)
+                .context(Field("airborne_deceleration"))?,
+            gravitational_acceleration: read_fx_16_16(&mut input)
+                .context(Field("gravitational_acceleration"))?,
+            climbing_acceleration: read_fx_16_16(&mut input)
+                .context(Field("climbing_acceleration"))?,
+            terminal_velocity: read_fx_16_16(&mut input)
+                .context(Field("terminal_velocity"))?,
+            external_deceleration: read_fx_16_16(&mut input)
+                .context(Field("external_deceleration"))?,
+            angular_acceleration: read_fx_16_16(&mut input)
+                .context(Field("angular_acceleration"))?,
+            angular_deceleration: read_fx_16_16(&mut input)
+                .context(Field("angular_deceleration"))?,
+            maximum_angular_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_angular_velocity"))?,
+            angular_recentering_velocity: read_fx_16_16(&mut input)
+                .context(Field("angular_recentering_velocity"))?,
+            fast_angular_velocity: read_fx_16_16(&mut input)
+                .context(Field("fast_angular_velocity"))?,
+            fast_angular_maximum: read_fx_16_16(&mut input)
+                .context(Field("fast_angular_maximum"))?,
+            maximum_elevation: read_fx_16_16(&mut input)
+                .context(Field("maximum_elevation"))?,
+            external_angular_deceleration: read_fx_16_16(&mut input)
+                .context(Field("external_angular_deceleration"))?,
+            step_delta: read_fx_16_16(&mut input)
+                .context(Field("step_delta"))?,
+            step_amplitude: read_fx_16_16(&mut input)
+                .context(Field("step_amplitude"))?,
+            radius: read_fx_16_16(&mut input).context(Field("radius"))?,
+            height: read_fx_16_16(&mut input).context(Field("height"))?,
+            dead_height: read_fx_16_16(&mut input)
+                .context(Field("dead_height"))?,
+            camera_height: read_fx_16_16(&mut input)
+                .context(Field("camera_height"))?,
+            half_camera_separation: read_fx_16_16(&mut input)
+                .context(Field("half_camera_separation"))?,
         })
     }
 }
 
-#[derive(Serialize)]
-struct PhysicsDefinitions {
-    walking: PhysicsDefinition,
-    running: PhysicsDefinition,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct PhysicsDefinitions {
+    pub walking: PhysicsDefinition,
+    pub running: PhysicsDefinition,
+    /// Any models after the first two. The engine only uses walking and
+    /// running, but these are output rather than silently dropped.
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Vec::is_empty")
+    )]
+    pub extra: Vec<PhysicsDefinition>,
 }
 
 impl PhysicsDefinitions {
     pub fn read(
-        mut input: impl Read,
+        input: &[u8],
         namedb: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<PhysicsDefinitions> {
+        const SIZE_OF_PHYSICS_DEFINITION: usize = 100;
+        let models = read_records(
+            input,
+            PHYSICS_PHYSICS_TAG,
+            SIZE_OF_PHYSICS_DEFINITION,
+            "physics model",
+            max_records,
+            diagnostics,
+            |x, _, _| PhysicsDefinition::read(x, namedb),
+        )?;
+        let kind = String::from_utf8_lossy(&PHYSICS_PHYSICS_TAG);
+        let count = models.len();
+        let mut models = models.into_iter();
+        let (Some(walking), Some(running)) = (models.next(), models.next())
+        else {
+            return Err(anyhow!(
+                "{kind:?} chunk: expected 2 physics models (walking and \
+                 running), but there are only {count}"
+            ));
+        };
+        if count > 2 {
+            diagnostics.warn(
+                WarningKind::ExtraPhysicsModels,
+                format_args!(
+                    "{kind:?} chunk: expected 2 physics models (walking and \
+                     running), but there are {count}"
+                ),
+            );
+        }
         Ok(PhysicsDefinitions {
-            walking: PhysicsDefinition::read(&mut input, namedb)?,
-            running: PhysicsDefinition::read(&mut input, namedb)?,
+            walking,
+            running,
+            extra: models.collect(),
         })
     }
+    /// How many physics models there are, normally 2.
+    pub fn count(&self) -> usize {
+        2 + self.extra.len()
+    }
+}
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct Physics {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub monster_definitions: Option<Vec<MonsterDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub effect_definitions: Option<Vec<EffectDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub projectile_definitions: Option<Vec<ProjectileDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub weapon_definitions: Option<Vec<WeaponDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub physics: Option<PhysicsDefinitions>,
 }
 
-#[derive(Serialize)]
-struct Physics {
-    monster_definitions: Vec<MonsterDefinition>,
-    effect_definitions: Vec<EffectDefinition>,
-    projectile_definitions: Vec<ProjectileDefinition>,
-    weapon_definitions: Vec<WeaponDefinition>,
-    physics: PhysicsDefinitions,
+impl Physics {
+    #[cfg(feature = "json")]
+    /// Writes each definition to its own file under `dir`, for `--explode`.
+    pub fn explode(
+        &self,
+        dir: &Path,
+        options: &ConvertOptions,
+    ) -> anyhow::Result<()> {
+        if let Some(x) = &self.monster_definitions {
+            explode_table(&dir.join("monsters"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.effect_definitions {
+            explode_table(&dir.join("effects"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.projectile_definitions {
+            explode_table(&dir.join("projectiles"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.weapon_definitions {
+            explode_table(&dir.join("weapons"), x, |x| &x.name, options)?;
+        }
+        if let Some(physics) = &self.physics {
+            let dir = dir.join("physics");
+            let models = [&physics.walking, &physics.running]
+                .into_iter()
+                .chain(&physics.extra);
+            for (index, definition) in models.enumerate() {
+                let stem = match index {
+                    0 => "walking".to_string(),
+                    1 => "running".to_string(),
+                    _ => index.to_string(),
+                };
+                let value = Indexed { index, definition };
+                write_exploded(&dir, &stem, &value, options)?;
+            }
+        }
+        Ok(())
+    }
+    /// Reads the tables out of the given chunks. Tables not listed in `only`
+    /// are skipped, unless it's empty. Tables whose chunks are missing are
+    /// skipped with a warning. A table with more than `max_records` records
+    /// is an error.
+    pub fn read(
+        chunks: &[Chunk],
+        namedbs: &NameDbs,
+        only: &[Table],
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Physics> {
+        let (
+            monster_definitions,
+            effect_definitions,
+            projectile_definitions,
+            weapon_definitions,
+            physics_definitions,
+        ) = join5(
+            || {
+                read_table(
+                    only,
+                    Table::Monsters,
+                    chunks,
+                    MONSTER_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        MonsterDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Effects,
+                    chunks,
+                    EFFECT_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        EffectDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Projectiles,
+                    chunks,
+                    PROJECTILE_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        ProjectileDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Weapons,
+                    chunks,
+                    WEAPON_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        WeaponDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Physics,
+                    chunks,
+                    PHYSICS_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        PhysicsDefinitions::read(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+        );
+        let monster_definitions = monster_definitions?;
+        let effect_definitions = effect_definitions?;
+        let projectile_definitions = projectile_definitions?;
+        let weapon_definitions = weapon_definitions?;
+        let physics_definitions = physics_definitions?;
+        if monster_definitions.is_none()
+            && effect_definitions.is_none()
+            && projectile_definitions.is_none()
+            && weapon_definitions.is_none()
+            && physics_definitions.is_none()
+        {
+            return Err(PhysicsEaterError::NoPhysicsChunks.into());
+        }
+        Ok(Physics {
+            monster_definitions,
+            effect_definitions,
+            projectile_definitions,
+            weapon_definitions,
+            physics: physics_definitions,
+        })
+    }
 }
 
+#[cfg(feature = "json")]
 pub fn convert_physics(
     physics_path: PathBuf,
-    namedbs: NameDbs,
+    mut namedbs: NameDbs,
+    options: &ConvertOptions,
+    lenient: bool,
+    diagnostics: &Diagnostics,
 ) -> anyhow::Result<()> {
-    let chunks = Chunk::read_m1_chunks(File::open(physics_path)?)?;
-    let monster_definitions = Chunk::find(&chunks, MONSTER_PHYSICS_TAG)
-        .and_then(|x| MonsterDefinition::read_definitions(x, &namedbs))?;
-    let effect_definitions = Chunk::find(&chunks, EFFECT_PHYSICS_TAG)
-        .and_then(|x| EffectDefinition::read_definitions(x, &namedbs))?;
-    let projectile_definitions = Chunk::find(&chunks, PROJECTILE_PHYSICS_TAG)
-        .and_then(|x| ProjectileDefinition::read_definitions(x, &namedbs))?;
-    let weapon_definitions = Chunk::find(&chunks, WEAPON_PHYSICS_TAG)
-        .and_then(|x| WeaponDefinition::read_definitions(x, &namedbs))?;
-    let physics_definitions = Chunk::find(&chunks, PHYSICS_PHYSICS_TAG)
-        .and_then(|x| PhysicsDefinitions::read(x, &namedbs))?;
-    let physics = Physics {
-        monster_definitions,
-        effect_definitions,
-        projectile_definitions,
-        weapon_definitions,
-        physics: physics_definitions,
-    };
-    serde_json::to_writer_pretty(std::io::stdout(), &physics)?;
-    Ok(())
+    let diagnostics = &diagnostics.at(physics_path.display());
+    let bare_table = options.bare_table()?;
+    let chunks = read_chunks(
+        open_input(&physics_path, diagnostics)?,
+        lenient,
+        diagnostics,
+    )?;
+    options.load_chunk_names(&mut namedbs, &chunks, diagnostics)?;
+    let physics = Physics::read(
+        &chunks,
+        &namedbs,
+        &options.only,
+        options.max_records,
+        diagnostics,
+    )?;
+    options.check_shapes(&physics, &namedbs, diagnostics)?;
+    options.check_references(&physics, &namedbs, diagnostics)?;
+    diagnostics.check()?;
+    let record_counts = [
+        (
+            MONSTER_PHYSICS_TAG,
+            physics.monster_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            EFFECT_PHYSICS_TAG,
+            physics.effect_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            PROJECTILE_PHYSICS_TAG,
+            physics.projectile_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            WEAPON_PHYSICS_TAG,
+            physics.weapon_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            PHYSICS_PHYSICS_TAG,
+            physics.physics.as_ref().map(PhysicsDefinitions::count),
+        ),
+    ];
+    options.report_chunks(&chunks, &record_counts, diagnostics);
+    if let Some(dir) = &options.explode {
+        return physics.explode(dir, options);
+    }
+    match bare_table {
+        None if options.offsets || options.embed_namedb => {
+            let layout = options
+                .offsets
+                .then(|| ChunkLayout::describe(&chunks, &record_counts));
+            let physics = Annotated {
+                layout,
+                namedb: options.embed_namedb.then(|| namedbs.tables()),
+                physics: &physics,
+            };
+            write_output(&physics, options)
+        }
+        None => write_output(&physics, options),
+        Some(Table::Monsters) => {
+            write_output(&physics.monster_definitions, options)
+        }
+        Some(Table::Effects) => {
+            write_output(&physics.effect_definitions, options)
+        }
+        Some(Table::Projectiles) => {
+            write_output(&physics.projectile_definitions, options)
+        }
+        Some(Table::Weapons) => {
+            write_output(&physics.weapon_definitions, options)
+        }
+        Some(Table::Physics) => write_output(&physics.physics, options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weapon_record_fields() {
+        let record = numbered_record(120);
+        let mut input = CountingReader::new(&record[..]);
+        let weapon = WeaponDefinition::read(
+            &mut input,
+            &NameDbs::default(),
+            0,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(input.bytes_read(), 120);
+        assert_eq!(weapon.item_type, Some(Name::Index(1)));
+        assert_eq!(
+            weapon.weapon_class,
+            Some(Name::Named("dual function".into()))
+        );
+        assert!(weapon.flags.is_automatic && weapon.flags.unknown);
+        assert!(!weapon.flags.disappears_after_use);
+        assert_eq!(weapon.firing_light_intensity, 8.0 + 9.0 / 65536.0);
+        assert_eq!(weapon.collection, Some(23));
+        assert_eq!(weapon._unused, 27);
+        assert_eq!(weapon.charged_sequence, Some(29));
+        assert_eq!(weapon.await_reload_ticks.unwrap().ticks, 32);
+        assert_eq!(weapon.ready_ticks.unwrap().ticks, 33);
+        let [primary, secondary] = &weapon.triggers.triggers;
+        assert_eq!(primary.ammunition_type, Some(Name::Index(4)));
+        assert_eq!(primary.rounds_per_magazine, Some(5));
+        assert_eq!(secondary.ammunition_type, Some(Name::Index(6)));
+        assert_eq!(secondary.rounds_per_magazine, Some(7));
+        assert_eq!(primary.ticks_per_round.as_ref().unwrap().ticks, 30);
+        assert_eq!(secondary.ticks_per_round.as_ref().unwrap().ticks, 31);
+        assert_eq!(primary.click_sound, Some(Name::Index(42)));
+        assert_eq!(secondary.click_sound, Some(Name::Index(43)));
+        // one reloading sound and one charging sound for both triggers
+        assert_eq!(primary.reloading_sound, Some(Name::Index(44)));
+        assert_eq!(secondary.reloading_sound, None);
+        assert_eq!(primary.charging_sound, Some(Name::Index(45)));
+        assert_eq!(secondary.charging_sound, Some(Name::Index(45)));
+        assert_eq!(primary.shell_casing_sound, Some(Name::Index(46)));
+        assert_eq!(secondary.shell_casing_sound, Some(Name::Index(47)));
+        assert_eq!(primary.projectile_type, Some(Name::Index(50)));
+        assert_eq!(secondary.projectile_type, Some(Name::Index(51)));
+        assert_eq!(primary.burst_count, Some(58));
+        assert_eq!(secondary.burst_count, Some(59));
+    }
 }
@@ -17,10 +17,14 @@
 
 use super::*;
 
-use std::{fs::File, io::Read};
+use std::io::Read;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use schemars::JsonSchema;
+#[cfg(feature = "json")]
 use serde::Serialize;
+#[cfg(feature = "json")]
 use serde_json::Value;
 
 pub const MONSTER_PHYSICS_TAG: [u8; 4] = *b"MNpx";
@@ -29,105 +33,186 @@ pub const PROJECTILE_PHYSICS_TAG: [u8; 4] = *b"PRpx";
 pub const PHYSICS_PHYSICS_TAG: [u8; 4] = *b"PXpx";
 pub const WEAPON_PHYSICS_TAG: [u8; 4] = *b"WPpx";
 
-#[derive(Serialize)]
-struct MonsterFlags {
-    omniscient: bool,
-    flies: bool,
-    is_alien: bool,
-    major: bool,
-    minor: bool,
-    cannot_skip: bool,
-    floats: bool,
-    cannot_attack: bool,
-    uses_sniper_ledges: bool,
-    is_invisible: bool,
-    is_subtly_invisible: bool,
-    kamikaze: bool, // misspelled grievously in the original source
-    berserker: bool,
-    enlarged: bool,
-    delayed_hard_death: bool,
-    fires_symmetrically: bool,
-    nuclear_hard_death: bool,
-    cannot_fire_backwards: bool,
-    can_die_in_flames: bool,
-    waits_with_clear_shot: bool,
-    tiny: bool,
-    attacks_immediately: bool,
-    not_afraid_of_water: bool,
-    not_afraid_of_sewage: bool,
-    not_afraid_of_lava: bool,
-    not_afraid_of_goo: bool,
-    can_teleport_under_media: bool,
-    chooses_weapons_randomly: bool,
+/// Every tag that M2 physics are kept under.
+pub const PHYSICS_TAGS: [[u8; 4]; 5] = [
+    MONSTER_PHYSICS_TAG,
+    EFFECT_PHYSICS_TAG,
+    PROJECTILE_PHYSICS_TAG,
+    PHYSICS_PHYSICS_TAG,
+    WEAPON_PHYSICS_TAG,
+];
+
+// The size of one record in each of the tables, in bytes.
+pub const SIZE_OF_MONSTER_DEFINITION: usize = 156;
+pub const SIZE_OF_EFFECT_DEFINITION: usize = 14;
+pub const SIZE_OF_PROJECTILE_DEFINITION: usize = 48;
+pub const SIZE_OF_PHYSICS_DEFINITION: usize = 104;
+pub const SIZE_OF_WEAPON_DEFINITION: usize = 134;
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct MonsterFlags {
+    pub omniscient: bool,
+    pub flies: bool,
+    pub is_alien: bool,
+    pub major: bool,
+    pub minor: bool,
+    pub cannot_skip: bool,
+    pub floats: bool,
+    pub cannot_attack: bool,
+    pub uses_sniper_ledges: bool,
+    pub is_invisible: bool,
+    pub is_subtly_invisible: bool,
+    pub kamikaze: bool, // misspelled grievously in the original source
+    pub berserker: bool,
+    pub enlarged: bool,
+    pub delayed_hard_death: bool,
+    pub fires_symmetrically: bool,
+    pub nuclear_hard_death: bool,
+    pub cannot_fire_backwards: bool,
+    pub can_die_in_flames: bool,
+    pub waits_with_clear_shot: bool,
+    pub tiny: bool,
+    pub attacks_immediately: bool,
+    pub not_afraid_of_water: bool,
+    pub not_afraid_of_sewage: bool,
+    pub not_afraid_of_lava: bool,
+    pub not_afraid_of_goo: bool,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub infinity: Option<InfinityMonsterFlags>,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u32,
+}
+
+decode_flags! {
+    impl MonsterFlags: u32 {
+        omniscient,
+        flies,
+        is_alien,
+        major,
+        minor,
+        cannot_skip,
+        floats,
+        cannot_attack,
+        uses_sniper_ledges,
+        is_invisible,
+        is_subtly_invisible,
+        kamikaze,
+        berserker,
+        enlarged,
+        delayed_hard_death,
+        fires_symmetrically,
+        nuclear_hard_death,
+        cannot_fire_backwards,
+        can_die_in_flames,
+        waits_with_clear_shot,
+        tiny,
+        attacks_immediately,
+        not_afraid_of_water,
+        not_afraid_of_sewage,
+        not_afraid_of_lava,
+        not_afraid_of_goo,
+    }
+    ; infinity: Option<InfinityMonsterFlags>
 }
 
 impl MonsterFlags {
-    pub fn read(input: impl Read) -> anyhow::Result<MonsterFlags> {
-        Ok(decode_flags!(
-            read32(input)? => MonsterFlags {
-                omniscient,
-                flies,
-                is_alien,
-                major,
-                minor,
-                cannot_skip,
-                floats,
-                cannot_attack,
-                uses_sniper_ledges,
-                is_invisible,
-                is_subtly_invisible,
-                kamikaze,
-                berserker,
-                enlarged,
-                delayed_hard_death,
-                fires_symmetrically,
-                nuclear_hard_death,
-                cannot_fire_backwards,
-                can_die_in_flames,
-                waits_with_clear_shot,
-                tiny,
-                attacks_immediately,
-                not_afraid_of_water,
-                not_afraid_of_sewage,
-                not_afraid_of_lava,
-                not_afraid_of_goo,
-                can_teleport_under_media,
-                chooses_weapons_randomly,
-            }
+    pub fn read(
+        input: impl Read,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<MonsterFlags> {
+        let flags = read32(input)?;
+        Ok(MonsterFlags::from_bits(
+            flags,
+            InfinityMonsterFlags::decode(flags, game, diagnostics),
+            InfinityMonsterFlags::unknown_to_m2(flags, game, diagnostics),
         ))
     }
 }
 
-#[derive(Serialize)]
-struct DamageDefinitionFlags {
-    alien_damage: bool,
+/// Monster flags that only Marathon Infinity understands.
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct InfinityMonsterFlags {
+    pub can_teleport_under_media: bool,
+    pub chooses_weapons_randomly: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u32,
+}
+
+decode_flags! {
+    impl InfinityMonsterFlags: u32 [26..] {
+        can_teleport_under_media,
+        chooses_weapons_randomly,
+    }
+}
+
+impl InfinityMonsterFlags {
+    fn decode(
+        flags: u32,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> Option<InfinityMonsterFlags> {
+        // the first 26 bits are shared with Marathon 2
+        (game == Game::Infinity)
+            .then(|| InfinityMonsterFlags::from_bits(flags, diagnostics))
+    }
+    /// When converting for Marathon 2, the bits that only Infinity knows
+    /// about are unknown, and kept as the reserved bits of the `MonsterFlags`.
+    fn unknown_to_m2(
+        flags: u32,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> u32 {
+        if game == Game::Infinity {
+            return 0;
+        }
+        let unknown = flags >> 26;
+        warn_unknown_flags(diagnostics, "MonsterFlags", unknown.into(), 26);
+        unknown << 26
+    }
+}
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct DamageDefinitionFlags {
+    pub alien_damage: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
+}
+
+decode_flags! {
+    impl DamageDefinitionFlags: u16 { alien_damage }
 }
 
 impl DamageDefinitionFlags {
-    pub fn read(input: impl Read) -> anyhow::Result<DamageDefinitionFlags> {
-        Ok(
-            decode_flags!(read16(input)? => DamageDefinitionFlags { alien_damage }),
-        )
+    pub fn read(
+        input: impl Read,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<DamageDefinitionFlags> {
+        Ok(DamageDefinitionFlags::from_bits(
+            read16(input)?,
+            diagnostics,
+        ))
     }
 }
 
-#[derive(Serialize)]
-struct DamageDefinition {
-    damage_type: Option<Value>,
-    flags: DamageDefinitionFlags,
-    base: i16,
-    random: i16,
-    scale: f32,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct DamageDefinition {
+    pub damage_type: Option<Name>,
+    pub flags: DamageDefinitionFlags,
+    pub base: i16,
+    pub random: i16,
+    pub scale: f32,
 }
 
 impl DamageDefinition {
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<DamageDefinition> {
         let damage_type = read_optional_16(&mut input)?
             .map(|x| namedbs.damage_type_names.identify(x));
-        let flags = DamageDefinitionFlags::read(&mut input)?;
+        let flags = DamageDefinitionFlags::read(&mut input, diagnostics)?;
         let base = read16(&mut input)? as i16;
         let random = read16(&mut input)? as i16;
         let scale = read_fx_16_16(&mut input)?;
@@ -139,11 +224,25 @@ impl DamageDefinition {
             scale,
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a damage definition.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.name16("damage_type", &namedbs.damage_type_names));
+        out.write16(fields.flags16("flags", DamageDefinitionFlags::NAMES));
+        out.write16(fields.int16("base"));
+        out.write16(fields.int16("random"));
+        out.write32(fields.fx_16_16("scale"));
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct AttackDefinition {
-    pub projectile_type: Value,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct AttackDefinition {
+    pub projectile_type: Name,
     pub repetitions: Option<u16>,
     pub error: f32,
     pub range: f32,
@@ -178,41 +277,80 @@ impl AttackDefinition {
             dz,
         }))
     }
+    #[cfg(feature = "json")]
+    /// Writes an attack, or if it's None, just its NONE projectile type.
+    pub fn write(
+        fields: Option<&Fields>,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        let Some(fields) = fields else {
+            out.write16(Ok(0xFFFF));
+            out.skip(14);
+            return Ok(());
+        };
+        let projectile_type = fields
+            .name16("projectile_type", &namedbs.projectile_names)
+            .and_then(|x| match x {
+                0xFFFF => Err(anyhow!(
+                    "{}/projectile_type can't be null, make the whole attack \
+                     null instead",
+                    fields.path()
+                )),
+                x => Ok(x),
+            });
+        out.write16(projectile_type);
+        out.write16(fields.optional16("repetitions"));
+        out.write16(fields.angle("error"));
+        out.write16(fields.fx_6_10("range"));
+        out.write16(fields.optional16("attack_sequence"));
+        out.write16(fields.fx_6_10("dx"));
+        out.write16(fields.fx_6_10("dy"));
+        out.write16(fields.fx_6_10("dz"));
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct MonsterDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    pub name: Value,
-    pub collection: Option<Value>,
-    pub clut: Option<u16>,
-    pub vitality: Option<u16>,
-    pub immunities: Vec<Value>,
-    pub weaknesses: Vec<Value>,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct MonsterDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    // a plain hit point count; NONE has no special meaning here
+    pub vitality: i16,
+    pub immunities: Vec<Name>,
+    pub weaknesses: Vec<Name>,
     pub flags: MonsterFlags,
-    pub class: Option<Value>,
-    pub friends: Vec<Value>,
-    pub enemies: Vec<Value>,
+    pub class: Option<Name>,
+    pub friends: Vec<Name>,
+    pub enemies: Vec<Name>,
     pub sound_pitch: f32,
-    pub activation_sound: Option<Value>,
-    pub friendly_activation_sound: Option<Value>,
-    pub clear_sound: Option<Value>,
-    pub kill_sound: Option<Value>,
-    pub apology_sound: Option<Value>,
-    pub friendly_fire_sound: Option<Value>,
-    pub flaming_sound: Option<Value>,
-    pub random_sound: Option<Value>,
-    pub random_sound_mask: Option<u16>,
-    pub carrying_item_type: Option<Value>,
+    pub activation_sound: Option<Name>,
+    pub friendly_activation_sound: Option<Name>,
+    pub clear_sound: Option<Name>,
+    pub kill_sound: Option<Name>,
+    pub apology_sound: Option<Name>,
+    pub friendly_fire_sound: Option<Name>,
+    pub flaming_sound: Option<Name>,
+    pub random_sound: Option<Name>,
+    // ANDed with a random number to decide whether to make the random
+    // sound; a mask, so every bit pattern means something, and there's no
+    // NONE
+    pub random_sound_mask: u16,
+    pub carrying_item_type: Option<Name>,
     pub radius: f32,
     pub height: f32,
     pub preferred_hover_height: f32,
     pub minimum_ledge_delta: f32,
     pub maximum_ledge_delta: f32,
     pub external_velocity_scale: f32,
-    pub impact_effect: Option<Value>,
-    pub melee_impact_effect: Option<Value>,
-    pub contrail_effect: Option<Value>,
+    pub impact_effect: Option<Name>,
+    pub melee_impact_effect: Option<Name>,
+    pub contrail_effect: Option<Name>,
     pub half_visual_arc: f32,
     pub half_vertical_visual_arc: f32,
     pub visual_range: f32,
@@ -221,7 +359,9 @@ struct MonsterDefinition {
     pub speed: f32,
     pub gravity: f32,
     pub terminal_velocity: f32,
-    pub door_retry_mask: Option<u16>,
+    // ANDed with a random number to decide whether to try a door again;
+    // a mask, like random_sound_mask, with no NONE
+    pub door_retry_mask: u16,
     pub shrapnel_radius: Option<f32>,
     pub shrapnel_damage: DamageDefinition,
     // these are marked as shape descriptors in the code, but they're actually
@@ -244,177 +384,342 @@ impl MonsterDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        game: Game,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<MonsterDefinition>> {
-        const SIZE_OF_MONSTER_DEFINITION: usize = 156;
-        if input.len() % SIZE_OF_MONSTER_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of monster definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_MONSTER_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| MonsterDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            MONSTER_PHYSICS_TAG,
+            SIZE_OF_MONSTER_DEFINITION,
+            "monster definition",
+            max_records,
+            diagnostics,
+            |x, i, d| MonsterDefinition::read(x, namedbs, game, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
+        game: Game,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<MonsterDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
         Ok(MonsterDefinition {
-            name: namedbs.monster_names.identify(index),
-            collection,
-            clut,
-            vitality: read_optional_16(&mut input)?,
-            immunities: read_generic_bitfield32(&mut input)?
+            name: namedbs.monster_names.identify_definition(index),
+            shape,
+            vitality: read16(&mut input).context(Field("vitality"))? as i16,
+            immunities: read_generic_bitfield32(&mut input)
+                .context(Field("immunities"))?
                 .into_iter()
-                .map(|x| namedbs.damage_type_names.identify(x))
+                .map(|x| namedbs.damage_type_names.identify_bit(x))
                 .collect(),
-            weaknesses: read_generic_bitfield32(&mut input)?
+            weaknesses: read_generic_bitfield32(&mut input)
+                .context(Field("weaknesses"))?
                 .into_iter()
-                .map(|x| namedbs.damage_type_names.identify(x))
+                .map(|x| namedbs.damage_type_names.identify_bit(x))
                 .collect(),
-            flags: MonsterFlags::read(&mut input)?,
-            class: read_optional_32(&mut input)?
+            flags: MonsterFlags::read(&mut input, game, diagnostics)
+                .context(Field("flags"))?,
+            class: read_optional_32(&mut input)
+                .context(Field("class"))?
                 .map(|x| namedbs.monster_class_names.identify(x)),
-            friends: read_generic_bitfield32(&mut input)?
+            friends: read_generic_bitfield32(&mut input)
+                .context(Field("friends"))?
                 .into_iter()
-                .map(|x| namedbs.monster_class_names.identify(x))
+                .map(|x| namedbs.monster_class_names.identify_bit(x))
                 .collect(),
-            enemies: read_generic_bitfield32(&mut input)?
+            enemies: read_generic_bitfield32(&mut input)
+                .context(Field("enemies"))?
                 .into_iter()
-                .map(|x| namedbs.monster_class_names.identify(x))
+                .map(|x| namedbs.monster_class_names.identify_bit(x))
                 .collect(),
-            sound_pitch: read_fx_16_16(&mut input)?,
-            activation_sound: read_optional_16(&mut input)?
+            sound_pitch: read_fx_16_16(&mut input)
+                .context(Field("sound_pitch"))?,
+            activation_sound: read_optional_16(&mut input)
+                .context(Field("activation_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            friendly_activation_sound: read_optional_16(&mut input)?
+            friendly_activation_sound: read_optional_16(&mut input)
+                .context(Field("friendly_activation_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            clear_sound: read_optional_16(&mut input)?
+            clear_sound: read_optional_16(&mut input)
+                .context(Field("clear_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            kill_sound: read_optional_16(&mut input)?
+            kill_sound: read_optional_16(&mut input)
+                .context(Field("kill_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            apology_sound: read_optional_16(&mut input)?
+            apology_sound: read_optional_16(&mut input)
+                .context(Field("apology_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            friendly_fire_sound: read_optional_16(&mut input)?
+            friendly_fire_sound: read_optional_16(&mut input)
+                .context(Field("friendly_fire_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            flaming_sound: read_optional_16(&mut input)?
+            flaming_sound: read_optional_16(&mut input)
+                .context(Field("flaming_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            random_sound: read_optional_16(&mut input)?
+            random_sound: read_optional_16(&mut input)
+                .context(Field("random_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            random_sound_mask: read_optional_16(&mut input)?,
-            carrying_item_type: read_optional_16(&mut input)?
+            random_sound_mask: read16(&mut input)
+                .context(Field("random_sound_mask"))?,
+            carrying_item_type: read_optional_16(&mut input)
+                .context(Field("carrying_item_type"))?
                 .map(|x| namedbs.item_names.identify(x)),
-            radius: read_world_distance(&mut input)?,
-            height: read_world_distance(&mut input)?,
-            preferred_hover_height: read_world_distance(&mut input)?,
-            minimum_ledge_delta: read_world_distance(&mut input)?,
-            maximum_ledge_delta: read_world_distance(&mut input)?,
-            external_velocity_scale: read_fx_16_16(&mut input)?,
-            impact_effect: read_optional_16(&mut input)?
+            radius: read_world_distance(&mut input)
+                .context(Field("radius"))?,
+            height: read_world_distance(&mut input)
+                .context(Field("height"))?,
+            preferred_hover_height: read_world_distance(&mut input)
+                .context(Field("preferred_hover_height"))?,
+            minimum_ledge_delta: read_world_distance(&mut input)
+                .context(Field("minimum_ledge_delta"))?,
+            maximum_ledge_delta: read_world_distance(&mut input)
+                .context(Field("maximum_ledge_delta"))?,
+            external_velocity_scale: read_fx_16_16(&mut input)
+                .context(Field("external_velocity_scale"))?,
+            impact_effect: read_optional_16(&mut input)
+                .context(Field("impact_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            melee_impact_effect: read_optional_16(&mut input)?
+            melee_impact_effect: read_optional_16(&mut input)
+                .context(Field("melee_impact_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            contrail_effect: read_optional_16(&mut input)?
+            contrail_effect: read_optional_16(&mut input)
+                .context(Field("contrail_effect"))?
                 .map(|x| namedbs.effect_names.identify(x)),
-            half_visual_arc: read_angle(&mut input)?,
-            half_vertical_visual_arc: read_angle(&mut input)?,
-            visual_range: read_world_distance(&mut input)?,
-            dark_visual_range: read_world_distance(&mut input)?,
-            intelligence: read_optional_16(&mut input)?,
-            speed: read_world_speed(&mut input)?,
-            gravity: read_world_accel(&mut input)?,
-            terminal_velocity: read_world_speed(&mut input)?,
-            door_retry_mask: read_optional_16(&mut input)?,
-            shrapnel_radius: read_optional_fx_6_10(&mut input)?,
-            shrapnel_damage: DamageDefinition::read(&mut input, namedbs)?,
-            hit_sequence: read_optional_16(&mut input)?,
-            hard_dying_sequence: read_optional_16(&mut input)?,
-            soft_dying_sequence: read_optional_16(&mut input)?,
-            hard_dead_sequence: read_optional_16(&mut input)?,
-            soft_dead_sequence: read_optional_16(&mut input)?,
-            stationary_sequence: read_optional_16(&mut input)?,
-            moving_sequence: read_optional_16(&mut input)?,
-            teleport_in_sequence: read_optional_16(&mut input)?,
-            teleport_out_sequence: read_optional_16(&mut input)?,
-            attack_frequency: read_optional_16(&mut input)?,
-            melee_attack: AttackDefinition::read(&mut input, namedbs)?,
-            ranged_attack: AttackDefinition::read(&mut input, namedbs)?,
+            half_visual_arc: read_angle(&mut input)
+                .context(Field("half_visual_arc"))?,
+            half_vertical_visual_arc: read_angle(&mut input)
+                .context(Field("half_vertical_visual_arc"))?,
+            visual_range: read_world_distance(&mut input)
+                .context(Field("visual_range"))?,
+            dark_visual_range: read_world_distance(&mut input)
+                .context(Field("dark_visual_range"))?,
+            intelligence: read_optional_16(&mut input)
+                .context(Field("intelligence"))?,
+            speed: read_world_speed(&mut input).context(Field("speed"))?,
+            gravity: read_world_accel(&mut input).context(Field("gravity"))?,
+            terminal_velocity: read_world_speed(&mut input)
+                .context(Field("terminal_velocity"))?,
+            door_retry_mask: read16(&mut input)
+                .context(Field("door_retry_mask"))?,
+            shrapnel_radius: read_optional_fx_6_10(&mut input)
+                .context(Field("shrapnel_radius"))?,
+            shrapnel_damage: DamageDefinition::read(
+                &mut input,
+                namedbs,
+                diagnostics,
+            )
+            .context(Field("shrapnel_damage"))?,
+            hit_sequence: read_optional_16(&mut input)
+                .context(Field("hit_sequence"))?,
+            hard_dying_sequence: read_optional_16(&mut input)
+                .context(Field("hard_dying_sequence"))?,
+            soft_dying_sequence: read_optional_16(&mut input)
+                .context(Field("soft_dying_sequence"))?,
+            hard_dead_sequence: read_optional_16(&mut input)
+                .context(Field("hard_dead_sequence"))?,
+            soft_dead_sequence: read_optional_16(&mut input)
+                .context(Field("soft_dead_sequence"))?,
+            stationary_sequence: read_optional_16(&mut input)
+                .context(Field("stationary_sequence"))?,
+            moving_sequence: read_optional_16(&mut input)
+                .context(Field("moving_sequence"))?,
+            teleport_in_sequence: read_optional_16(&mut input)
+                .context(Field("teleport_in_sequence"))?,
+            teleport_out_sequence: read_optional_16(&mut input)
+                .context(Field("teleport_out_sequence"))?,
+            attack_frequency: read_optional_16(&mut input)
+                .context(Field("attack_frequency"))?,
+            melee_attack: AttackDefinition::read(&mut input, namedbs)
+                .context(Field("melee_attack"))?,
+            ranged_attack: AttackDefinition::read(&mut input, namedbs)
+                .context(Field("ranged_attack"))?,
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a definition.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.shape(namedbs));
+        out.write16(fields.int16("vitality"));
+        out.write32(fields.bits32("immunities", &namedbs.damage_type_names));
+        out.write32(fields.bits32("weaknesses", &namedbs.damage_type_names));
+        out.write32(fields.flags(
+            "flags",
+            &[MonsterFlags::NAMES, InfinityMonsterFlags::NAMES].concat(),
+        ));
+        out.write32(fields.name32("class", &namedbs.monster_class_names));
+        out.write32(fields.bits32("friends", &namedbs.monster_class_names));
+        out.write32(fields.bits32("enemies", &namedbs.monster_class_names));
+        out.write32(fields.fx_16_16("sound_pitch"));
+        for key in [
+            "activation_sound",
+            "friendly_activation_sound",
+            "clear_sound",
+            "kill_sound",
+            "apology_sound",
+            "friendly_fire_sound",
+            "flaming_sound",
+            "random_sound",
+        ] {
+            out.write16(fields.name16(key, &namedbs.sound_names));
+        }
+        out.write16(fields.int16("random_sound_mask"));
+        out.write16(fields.name16("carrying_item_type", &namedbs.item_names));
+        for key in [
+            "radius",
+            "height",
+            "preferred_hover_height",
+            "minimum_ledge_delta",
+            "maximum_ledge_delta",
+        ] {
+            out.write16(fields.fx_6_10(key));
+        }
+        out.write32(fields.fx_16_16("external_velocity_scale"));
+        for key in ["impact_effect", "melee_impact_effect", "contrail_effect"]
+        {
+            out.write16(fields.name16(key, &namedbs.effect_names));
+        }
+        out.write16(fields.angle("half_visual_arc"));
+        out.write16(fields.angle("half_vertical_visual_arc"));
+        out.write16(fields.fx_6_10("visual_range"));
+        out.write16(fields.fx_6_10("dark_visual_range"));
+        out.write16(fields.optional16("intelligence"));
+        out.write16(fields.fx_6_10("speed"));
+        out.write16(fields.fx_6_10("gravity"));
+        out.write16(fields.fx_6_10("terminal_velocity"));
+        out.write16(fields.int16("door_retry_mask"));
+        out.write16(fields.optional_fx_6_10("shrapnel_radius"));
+        DamageDefinition::write(
+            &fields.object("shrapnel_damage")?,
+            namedbs,
+            out,
+        )?;
+        for key in [
+            "hit_sequence",
+            "hard_dying_sequence",
+            "soft_dying_sequence",
+            "hard_dead_sequence",
+            "soft_dead_sequence",
+            "stationary_sequence",
+            "moving_sequence",
+            "teleport_in_sequence",
+            "teleport_out_sequence",
+            "attack_frequency",
+        ] {
+            out.write16(fields.optional16(key));
+        }
+        for key in ["melee_attack", "ranged_attack"] {
+            let attack = fields.optional_object(key)?;
+            AttackDefinition::write(attack.as_ref(), namedbs, out)?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct EffectFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct EffectFlags {
     pub end_when_animation_loops: bool,
     pub end_when_transfer_animation_loops: bool,
     pub sound_only: bool,
     pub make_twin_visible: bool, // ????
     pub media_effect: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
 }
 
-#[derive(Serialize)]
-struct EffectDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    collection: Option<Value>,
-    clut: Option<u16>,
-    sequence: Option<u16>,
-    sound_pitch: f32,
-    flags: EffectFlags,
-    delay: Option<u16>,
-    delay_sound: Option<Value>,
+decode_flags! {
+    impl EffectFlags: u16 {
+        end_when_animation_loops,
+        end_when_transfer_animation_loops,
+        sound_only,
+        make_twin_visible,
+        media_effect,
+    }
+}
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct EffectDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    pub sequence: Option<u16>,
+    pub sound_pitch: f32,
+    pub flags: EffectFlags,
+    pub delay: Option<u16>,
+    pub delay_sound: Option<Name>,
 }
 
 impl EffectDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<EffectDefinition>> {
-        const SIZE_OF_EFFECT_DEFINITION: usize = 14;
-        if input.len() % SIZE_OF_EFFECT_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of effect definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_EFFECT_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| EffectDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            EFFECT_PHYSICS_TAG,
+            SIZE_OF_EFFECT_DEFINITION,
+            "effect definition",
+            max_records,
+            diagnostics,
+            |x, i, d| EffectDefinition::read(x, namedbs, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<EffectDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
         Ok(EffectDefinition {
-            name: namedbs.effect_names.identify(index),
-            collection,
-            clut,
-            sequence: read_optional_16(&mut input)?,
-            sound_pitch: read_fx_16_16(&mut input)?,
-            flags: decode_flags!(read16(&mut input)? => EffectFlags {
-                end_when_animation_loops,
-                end_when_transfer_animation_loops,
-                sound_only,
-                make_twin_visible,
-                media_effect,
-            }),
-            delay: read_optional_16(&mut input)?,
-            delay_sound: read_optional_16(&mut input)?
+            name: namedbs.effect_names.identify_definition(index),
+            shape,
+            sequence: read_optional_16(&mut input)
+                .context(Field("sequence"))?,
+            sound_pitch: read_fx_16_16(&mut input)
+                .context(Field("sound_pitch"))?,
+            flags: EffectFlags::from_bits(
+                read16(&mut input).context(Field("flags"))?,
+                diagnostics,
+            ),
+            delay: read_optional_16(&mut input).context(Field("delay"))?,
+            delay_sound: read_optional_16(&mut input)
+                .context(Field("delay_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a definition.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.shape(namedbs));
+        out.write16(fields.optional16("sequence"));
+        out.write32(fields.fx_16_16("sound_pitch"));
+        out.write16(fields.flags16("flags", EffectFlags::NAMES));
+        out.write16(fields.optional16("delay"));
+        out.write16(fields.name16("delay_sound", &namedbs.sound_names));
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct ProjectileFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct ProjectileFlags {
     pub guided: bool,
     pub stop_when_animation_loops: bool,
     pub persistent: bool,
@@ -436,144 +741,332 @@ struct ProjectileFlags {
     pub horizontal_wander: bool,
     pub vertical_wander: bool,
     pub affected_by_half_gravity: bool,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub infinity: Option<InfinityProjectileFlags>,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u32,
+}
+
+decode_flags! {
+    impl ProjectileFlags: u32 {
+        guided,
+        stop_when_animation_loops,
+        persistent,
+        alien,
+        affected_by_gravity,
+        no_horizontal_error,
+        no_vertical_error,
+        can_toggle_control_panels,
+        positive_vertical_error,
+        melee,
+        persistent_and_virulent,
+        usually_pass_transparent_side,
+        sometimes_pass_transparent_side,
+        doubly_affected_by_gravity,
+        rebounds_from_floor,
+        penetrates_media,
+        becomes_item_on_detonation,
+        bleeding_projectile,
+        horizontal_wander,
+        vertical_wander,
+        affected_by_half_gravity,
+    }
+    ; infinity: Option<InfinityProjectileFlags>
+}
+
+/// Projectile flags that only Marathon Infinity understands.
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct InfinityProjectileFlags {
     pub penetrates_media_boundary: bool,
     pub passes_through_objects: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u32,
+}
+
+decode_flags! {
+    impl InfinityProjectileFlags: u32 [21..] {
+        penetrates_media_boundary,
+        passes_through_objects,
+    }
+}
+
+impl InfinityProjectileFlags {
+    fn decode(
+        flags: u32,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> Option<InfinityProjectileFlags> {
+        // the first 21 bits are shared with Marathon 2
+        (game == Game::Infinity)
+            .then(|| InfinityProjectileFlags::from_bits(flags, diagnostics))
+    }
+    /// When converting for Marathon 2, the bits that only Infinity knows
+    /// about are unknown, and kept as the reserved bits of the `ProjectileFlags`.
+    fn unknown_to_m2(
+        flags: u32,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> u32 {
+        if game == Game::Infinity {
+            return 0;
+        }
+        let unknown = flags >> 21;
+        warn_unknown_flags(diagnostics, "ProjectileFlags", unknown.into(), 21);
+        unknown << 21
+    }
 }
 
-#[derive(Serialize)]
-struct ProjectileDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    collection: Option<Value>,
-    clut: Option<u16>,
-    sequence: Option<u16>,
-    detonation_effect: Option<Value>,
-    media_detonation_effect: Option<Value>,
-    contrail_effect: Option<Value>,
-    ticks_between_contrails: Option<Value>,
-    maximum_contrails: Option<Value>,
-    media_projectile_promotion: Option<Value>,
-    radius: f32,
-    area_of_effect: f32,
-    damage: DamageDefinition,
-    flags: ProjectileFlags,
-    speed: f32,
-    maximum_range: f32,
-    sound_pitch: f32,
-    flyby_sound: Option<Value>,
-    rebound_sound: Option<Value>,
+// Checked against Aleph One's `projectile_definition`. The 48 bytes break
+// down as: shape (collection/clut + sequence) 4, effects/contrails/promotion
+// 12, radius + area of effect 4, damage 12, flags 4, speed + range 4, sound
+// pitch 4, flyby + rebound sound 4. There is no separate detonation sound.
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct ProjectileDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub shape: Shape,
+    pub sequence: Option<u16>,
+    pub detonation_effect: Option<Name>,
+    pub media_detonation_effect: Option<Name>,
+    pub contrail_effect: Option<Name>,
+    pub ticks_between_contrails: Option<Ticks>,
+    /// NONE means no limit.
+    pub maximum_contrails: Option<u16>,
+    pub media_projectile_promotion: Option<Name>,
+    pub radius: f32,
+    pub area_of_effect: f32,
+    pub damage: DamageDefinition,
+    pub flags: ProjectileFlags,
+    pub speed: f32,
+    pub maximum_range: f32,
+    pub sound_pitch: f32,
+    pub flyby_sound: Option<Name>,
+    pub rebound_sound: Option<Name>,
 }
 
 impl ProjectileDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        game: Game,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<ProjectileDefinition>> {
-        const SIZE_OF_PROJECTILE_DEFINITION: usize = 48;
-        if input.len() % SIZE_OF_PROJECTILE_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of projectile definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_PROJECTILE_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| ProjectileDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            PROJECTILE_PHYSICS_TAG,
+            SIZE_OF_PROJECTILE_DEFINITION,
+            "projectile definition",
+            max_records,
+            diagnostics,
+            |x, i, d| ProjectileDefinition::read(x, namedbs, game, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
+        game: Game,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<ProjectileDefinition> {
-        let collection_and_clut = read_optional_16(&mut input)?;
-        let collection = collection_and_clut
-            .map(|x| namedbs.collection_names.identify(x % 32));
-        let clut = collection_and_clut.map(|x| x / 32);
+        let shape =
+            Shape::read(&mut input, namedbs).context(Field("shape"))?;
+        let sequence =
+            read_optional_16(&mut input).context(Field("sequence"))?;
+        let detonation_effect = read_optional_16(&mut input)
+            .context(Field("detonation_effect"))?
+            .map(|x| namedbs.effect_names.identify(x));
+        let media_detonation_effect = read_optional_16(&mut input)
+            .context(Field("media_detonation_effect"))?
+            .map(|x| namedbs.effect_names.identify(x));
+        let contrail_effect = read_optional_16(&mut input)
+            .context(Field("contrail_effect"))?
+            .map(|x| namedbs.effect_names.identify(x));
+        let ticks_between_contrails =
+            Ticks::read_optional(&mut input, namedbs)
+                .context(Field("ticks_between_contrails"))?;
+        let maximum_contrails = read_optional_16(&mut input)
+            .context(Field("maximum_contrails"))?;
+        let media_projectile_promotion = read_optional_16(&mut input)
+            .context(Field("media_projectile_promotion"))?
+            .map(|x| namedbs.projectile_names.identify(x));
+        let radius =
+            read_world_distance(&mut input).context(Field("radius"))?;
+        let area_of_effect = read_world_distance(&mut input)
+            .context(Field("area_of_effect"))?;
+        let damage = DamageDefinition::read(&mut input, namedbs, diagnostics)
+            .context(Field("damage"))?;
+        let flags = read32(&mut input).context(Field("flags"))?;
         Ok(ProjectileDefinition {
-            name: namedbs.projectile_names.identify(index),
-            collection,
-            clut,
-            sequence: read_optional_16(&mut input)?,
-            detonation_effect: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            media_detonation_effect: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            contrail_effect: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            ticks_between_contrails: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            maximum_contrails: read_optional_16(&mut input)?
-                .map(|x| namedbs.effect_names.identify(x)),
-            media_projectile_promotion: read_optional_16(&mut input)?
-                .map(|x| namedbs.projectile_names.identify(x)),
-            radius: read_world_distance(&mut input)?,
-            area_of_effect: read_world_distance(&mut input)?,
-            damage: DamageDefinition::read(&mut input, namedbs)?,
-            flags: decode_flags!(read32(&mut input)? => ProjectileFlags {
-                guided,
-                stop_when_animation_loops,
-                persistent,
-                alien,
-                affected_by_gravity,
-                no_horizontal_error,
-                no_vertical_error,
-                can_toggle_control_panels,
-                positive_vertical_error,
-                melee,
-                persistent_and_virulent,
-                usually_pass_transparent_side,
-                sometimes_pass_transparent_side,
-                doubly_affected_by_gravity,
-                rebounds_from_floor,
-                penetrates_media,
-                becomes_item_on_detonation,
-                bleeding_projectile,
-                horizontal_wander,
-                vertical_wander,
-                affected_by_half_gravity,
-                penetrates_media_boundary,
-                passes_through_objects,
-            }),
-            speed: read_world_speed(&mut input)?,
-            maximum_range: read_world_distance(&mut input)?,
-            sound_pitch: read_fx_16_16(&mut input)?,
-            flyby_sound: read_optional_16(&mut input)?
+            name: namedbs.projectile_names.identify_definition(index),
+            shape,
+            sequence,
+            detonation_effect,
+            media_detonation_effect,
+            contrail_effect,
+            ticks_between_contrails,
+            maximum_contrails,
+            media_projectile_promotion,
+            radius,
+            area_of_effect,
+            damage,
+            flags: ProjectileFlags::from_bits(
+                flags,
+                InfinityProjectileFlags::decode(flags, game, diagnostics),
+                InfinityProjectileFlags::unknown_to_m2(
+                    flags,
+                    game,
+                    diagnostics,
+                ),
+            ),
+            speed: read_world_speed(&mut input).context(Field("speed"))?,
+            maximum_range: read_world_distance(&mut input)
+                .context(Field("maximum_range"))?,
+            sound_pitch: read_fx_16_16(&mut input)
+                .context(Field("sound_pitch"))?,
+            flyby_sound: read_optional_16(&mut input)
+                .context(Field("flyby_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
-            rebound_sound: read_optional_16(&mut input)?
+            rebound_sound: read_optional_16(&mut input)
+                .context(Field("rebound_sound"))?
                 .map(|x| namedbs.sound_names.identify(x)),
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a definition.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.shape(namedbs));
+        out.write16(fields.optional16("sequence"));
+        for key in [
+            "detonation_effect",
+            "media_detonation_effect",
+            "contrail_effect",
+        ] {
+            out.write16(fields.name16(key, &namedbs.effect_names));
+        }
+        out.write16(fields.ticks("ticks_between_contrails"));
+        out.write16(fields.optional16("maximum_contrails"));
+        out.write16(
+            fields.name16(
+                "media_projectile_promotion",
+                &namedbs.projectile_names,
+            ),
+        );
+        out.write16(fields.fx_6_10("radius"));
+        out.write16(fields.fx_6_10("area_of_effect"));
+        DamageDefinition::write(&fields.object("damage")?, namedbs, out)?;
+        out.write32(fields.flags(
+            "flags",
+            &[ProjectileFlags::NAMES, InfinityProjectileFlags::NAMES].concat(),
+        ));
+        out.write16(fields.fx_6_10("speed"));
+        out.write16(fields.fx_6_10("maximum_range"));
+        out.write32(fields.fx_16_16("sound_pitch"));
+        out.write16(fields.name16("flyby_sound", &namedbs.sound_names));
+        out.write16(fields.name16("rebound_sound", &namedbs.sound_names));
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct WeaponFlags {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct WeaponFlags {
     pub is_automatic: bool,
     pub disappears_after_use: bool,
     pub plays_instant_shell_casing_sound: bool,
     pub overloads: bool,
     pub has_random_ammo_on_pickup: bool,
-    pub powerup_is_temporary: bool, // ???
+    /// The `powerup_type` item only lasts for `powerup_ticks`. Aleph One
+    /// reads this flag, but nothing acts on it.
+    pub powerup_is_temporary: bool,
     pub reloads_in_one_hand: bool,
     pub fires_out_of_phase: bool,
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub infinity: Option<InfinityWeaponFlags>,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
+}
+
+decode_flags! {
+    impl WeaponFlags: u16 {
+        is_automatic,
+        disappears_after_use,
+        plays_instant_shell_casing_sound,
+        overloads,
+        has_random_ammo_on_pickup,
+        powerup_is_temporary,
+        reloads_in_one_hand,
+        fires_out_of_phase,
+    }
+    ; infinity: Option<InfinityWeaponFlags>
+}
+
+/// Weapon flags that only Marathon Infinity understands.
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct InfinityWeaponFlags {
     pub fires_under_media: bool,
     pub triggers_share_ammo: bool,
     pub secondary_has_angular_flipping: bool,
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "is_zero"))]
+    pub _reserved_bits: u16,
+}
+
+decode_flags! {
+    impl InfinityWeaponFlags: u16 [8..] {
+        fires_under_media,
+        triggers_share_ammo,
+        secondary_has_angular_flipping,
+    }
+}
+
+impl InfinityWeaponFlags {
+    fn decode(
+        flags: u16,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> Option<InfinityWeaponFlags> {
+        // the first 8 bits are shared with Marathon 2
+        (game == Game::Infinity)
+            .then(|| InfinityWeaponFlags::from_bits(flags, diagnostics))
+    }
+    /// When converting for Marathon 2, the bits that only Infinity knows
+    /// about are unknown, and kept as the reserved bits of the `WeaponFlags`.
+    fn unknown_to_m2(
+        flags: u16,
+        game: Game,
+        diagnostics: &Diagnostics,
+    ) -> u16 {
+        if game == Game::Infinity {
+            return 0;
+        }
+        let unknown = flags >> 8;
+        warn_unknown_flags(diagnostics, "WeaponFlags", unknown.into(), 8);
+        unknown << 8
+    }
 }
 
-#[derive(Serialize)]
-struct TriggerDefinition {
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct TriggerDefinition {
     pub rounds_per_magazine: Option<u16>,
-    pub ammunition_type: Option<Value>,
-    pub ticks_per_round: Option<u16>,
-    pub recovery_ticks: Option<u16>,
-    pub charging_ticks: Option<u16>,
+    pub ammunition_type: Option<Name>,
+    pub ticks_per_round: Option<Ticks>,
+    pub recovery_ticks: Option<Ticks>,
+    pub charging_ticks: Option<Ticks>,
     pub recoil_magnitude: f32,
-    pub firing_sound: Option<Value>,
-    pub click_sound: Option<Value>,
-    pub charging_sound: Option<Value>,
-    pub shell_casing_sound: Option<Value>,
-    pub reloading_sound: Option<Value>,
-    pub charged_sound: Option<Value>,
-    pub projectile_type: Option<Value>,
+    pub firing_sound: Option<Name>,
+    pub click_sound: Option<Name>,
+    pub charging_sound: Option<Name>,
+    pub shell_casing_sound: Option<Name>,
+    pub reloading_sound: Option<Name>,
+    pub charged_sound: Option<Name>,
+    pub projectile_type: Option<Name>,
     pub theta_error: f32,
     pub dx: f32,
     pub dz: f32,
@@ -591,9 +1084,9 @@ impl TriggerDefinition {
             rounds_per_magazine: read_optional_16(&mut input)?,
             ammunition_type: read_optional_16(&mut input)?
                 .map(|x| namedbs.item_names.identify(x)),
-            ticks_per_round: read_optional_16(&mut input)?,
-            recovery_ticks: read_optional_16(&mut input)?,
-            charging_ticks: read_optional_16(&mut input)?,
+            ticks_per_round: Ticks::read_optional(&mut input, namedbs)?,
+            recovery_ticks: Ticks::read_optional(&mut input, namedbs)?,
+            charging_ticks: Ticks::read_optional(&mut input, namedbs)?,
             recoil_magnitude: read_world_distance(&mut input)?,
             firing_sound: read_optional_16(&mut input)?
                 .map(|x| namedbs.sound_names.identify(x)),
@@ -616,111 +1109,267 @@ impl TriggerDefinition {
             burst_count: read_optional_16(&mut input)?,
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a trigger.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.optional16("rounds_per_magazine"));
+        out.write16(fields.name16("ammunition_type", &namedbs.item_names));
+        out.write16(fields.ticks("ticks_per_round"));
+        out.write16(fields.ticks("recovery_ticks"));
+        out.write16(fields.ticks("charging_ticks"));
+        out.write16(fields.fx_6_10("recoil_magnitude"));
+        for key in [
+            "firing_sound",
+            "click_sound",
+            "charging_sound",
+            "shell_casing_sound",
+            "reloading_sound",
+            "charged_sound",
+        ] {
+            out.write16(fields.name16(key, &namedbs.sound_names));
+        }
+        out.write16(
+            fields.name16("projectile_type", &namedbs.projectile_names),
+        );
+        out.write16(fields.angle("theta_error"));
+        out.write16(fields.fx_6_10("dx"));
+        out.write16(fields.fx_6_10("dz"));
+        out.write16(fields.optional16("shell_casing_type"));
+        out.write16(fields.optional16("burst_count"));
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct WeaponDefinition {
-    #[serde(skip_serializing_if = "serde_json::Value::is_number")]
-    name: Value,
-    item_type: Option<Value>,
-    powerup_type: Option<Value>, // ??????
-    weapon_class: Option<Value>,
-    flags: WeaponFlags,
-    firing_light_intensity: f32,
-    firing_intensity_decay_ticks: Option<u16>,
-    idle_height: f32,
-    bob_amplitude: f32,
-    kick_height: f32,
-    reload_height: f32,
-    idle_width: f32,
-    horizontal_amplitude: f32,
-    collection: Option<u16>,
-    idle_sequence: Option<u16>,
-    firing_sequence: Option<u16>,
-    reloading_sequence: Option<u16>,
-    #[serde(skip)]
-    _unused: u16,
-    charging_sequence: Option<u16>,
-    charged_sequence: Option<u16>,
-    ready_ticks: Option<u16>,
-    await_reload_ticks: Option<u16>,
-    loading_ticks: Option<u16>,
-    finish_loading_ticks: Option<u16>,
-    powerup_ticks: Option<u16>,
-    triggers: [TriggerDefinition; 2],
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct WeaponDefinition {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Name::is_number")
+    )]
+    pub name: Name,
+    pub item_type: Option<Name>,
+    /// An item type, not a count: the item that would power this weapon up.
+    /// NONE for every weapon in the original games. Aleph One reads it, but
+    /// nothing acts on it.
+    pub powerup_type: Option<Name>,
+    pub weapon_class: Option<Name>,
+    pub flags: WeaponFlags,
+    pub firing_light_intensity: f32,
+    pub firing_intensity_decay_ticks: Option<Ticks>,
+    pub idle_height: f32,
+    pub bob_amplitude: f32,
+    pub kick_height: f32,
+    pub reload_height: f32,
+    pub idle_width: f32,
+    pub horizontal_amplitude: f32,
+    pub collection: Option<u16>,
+    pub idle_sequence: Option<u16>,
+    pub firing_sequence: Option<u16>,
+    pub reloading_sequence: Option<u16>,
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub _unused: u16,
+    pub charging_sequence: Option<u16>,
+    pub charged_sequence: Option<u16>,
+    pub ready_ticks: Option<Ticks>,
+    pub await_reload_ticks: Option<Ticks>,
+    pub loading_ticks: Option<Ticks>,
+    pub finish_loading_ticks: Option<Ticks>,
+    /// How long a `powerup_type` powerup lasts, in ticks. Unused, like
+    /// `powerup_type`.
+    pub powerup_ticks: Option<Ticks>,
+    pub triggers: Triggers<TriggerDefinition>,
 }
 
 impl WeaponDefinition {
     pub fn read_definitions(
         input: &[u8],
         namedbs: &NameDbs,
+        game: Game,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<WeaponDefinition>> {
-        const SIZE_OF_WEAPON_DEFINITION: usize = 134;
-        if input.len() % SIZE_OF_WEAPON_DEFINITION != 0 {
-            return Err(anyhow!("non-integer number of weapon definitions, or corrupted/misdetected physics file"));
-        }
-        input
-            .chunks_exact(SIZE_OF_WEAPON_DEFINITION)
-            .enumerate()
-            .map(|(i, x)| WeaponDefinition::read(x, namedbs, i))
-            .collect()
+        read_records(
+            input,
+            WEAPON_PHYSICS_TAG,
+            SIZE_OF_WEAPON_DEFINITION,
+            "weapon definition",
+            max_records,
+            diagnostics,
+            |x, i, d| WeaponDefinition::read(x, namedbs, game, i, d),
+        )
     }
     pub fn read(
         mut input: impl Read,
         namedbs: &NameDbs,
+        game: Game,
         index: usize,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<WeaponDefinition> {
+        let item_type = read_optional_16(&mut input)
+            .context(Field("item_type"))?
+            .map(|x| namedbs.item_names.identify(x));
+        let powerup_type = read_optional_16(&mut input)
+            .context(Field("powerup_type"))?
+            .map(|x| namedbs.item_names.identify(x));
+        let weapon_class = read_optional_16(&mut input)
+            .context(Field("weapon_class"))?
+            .map(|x| namedbs.weapon_class_names.identify(x));
+        let flags = read16(&mut input).context(Field("flags"))?;
         Ok(WeaponDefinition {
-            name: namedbs.weapon_names.identify(index),
-            item_type: read_optional_16(&mut input)?
-                .map(|x| namedbs.item_names.identify(x)),
-            powerup_type: read_optional_16(&mut input)?
-                .map(|x| namedbs.item_names.identify(x)),
-            weapon_class: read_optional_16(&mut input)?
-                .map(|x| namedbs.weapon_class_names.identify(x)),
-            flags: decode_flags!(read16(&mut input)? => WeaponFlags {
-                is_automatic,
-                disappears_after_use,
-                plays_instant_shell_casing_sound,
-                overloads,
-                has_random_ammo_on_pickup,
-                powerup_is_temporary,
-                reloads_in_one_hand,
-                fires_out_of_phase,
-                fires_under_media,
-                triggers_share_ammo,
-                secondary_has_angular_flipping,
-            }),
-            firing_light_intensity: read_fx_16_16(&mut input)?,
-            firing_intensity_decay_ticks: read_optional_16(&mut input)?,
-            idle_height: read_fx_16_16(&mut input)?,
-            bob_amplitude: read_fx_16_16(&mut input)?,
-            kick_height: read_fx_16_16(&mut input)?,
-            reload_height: read_fx_16_16(&mut input)?,
-            idle_width: read_fx_16_16(&mut input)?,
-            horizontal_amplitude: read_fx_16_16(&mut input)?,
-            collection: read_optional_16(&mut input)?,
-            idle_sequence: read_optional_16(&mut input)?,
-            firing_sequence: read_optional_16(&mut input)?,
-            reloading_sequence: read_optional_16(&mut input)?,
-            _unused: read16(&mut input)?,
-            charging_sequence: read_optional_16(&mut input)?,
-            charged_sequence: read_optional_16(&mut input)?,
-            ready_ticks: read_optional_16(&mut input)?,
-            await_reload_ticks: read_optional_16(&mut input)?,
-            loading_ticks: read_optional_16(&mut input)?,
-            finish_loading_ticks: read_optional_16(&mut input)?,
-            powerup_ticks: read_optional_16(&mut input)?,
-            triggers: [
-                TriggerDefinition::read(&mut input, namedbs, 0)?,
-                TriggerDefinition::read(&mut input, namedbs, 1)?,
-            ],
+            name: namedbs.weapon_names.identify_definition(index),
+            item_type,
+            powerup_type,
+            weapon_class,
+            flags: WeaponFlags::from_bits(
+                flags,
+                InfinityWeaponFlags::decode(flags, game, diagnostics),
+                InfinityWeaponFlags::unknown_to_m2(flags, game, diagnostics),
+            ),
+            firing_light_intensity: read_fx_16_16(&mut input)
+                .context(Field("firing_light_intensity"))?,
+            firing_intensity_decay_ticks: Ticks::read_optional(
+                &mut input, namedbs,
+            )
+            .context(Field("firing_intensity_decay_ticks"))?,
+            idle_height: read_fx_16_16(&mut input)
+                .context(Field("idle_height"))?,
+            bob_amplitude: read_fx_16_16(&mut input)
+                .context(Field("bob_amplitude"))?,
+            kick_height: read_fx_16_16(&mut input)
+                .context(Field("kick_height"))?,
+            reload_height: read_fx_16_16(&mut input)
+                .context(Field("reload_height"))?,
+            idle_width: read_fx_16_16(&mut input)
+                .context(Field("idle_width"))?,
+            horizontal_amplitude: read_fx_16_16(&mut input)
+                .context(Field("horizontal_amplitude"))?,
+            collection: read_optional_16(&mut input)
+                .context(Field("collection"))?,
+            idle_sequence: read_optional_16(&mut input)
+                .context(Field("idle_sequence"))?,
+            firing_sequence: read_optional_16(&mut input)
+                .context(Field("firing_sequence"))?,
+            reloading_sequence: read_optional_16(&mut input)
+                .context(Field("reloading_sequence"))?,
+            _unused: read16(&mut input).context(Field("_unused"))?,
+            charging_sequence: read_optional_16(&mut input)
+                .context(Field("charging_sequence"))?,
+            charged_sequence: read_optional_16(&mut input)
+                .context(Field("charged_sequence"))?,
+            ready_ticks: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("ready_ticks"))?,
+            await_reload_ticks: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("await_reload_ticks"))?,
+            loading_ticks: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("loading_ticks"))?,
+            finish_loading_ticks: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("finish_loading_ticks"))?,
+            powerup_ticks: Ticks::read_optional(&mut input, namedbs)
+                .context(Field("powerup_ticks"))?,
+            triggers: Triggers::new(
+                [
+                    TriggerDefinition::read(&mut input, namedbs, 0)
+                        .context(Field("triggers/0"))?,
+                    TriggerDefinition::read(&mut input, namedbs, 1)
+                        .context(Field("triggers/1"))?,
+                ],
+                namedbs,
+            ),
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a definition.
+    pub fn write(
+        fields: &Fields,
+        namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        out.write16(fields.name16("item_type", &namedbs.item_names));
+        out.write16(fields.name16("powerup_type", &namedbs.item_names));
+        out.write16(
+            fields.name16("weapon_class", &namedbs.weapon_class_names),
+        );
+        out.write16(fields.flags16(
+            "flags",
+            &[WeaponFlags::NAMES, InfinityWeaponFlags::NAMES].concat(),
+        ));
+        out.write32(fields.fx_16_16("firing_light_intensity"));
+        out.write16(fields.ticks("firing_intensity_decay_ticks"));
+        for key in [
+            "idle_height",
+            "bob_amplitude",
+            "kick_height",
+            "reload_height",
+            "idle_width",
+            "horizontal_amplitude",
+        ] {
+            out.write32(fields.fx_16_16(key));
+        }
+        for key in [
+            "collection",
+            "idle_sequence",
+            "firing_sequence",
+            "reloading_sequence",
+        ] {
+            out.write16(fields.optional16(key));
+        }
+        // _unused isn't in the JSON, so it keeps whatever it had
+        out.skip(2);
+        out.write16(fields.optional16("charging_sequence"));
+        out.write16(fields.optional16("charged_sequence"));
+        for key in [
+            "ready_ticks",
+            "await_reload_ticks",
+            "loading_ticks",
+            "finish_loading_ticks",
+            "powerup_ticks",
+        ] {
+            out.write16(fields.ticks(key));
+        }
+        for trigger in fields.triggers()? {
+            TriggerDefinition::write(&trigger, namedbs, out)?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct PhysicsDefinition {
+#[cfg(feature = "json")]
+/// Every field of a physics model is 16.16 fixed point, in this order.
+const PHYSICS_FIELD_NAMES: [&str; 26] = [
+    "maximum_forward_velocity",
+    "maximum_backward_velocity",
+    "maximum_perpendicular_velocity",
+    "acceleration",
+    "deceleration",
+    "airborne_deceleration",
+    "gravitational_acceleration",
+    "climbing_acceleration",
+    "terminal_velocity",
+    "external_deceleration",
+    "angular_acceleration",
+    "angular_deceleration",
+    "maximum_angular_velocity",
+    "angular_recentering_velocity",
+    "fast_angular_velocity",
+    "fast_angular_maximum",
+    "maximum_elevation",
+    "external_angular_deceleration",
+    "step_delta",
+    "step_amplitude",
+    "radius",
+    "height",
+    "dead_height",
+    "camera_height",
+    "splash_height",
+    "half_camera_separation",
+];
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct PhysicsDefinition {
     pub maximum_forward_velocity: f32,
     pub maximum_backward_velocity: f32,
     pub maximum_perpendicular_velocity: f32,
@@ -755,91 +1404,868 @@ impl PhysicsDefinition {
         _namedb: &NameDbs,
     ) -> anyhow::Result<PhysicsDefinition> {
         Ok(PhysicsDefinition {
-            maximum_forward_velocity: read_fx_16_16(&mut input)?,
-            maximum_backward_velocity: read_fx_16_16(&mut input)?,
-            maximum_perpendicular_velocity: read_fx_16_16(&mut input)?,
-            acceleration: read_fx_16_16(&mut input)?,
-            deceleration: read_fx_16_16(&mut input)?,
-            airborne_deceleration: read_fx_16_16(&mut input)?,
-            gravitational_acceleration: read_fx_16_16(&mut input)?,
-            climbing_acceleration: read_fx_16_16(&mut input)?,
-            terminal_velocity: read_fx_16_16(&mut input)?,
-            external_deceleration: read_fx_16_16(&mut input)?,
-            angular_acceleration: read_fx_16_16(&mut input)?,
-            angular_deceleration: read_fx_16_16(&mut input)?,
-            maximum_angular_velocity: read_fx_16_16(&mut input)?,
-            angular_recentering_velocity: read_fx_16_16(&mut input)?,
-            fast_angular_velocity: read_fx_16_16(&mut input)?,
-            fast_angular_maximum: read_fx_16_16(&mut input)?,
-            maximum_elevation: read_fx_16_16(&mut input)?,
-            external_angular_deceleration: read_fx_16_16(&mut input)?,
-            step_delta: read_fx_16_16(&mut input)?,
-            step_amplitude: read_fx_16_16(&mut input)?,
-            radius: read_fx_16_16(&mut input)?,
-            height: read_fx_16_16(&mut input)?,
-            dead_height: read_fx_16_16(&mut input)?,
-            camera_height: read_fx_16_16(&mut input)?,
-            splash_height: read_fx_16_16(&mut input)?,
-            half_camera_separation: read_fx_16_16(&mut input)?,
+            maximum_forward_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_forward_velocity"))?,
+            maximum_backward_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_backward_velocity"))?,
+            maximum_perpendicular_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_perpendicular_velocity"))?,
+            acceleration: read_fx_16_16(&mut input)
+                .context(Field("acceleration"))?,
+            deceleration: read_fx_16_16(&mut input)
+                .context(Field("deceleration"))?,
+            airborne_deceleration: read_fx_16_16(&mut input)
+                .context(Field("airborne_deceleration"))?,
+            gravitational_acceleration: read_fx_16_16(&mut input)
+                .context(Field("gravitational_acceleration"))?,
+            climbing_acceleration: read_fx_16_16(&mut input)
+                .context(Field("climbing_acceleration"))?,
+            terminal_velocity: read_fx_16_16(&mut input)
+                .context(Field("terminal_velocity"))?,
+            external_deceleration: read_fx_16_16(&mut input)
+                .context(Field("external_deceleration"))?,
+            angular_acceleration: read_fx_16_16(&mut input)
+                .context(Field("angular_acceleration"))?,
+            angular_deceleration: read_fx_16_16(&mut input)
+                .context(Field("angular_deceleration"))?,
+            maximum_angular_velocity: read_fx_16_16(&mut input)
+                .context(Field("maximum_angular_velocity"))?,
+            angular_recentering_velocity: read_fx_16_16(&mut input)
+                .context(Field("angular_recentering_velocity"))?,
+            fast_angular_velocity: read_fx_16_16(&mut input)
+                .context(Field("fast_angular_velocity"))?,
+            fast_angular_maximum: read_fx_16_16(&mut input)
+                .context(Field("fast_angular_maximum"))?,
+            maximum_elevation: read_fx_16_16(&mut input)
+                .context(Field("maximum_elevation"))?,
+            external_angular_deceleration: read_fx_16_16(&mut input)
+                .context(Field("external_angular_deceleration"))?,
+            step_delta: read_fx_16_16(&mut input)
+                .context(Field("step_delta"))?,
+            step_amplitude: read_fx_16_16(&mut input)
+                .context(Field("step_amplitude"))?,
+            radius: read_fx_16_16(&mut input).context(Field("radius"))?,
+            height: read_fx_16_16(&mut input).context(Field("height"))?,
+            dead_height: read_fx_16_16(&mut input)
+                .context(Field("dead_height"))?,
+            camera_height: read_fx_16_16(&mut input)
+                .context(Field("camera_height"))?,
+            splash_height: read_fx_16_16(&mut input)
+                .context(Field("splash_height"))?,
+            half_camera_separation: read_fx_16_16(&mut input)
+                .context(Field("half_camera_separation"))?,
         })
     }
+    #[cfg(feature = "json")]
+    /// The inverse of `read`, from the JSON form of a physics model.
+    pub fn write(
+        fields: &Fields,
+        _namedbs: &NameDbs,
+        out: &mut RecordWriter,
+    ) -> anyhow::Result<()> {
+        for key in PHYSICS_FIELD_NAMES {
+            out.write32(fields.fx_16_16(key));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize)]
-struct PhysicsDefinitions {
-    walking: PhysicsDefinition,
-    running: PhysicsDefinition,
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct PhysicsDefinitions {
+    pub walking: PhysicsDefinition,
+    pub running: PhysicsDefinition,
+    /// Any models after the first two. The engine only uses walking and
+    /// running, but these are output rather than silently dropped.
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Vec::is_empty")
+    )]
+    pub extra: Vec<PhysicsDefinition>,
 }
 
 impl PhysicsDefinitions {
     pub fn read(
-        mut input: impl Read,
+        input: &[u8],
         namedb: &NameDbs,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<PhysicsDefinitions> {
+        let models = read_records(
+            input,
+            PHYSICS_PHYSICS_TAG,
+            SIZE_OF_PHYSICS_DEFINITION,
+            "physics model",
+            max_records,
+            diagnostics,
+            |x, _, _| PhysicsDefinition::read(x, namedb),
+        )?;
+        let kind = String::from_utf8_lossy(&PHYSICS_PHYSICS_TAG);
+        let count = models.len();
+        let mut models = models.into_iter();
+        let (Some(walking), Some(running)) = (models.next(), models.next())
+        else {
+            return Err(anyhow!(
+                "{kind:?} chunk: expected 2 physics models (walking and \
+                 running), but there are only {count}"
+            ));
+        };
+        if count > 2 {
+            diagnostics.warn(
+                WarningKind::ExtraPhysicsModels,
+                format_args!(
+                    "{kind:?} chunk: expected 2 physics models (walking and \
+                     running), but there are {count}"
+                ),
+            );
+        }
         Ok(PhysicsDefinitions {
-            walking: PhysicsDefinition::read(&mut input, namedb)?,
-            running: PhysicsDefinition::read(&mut input, namedb)?,
+            walking,
+            running,
+            extra: models.collect(),
         })
     }
+    /// How many physics models there are, normally 2.
+    pub fn count(&self) -> usize {
+        2 + self.extra.len()
+    }
+}
+
+#[cfg_attr(feature = "json", derive(Serialize, JsonSchema))]
+pub struct Physics {
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub monster_definitions: Option<Vec<MonsterDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub effect_definitions: Option<Vec<EffectDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub projectile_definitions: Option<Vec<ProjectileDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub weapon_definitions: Option<Vec<WeaponDefinition>>,
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub physics: Option<PhysicsDefinitions>,
 }
 
-#[derive(Serialize)]
-struct Physics {
-    monster_definitions: Vec<MonsterDefinition>,
-    effect_definitions: Vec<EffectDefinition>,
-    projectile_definitions: Vec<ProjectileDefinition>,
-    weapon_definitions: Vec<WeaponDefinition>,
-    physics: PhysicsDefinitions,
+impl Physics {
+    #[cfg(feature = "json")]
+    /// Writes each definition to its own file under `dir`, for `--explode`.
+    pub fn explode(
+        &self,
+        dir: &Path,
+        options: &ConvertOptions,
+    ) -> anyhow::Result<()> {
+        if let Some(x) = &self.monster_definitions {
+            explode_table(&dir.join("monsters"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.effect_definitions {
+            explode_table(&dir.join("effects"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.projectile_definitions {
+            explode_table(&dir.join("projectiles"), x, |x| &x.name, options)?;
+        }
+        if let Some(x) = &self.weapon_definitions {
+            explode_table(&dir.join("weapons"), x, |x| &x.name, options)?;
+        }
+        if let Some(physics) = &self.physics {
+            let dir = dir.join("physics");
+            let models = [&physics.walking, &physics.running]
+                .into_iter()
+                .chain(&physics.extra);
+            for (index, definition) in models.enumerate() {
+                let stem = match index {
+                    0 => "walking".to_string(),
+                    1 => "running".to_string(),
+                    _ => index.to_string(),
+                };
+                let value = Indexed { index, definition };
+                write_exploded(&dir, &stem, &value, options)?;
+            }
+        }
+        Ok(())
+    }
+    /// Reads the tables out of the given chunks. Tables not listed in `only`
+    /// are skipped, unless it's empty. Tables whose chunks are missing are
+    /// skipped with a warning. A table with more than `max_records` records
+    /// is an error.
+    pub fn read(
+        chunks: &[Chunk],
+        namedbs: &NameDbs,
+        only: &[Table],
+        game: Game,
+        max_records: Option<usize>,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Physics> {
+        let (
+            monster_definitions,
+            effect_definitions,
+            projectile_definitions,
+            weapon_definitions,
+            physics_definitions,
+        ) = join5(
+            || {
+                read_table(
+                    only,
+                    Table::Monsters,
+                    chunks,
+                    MONSTER_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        MonsterDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            game,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Effects,
+                    chunks,
+                    EFFECT_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        EffectDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Projectiles,
+                    chunks,
+                    PROJECTILE_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        ProjectileDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            game,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Weapons,
+                    chunks,
+                    WEAPON_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        WeaponDefinition::read_definitions(
+                            x,
+                            namedbs,
+                            game,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+            || {
+                read_table(
+                    only,
+                    Table::Physics,
+                    chunks,
+                    PHYSICS_PHYSICS_TAG,
+                    diagnostics,
+                    |x| {
+                        PhysicsDefinitions::read(
+                            x,
+                            namedbs,
+                            max_records,
+                            diagnostics,
+                        )
+                    },
+                )
+            },
+        );
+        let monster_definitions = monster_definitions?;
+        let effect_definitions = effect_definitions?;
+        let projectile_definitions = projectile_definitions?;
+        let weapon_definitions = weapon_definitions?;
+        let physics_definitions = physics_definitions?;
+        if monster_definitions.is_none()
+            && effect_definitions.is_none()
+            && projectile_definitions.is_none()
+            && weapon_definitions.is_none()
+            && physics_definitions.is_none()
+        {
+            return Err(PhysicsEaterError::NoPhysicsChunks.into());
+        }
+        Ok(Physics {
+            monster_definitions,
+            effect_definitions,
+            projectile_definitions,
+            weapon_definitions,
+            physics: physics_definitions,
+        })
+    }
 }
 
+#[cfg(feature = "json")]
 pub fn convert_physics(
     physics_path: PathBuf,
-    namedbs: NameDbs,
+    mut namedbs: NameDbs,
+    options: &ConvertOptions,
+    game: Game,
+    parent: Option<&Wad>,
+    lenient: bool,
+    diagnostics: &Diagnostics,
 ) -> anyhow::Result<()> {
-    let physics_wad = Wad::read_wad(File::open(physics_path)?)?;
-    let monster_definitions =
-        Chunk::find(&physics_wad.files[0], MONSTER_PHYSICS_TAG)
-            .and_then(|x| MonsterDefinition::read_definitions(x, &namedbs))?;
-    let effect_definitions =
-        Chunk::find(&physics_wad.files[0], EFFECT_PHYSICS_TAG)
-            .and_then(|x| EffectDefinition::read_definitions(x, &namedbs))?;
-    let projectile_definitions =
-        Chunk::find(&physics_wad.files[0], PROJECTILE_PHYSICS_TAG).and_then(
-            |x| ProjectileDefinition::read_definitions(x, &namedbs),
-        )?;
-    let weapon_definitions =
-        Chunk::find(&physics_wad.files[0], WEAPON_PHYSICS_TAG)
-            .and_then(|x| WeaponDefinition::read_definitions(x, &namedbs))?;
-    let physics_definitions =
-        Chunk::find(&physics_wad.files[0], PHYSICS_PHYSICS_TAG)
-            .and_then(|x| PhysicsDefinitions::read(x, &namedbs))?;
-    let physics = Physics {
-        monster_definitions,
-        effect_definitions,
-        projectile_definitions,
-        weapon_definitions,
-        physics: physics_definitions,
+    let diagnostics = &diagnostics.at(physics_path.display());
+    let bare_table = options.bare_table()?;
+    let mut physics_wad = Wad::open(&physics_path, lenient, diagnostics)?;
+    if let Some(parent) = parent {
+        physics_wad = physics_wad.overlay_onto(parent).with_context(|| {
+            format!("unable to overlay {physics_path:?} onto its parent")
+        })?;
+    } else if physics_wad.parent_checksum != 0 {
+        diagnostics.warn(
+            WarningKind::MissingParent,
+            format_args!(
+                "this is an overlay of a WAD with checksum {:08X}, and may be \
+                 missing chunks that only its parent has (use --parent to \
+                 give it)",
+                physics_wad.parent_checksum
+            ),
+        );
+    }
+    let chunks = physics_wad
+        .files
+        .first()
+        .ok_or(PhysicsEaterError::NoSubfiles)?;
+    options.load_chunk_names(&mut namedbs, chunks, diagnostics)?;
+    let physics = Physics::read(
+        chunks,
+        &namedbs,
+        &options.only,
+        game,
+        options.max_records,
+        diagnostics,
+    )?;
+    options.check_shapes(&physics, &namedbs, diagnostics)?;
+    options.check_references(&physics, &namedbs, diagnostics)?;
+    diagnostics.check()?;
+    let record_counts = [
+        (
+            MONSTER_PHYSICS_TAG,
+            physics.monster_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            EFFECT_PHYSICS_TAG,
+            physics.effect_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            PROJECTILE_PHYSICS_TAG,
+            physics.projectile_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            WEAPON_PHYSICS_TAG,
+            physics.weapon_definitions.as_ref().map(Vec::len),
+        ),
+        (
+            PHYSICS_PHYSICS_TAG,
+            physics.physics.as_ref().map(PhysicsDefinitions::count),
+        ),
+    ];
+    options.report_chunks(chunks, &record_counts, diagnostics);
+    if let Some(dir) = &options.explode {
+        return physics.explode(dir, options);
+    }
+    match bare_table {
+        None if options.offsets || options.embed_namedb => {
+            let layout = options
+                .offsets
+                .then(|| ChunkLayout::describe(chunks, &record_counts));
+            let physics = Annotated {
+                layout,
+                namedb: options.embed_namedb.then(|| namedbs.tables()),
+                physics: &physics,
+            };
+            write_output(&physics, options)
+        }
+        None => write_output(&physics, options),
+        Some(Table::Monsters) => {
+            write_output(&physics.monster_definitions, options)
+        }
+        Some(Table::Effects) => {
+            write_output(&physics.effect_definitions, options)
+        }
+        Some(Table::Projectiles) => {
+            write_output(&physics.projectile_definitions, options)
+        }
+        Some(Table::Weapons) => {
+            write_output(&physics.weapon_definitions, options)
+        }
+        Some(Table::Physics) => write_output(&physics.physics, options),
+    }
+}
+
+#[cfg(feature = "json")]
+/// Patches the records of one chunk, which are `record_size` bytes each.
+/// `records` are the chunk's records in JSON form, and `patch` is an object
+/// keyed by whatever `index_of` understands. Everything wrong with the patch
+/// is added to `problems`, and records with problems are left alone, as is
+/// any padding after the last record.
+#[allow(clippy::too_many_arguments)]
+fn patch_chunk(
+    chunks: &mut [Chunk],
+    tag: [u8; 4],
+    record_size: usize,
+    records: &[Value],
+    patch: &Value,
+    path: &str,
+    index_of: impl Fn(&str) -> anyhow::Result<usize>,
+    encode: impl Fn(&Fields, &mut RecordWriter) -> anyhow::Result<()>,
+    problems: &mut Vec<anyhow::Error>,
+) {
+    let Value::Object(patch) = patch else {
+        problems.push(anyhow!(
+            "{path}: expected an object keyed by index or name"
+        ));
+        return;
+    };
+    let Some(chunk) = chunks.iter_mut().find(|chunk| chunk.kind == tag) else {
+        problems.push(PhysicsEaterError::ChunkNotFound { kind: tag }.into());
+        return;
+    };
+    for (key, patch) in patch {
+        let index = match index_of(key) {
+            Ok(x) => x,
+            Err(x) => {
+                problems.push(x.context(format!("{path}/{key}")));
+                continue;
+            }
+        };
+        let path = format!("{path}/{index}");
+        let Some(base) = records.get(index) else {
+            problems.push(anyhow!(
+                "{path}: there are only {} records in the chunk",
+                records.len()
+            ));
+            continue;
+        };
+        let range = index * record_size..(index + 1) * record_size;
+        match patch_record(
+            &chunk.data[range.clone()],
+            base,
+            patch,
+            &path,
+            &encode,
+        ) {
+            Ok(patched) => chunk.data[range].copy_from_slice(&patched),
+            Err(x) => problems.extend(x),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+/// Applies a patch to a physics WAD, and writes out the result. The patch is
+/// in the same form as the JSON `convert_physics` outputs, but only has the
+/// fields to change, e.g. `{"monster_definitions": {"7": {"vitality": 99}}}`.
+/// Definitions are keyed by index or by name, and physics models by
+/// "walking", "running" or index. Every byte the patch doesn't change is
+/// left exactly as it was.
+///
+/// Every problem with the patch is reported to `diagnostics`, as a
+/// `BadPatch` error, and if there were any, nothing is written. With
+/// `check`, the patch is only checked, and nothing is written either way;
+/// otherwise, `output_path` is required.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_patch(
+    physics_path: PathBuf,
+    patch: &Value,
+    output_path: Option<PathBuf>,
+    check: bool,
+    namedbs: &NameDbs,
+    game: Game,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let Value::Object(patch) = patch else { return Err(anyhow!("the patch must be a JSON object")) };
+    if is_m1_physics(open_input(&physics_path, diagnostics)?)? {
+        return Err(anyhow!("only M2 WADs can be patched"));
+    }
+    let mut wad = Wad::open(&physics_path, lenient, diagnostics)?;
+    let chunks = wad.files.first_mut().ok_or(PhysicsEaterError::NoSubfiles)?;
+    // Read with bare indices, so that encoding the unpatched records never
+    // depends on looking a name back up.
+    let raw = namedbs.with_style(NameStyle {
+        raw_ids: true,
+        ..Default::default()
+    });
+    // Go through JSON text, as the output of the convert commands does, so
+    // that a value copied from that output (or from MML) compares equal to
+    // the base instead of being an `f32` widened to `f64`.
+    let base = Physics::read(chunks, &raw, &[], game, None, diagnostics)?;
+    let base: Value = serde_json::from_str(&serde_json::to_string(&base)?)?;
+    let by_name = |db: &NameDb, key: &str| match key.parse() {
+        Ok(index) => Ok(index),
+        Err(_) => db.resolve(key),
     };
-    serde_json::to_writer_pretty(std::io::stdout(), &physics)?;
+    let mut problems = vec![];
+    for (key, patch) in patch {
+        let records = match base.get(key) {
+            Some(Value::Array(x)) => x.clone(),
+            Some(Value::Object(x)) => {
+                let mut ret: Vec<Value> = [&x["walking"], &x["running"]]
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                if let Some(Value::Array(extra)) = x.get("extra") {
+                    ret.extend(extra.iter().cloned());
+                }
+                ret
+            }
+            _ => Vec::new(),
+        };
+        match key.as_str() {
+            "monster_definitions" => patch_chunk(
+                chunks,
+                MONSTER_PHYSICS_TAG,
+                SIZE_OF_MONSTER_DEFINITION,
+                &records,
+                patch,
+                key,
+                |x| by_name(&namedbs.monster_names, x),
+                |x, out| MonsterDefinition::write(x, namedbs, out),
+                &mut problems,
+            ),
+            "effect_definitions" => patch_chunk(
+                chunks,
+                EFFECT_PHYSICS_TAG,
+                SIZE_OF_EFFECT_DEFINITION,
+                &records,
+                patch,
+                key,
+                |x| by_name(&namedbs.effect_names, x),
+                |x, out| EffectDefinition::write(x, namedbs, out),
+                &mut problems,
+            ),
+            "projectile_definitions" => patch_chunk(
+                chunks,
+                PROJECTILE_PHYSICS_TAG,
+                SIZE_OF_PROJECTILE_DEFINITION,
+                &records,
+                patch,
+                key,
+                |x| by_name(&namedbs.projectile_names, x),
+                |x, out| ProjectileDefinition::write(x, namedbs, out),
+                &mut problems,
+            ),
+            "weapon_definitions" => patch_chunk(
+                chunks,
+                WEAPON_PHYSICS_TAG,
+                SIZE_OF_WEAPON_DEFINITION,
+                &records,
+                patch,
+                key,
+                |x| by_name(&namedbs.weapon_names, x),
+                |x, out| WeaponDefinition::write(x, namedbs, out),
+                &mut problems,
+            ),
+            "physics" => patch_chunk(
+                chunks,
+                PHYSICS_PHYSICS_TAG,
+                SIZE_OF_PHYSICS_DEFINITION,
+                &records,
+                patch,
+                key,
+                |x| match x {
+                    "walking" => Ok(0),
+                    "running" => Ok(1),
+                    x => x.parse().map_err(|_| {
+                        anyhow!(
+                            "expected \"walking\", \"running\" or an index"
+                        )
+                    }),
+                },
+                |x, out| PhysicsDefinition::write(x, namedbs, out),
+                &mut problems,
+            ),
+            _ => problems
+                .push(anyhow!("{key:?} isn't a table that can be patched")),
+        }
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            diagnostics
+                .error(WarningKind::BadPatch, format_args!("{problem:#}"));
+        }
+        return Err(anyhow!(
+            "the patch has {} problem(s), so nothing was written",
+            problems.len()
+        ));
+    }
+    if check {
+        return Ok(());
+    }
+    let output_path =
+        output_path.ok_or_else(|| anyhow!("no output path was given"))?;
+    let mut out = vec![];
+    wad.write_wad(&mut out)?;
+    std::fs::write(&output_path, out)
+        .with_context(|| format!("unable to write {output_path:?}"))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projectile_record_is_fully_read() {
+        let record = numbered_record(48);
+        let mut input = CountingReader::new(&record[..]);
+        let projectile = ProjectileDefinition::read(
+            &mut input,
+            &NameDbs::default(),
+            Game::Infinity,
+            0,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(input.bytes_read(), 48);
+        // the last two words of the record
+        assert_eq!(projectile.flyby_sound, Some(Name::Index(23)));
+        assert_eq!(projectile.rebound_sound, Some(Name::Index(24)));
+    }
+
+    fn monster_with_vitality(vitality: u16) -> MonsterDefinition {
+        let mut record = numbered_record(156);
+        // right after the collection and CLUT
+        record[2..4].copy_from_slice(&vitality.to_be_bytes());
+        MonsterDefinition::read(
+            &record[..],
+            &NameDbs::default(),
+            Game::Infinity,
+            0,
+            &Diagnostics::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn vitality_is_signed_not_optional() {
+        assert_eq!(monster_with_vitality(1500).vitality, 1500);
+        // these used to read as "none"
+        assert_eq!(monster_with_vitality(0xFFFF).vitality, -1);
+        assert_eq!(monster_with_vitality(0xFF38).vitality, -200);
+        assert_eq!(monster_with_vitality(0x8000).vitality, i16::MIN);
+    }
+
+    /// A record that's shorter than its reader expects fails with where the
+    /// reader ran out, and in which record and field.
+    #[test]
+    fn read_error_names_record_and_field() {
+        let input = numbered_record(200);
+        let error = read_records(
+            &input,
+            PHYSICS_PHYSICS_TAG,
+            100,
+            "physics model",
+            None,
+            &Diagnostics::default(),
+            |x, _, _| PhysicsDefinition::read(x, &NameDbs::default()),
+        )
+        .err()
+        .expect("a short record was read");
+        assert_eq!(
+            format!("{error:#}").split(": ").take(3).collect::<Vec<_>>(),
+            [
+                "\"PXpx\" chunk",
+                "at offset 0x64 (byte 100 of the record) while reading \
+                 physics model #0",
+                "field \"half_camera_separation\"",
+            ]
+        );
+    }
+
+    /// "No shape" packs to a value that reads back as no shape, and not as
+    /// collection 0 CLUT 0. Patching a record keeps whichever NONE marker it
+    /// had: 0x8000 stays 0x8000, even though it'd be packed as 0xFFFF.
+    #[cfg(feature = "json")]
+    #[test]
+    fn none_shape_round_trip() {
+        let namedbs = NameDbs::default();
+        let packed = Shape::pack_optional(None, None).unwrap();
+        let shape = Shape::read(&packed.to_be_bytes()[..], &namedbs).unwrap();
+        assert!(shape.is_none());
+        assert_eq!(Shape::pack_optional(Some(0), Some(0)).unwrap(), 0);
+        let mut original = numbered_record(14);
+        original[..2].copy_from_slice(&0x8000u16.to_be_bytes());
+        let effect = EffectDefinition::read(
+            &original[..],
+            &namedbs,
+            0,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert!(effect.shape.is_none());
+        let base = serde_json::to_value(&effect).unwrap();
+        let patch = serde_json::json!({"delay": 100});
+        let patched =
+            patch_record(&original, &base, &patch, "effect", |x, out| {
+                EffectDefinition::write(x, &namedbs, out)
+            })
+            .unwrap();
+        assert_eq!(patched[..2], [0x80, 0x00]);
+        assert_eq!(patched[10..12], 100u16.to_be_bytes());
+        assert_eq!(patched[2..10], original[2..10]);
+    }
+
+    /// A chunk padded out past its last record is patched record by record,
+    /// and the padding is left where it was.
+    #[cfg(feature = "json")]
+    #[test]
+    fn patch_padded_chunk() {
+        let namedbs = NameDbs::default();
+        let diagnostics = Diagnostics::default();
+        let mut data = numbered_record(2 * SIZE_OF_EFFECT_DEFINITION);
+        data[..2].fill(0); // so that the shape isn't NONE
+        data[14..16].fill(0);
+        data.extend([0; 4]);
+        let records: Vec<Value> = EffectDefinition::read_definitions(
+            &data,
+            &namedbs,
+            None,
+            &diagnostics,
+        )
+        .unwrap()
+        .iter()
+        .map(|x| serde_json::to_value(x).unwrap())
+        .collect();
+        assert_eq!(records.len(), 2);
+        let mut chunks = [Chunk::new(EFFECT_PHYSICS_TAG, data.clone())];
+        let mut problems = vec![];
+        patch_chunk(
+            &mut chunks,
+            EFFECT_PHYSICS_TAG,
+            SIZE_OF_EFFECT_DEFINITION,
+            &records,
+            &serde_json::json!({"1": {"delay": 100}}),
+            "effect_definitions",
+            |x| Ok(x.parse()?),
+            |x, out| EffectDefinition::write(x, &namedbs, out),
+            &mut problems,
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+        let patched = &chunks[0].data;
+        assert_eq!(patched.len(), data.len());
+        // the delay is the sixth word of the second record
+        assert_eq!(patched[24..26], 100u16.to_be_bytes());
+        assert_eq!(patched[..24], data[..24]);
+        assert_eq!(patched[26..], data[26..]);
+    }
+
+    /// A flag bit nobody knows the meaning of is warned about, kept in
+    /// `_reserved_bits`, and encoded back into the same place.
+    #[cfg(feature = "json")]
+    #[test]
+    fn reserved_flag_bits_round_trip() {
+        let diagnostics = Diagnostics::default();
+        let bits: u16 = 0x8000 | 0x0011;
+        let flags = EffectFlags::from_bits(bits, &diagnostics);
+        assert!(flags.end_when_animation_loops && flags.media_effect);
+        assert!(!flags.sound_only);
+        assert_eq!(flags._reserved_bits, 0x8000);
+        let [warning] = &diagnostics.recorded()[..] else {
+            panic!("expected one warning")
+        };
+        assert_eq!(warning.code, WarningKind::UnknownFlags);
+        assert!(warning.message.contains("bit 15"), "{}", warning.message);
+        let value = serde_json::json!({ "flags": flags });
+        assert_eq!(value["flags"]["_reserved_bits"], 0x8000);
+        let fields = Fields::new(&value, "effect").unwrap();
+        let encoded = fields.flags16("flags", EffectFlags::NAMES).unwrap();
+        assert_eq!(encoded, bits);
+        // but a bit past the end of the field has nowhere to go
+        let value = serde_json::json!({
+            "flags": {"sound_only": true, "_reserved_bits": 0x10000},
+        });
+        let fields = Fields::new(&value, "effect").unwrap();
+        let error = fields.flags16("flags", EffectFlags::NAMES).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(PhysicsEaterError::UnknownFlagBits {
+                bits: 0x10004,
+                width: 16,
+                ..
+            })
+        ));
+        assert_eq!(
+            error.to_string(),
+            "effect/flags: 0x10004 has bits set past the end of a 16-bit \
+             flags field"
+        );
+    }
+
+    /// Infinity weapon #1, the magnum: the magnum pistol item, no powerup, and
+    /// the dual wield class (the rest of the record is numbered). With the
+    /// Infinity namedb, `powerup_type` reads as an item and `powerup_ticks` as
+    /// a tick count, and both read NONE as none.
+    #[test]
+    fn weapon_powerup_fields() {
+        let namedbs = NameDbs::new(
+            Some(std::path::Path::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/infinity_names"
+            ))),
+            Default::default(),
+        )
+        .unwrap();
+        let read = |powerup_type: u16, powerup_ticks: u16| {
+            let mut record = numbered_record(134);
+            record[..6].copy_from_slice(&[0, 1, 0xFF, 0xFF, 0, 3]);
+            record[6..8].fill(0); // flags
+            record[2..4].copy_from_slice(&powerup_type.to_be_bytes());
+            record[60..62].copy_from_slice(&powerup_ticks.to_be_bytes());
+            WeaponDefinition::read(
+                &record[..],
+                &namedbs,
+                Game::Infinity,
+                1,
+                &Diagnostics::default(),
+            )
+            .unwrap()
+        };
+        let named = |x: &str| Some(Name::Named(x.to_string()));
+        let magnum = read(0xFFFF, 0xFFFF);
+        assert_eq!(magnum.name, Name::Named("pistol".to_string()));
+        assert_eq!(magnum.item_type, named("magnum pistol"));
+        assert_eq!(magnum.powerup_type, None);
+        assert_eq!(magnum.weapon_class, named("dual wield"));
+        assert!(magnum.powerup_ticks.is_none());
+        // the word before powerup_ticks, and the last word of the record
+        assert_eq!(magnum.finish_loading_ticks.unwrap().ticks, 30);
+        assert_eq!(magnum.triggers[1].burst_count, Some(67));
+        let powered_up = read(5, 900);
+        assert_eq!(powered_up.powerup_type, named("assault rifle"));
+        assert_eq!(powered_up.powerup_ticks.unwrap().ticks, 900);
+    }
+}
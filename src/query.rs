@@ -0,0 +1,59 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Looking up a single field of a physics file by JSON Pointer, for quick
+//! scripting without piping the whole conversion through `jq`.
+
+use super::*;
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+
+/// Parses a physics file, M1 or M2, and returns the value at `pointer` (as
+/// in RFC 6901, e.g. `/monster_definitions/7/speed`) in the same serialized
+/// form that `convert-*-physics` outputs. Definitions are addressed by
+/// index, not by name.
+pub fn query_physics(
+    path: &Path,
+    pointer: &str,
+    namedbs: &NameDbs,
+    game: Game,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Value> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(anyhow!(
+            "{pointer:?} isn't a JSON pointer; it should start with \"/\""
+        ));
+    }
+    let input = read_input(path, diagnostics)?;
+    let physics = parse_physics(
+        &input,
+        namedbs,
+        game,
+        lenient,
+        &diagnostics.at(path.display()),
+    )
+    .with_context(|| format!("unable to parse {path:?}"))?;
+    let mut physics = serde_json::to_value(physics)?;
+    physics
+        .pointer_mut(pointer)
+        .map(Value::take)
+        .ok_or_else(|| anyhow!("{pointer:?} doesn't resolve in {path:?}"))
+}
@@ -0,0 +1,140 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Checking the fields that point from one definition to another (a
+//! monster's impact effect, a trigger's projectile...) against the tables
+//! they point into. A truncated table, or physics mixed and matched from two
+//! scenarios, parses fine but leaves references to definitions that don't
+//! exist.
+
+use super::*;
+
+use serde_json::Value;
+
+/// The fields that refer to other definitions: the key of the table they're
+/// in, what to call one of its definitions in a warning, JSON pointers to
+/// the fields, and the same two things for the table they refer into. The
+/// names in references come from that table's namedb, `{what}_names`.
+const REFERENCE_FIELDS: &[(&str, &str, &[&str], &str, &str)] = &[
+    (
+        "monster_definitions",
+        "monster",
+        &["/impact_effect", "/melee_impact_effect", "/contrail_effect"],
+        "effect_definitions",
+        "effect",
+    ),
+    (
+        "monster_definitions",
+        "monster",
+        &[
+            "/melee_attack/projectile_type",
+            "/ranged_attack/projectile_type",
+        ],
+        "projectile_definitions",
+        "projectile",
+    ),
+    (
+        "projectile_definitions",
+        "projectile",
+        &[
+            "/detonation_effect",
+            "/media_detonation_effect",
+            "/contrail_effect",
+        ],
+        "effect_definitions",
+        "effect",
+    ),
+    (
+        "projectile_definitions",
+        "projectile",
+        &["/media_projectile_promotion"],
+        "projectile_definitions",
+        "projectile",
+    ),
+    (
+        "weapon_definitions",
+        "weapon",
+        &[
+            "/triggers/0/projectile_type",
+            "/triggers/1/projectile_type",
+            "/triggers/primary/projectile_type",
+            "/triggers/secondary/projectile_type",
+        ],
+        "projectile_definitions",
+        "projectile",
+    ),
+];
+
+/// Works out which definition a reference refers to, from its serialized
+/// form: an index, a name from the namedb, or an object with an index in it.
+/// None if it refers to nothing.
+fn reference_index(reference: &Value, names: Option<&NameDb>) -> Option<u64> {
+    match reference {
+        Value::Number(x) => x.as_u64(),
+        Value::String(x) => names?.resolve(x).ok().map(|x| x as u64),
+        Value::Object(x) => x.get("index").and_then(Value::as_u64),
+        _ => None,
+    }
+}
+
+/// Warns about every reference, in already-serialized physics, to a
+/// definition past the end of the table it refers into. References into a
+/// table that isn't there at all (because of `--only`, or a missing chunk)
+/// aren't checked. Returns how many warnings there were.
+pub fn check_references(
+    physics: &Value,
+    namedbs: &NameDbs,
+    diagnostics: &Diagnostics,
+) -> usize {
+    let mut warnings = 0;
+    for (key, what, pointers, target_key, target_what) in REFERENCE_FIELDS {
+        let Some(Value::Array(records)) = physics.get(key) else { continue };
+        let Some(Value::Array(targets)) = physics.get(target_key) else { continue };
+        let names = namedbs.table(&format!("{target_what}_names"));
+        for (index, record) in records.iter().enumerate() {
+            let label = match record.get("name") {
+                Some(Value::String(name)) => {
+                    format!("{what} #{index} ({name})")
+                }
+                _ => format!("{what} #{index}"),
+            };
+            let diagnostics = diagnostics.at(label);
+            for pointer in pointers.iter() {
+                let Some(reference) = record
+                    .pointer(pointer)
+                    .and_then(|x| reference_index(x, names))
+                else {
+                    continue;
+                };
+                if reference < targets.len() as u64 {
+                    continue;
+                }
+                diagnostics.warn(
+                    WarningKind::DanglingReference,
+                    format_args!(
+                        "{} is {reference}, but there are only {} {target_what} \
+                         definitions",
+                        &pointer[1..],
+                        targets.len()
+                    ),
+                );
+                warnings += 1;
+            }
+        }
+    }
+    warnings
+}
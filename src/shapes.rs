@@ -0,0 +1,314 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Just enough of a Marathon 2/Infinity shapes file to know what's in each
+//! collection: how many CLUTs, sequences and so on. That's enough to check
+//! the sequence indices in physics against it.
+
+use super::*;
+
+#[cfg(feature = "json")]
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use serde::Serialize;
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+const MAXIMUM_COLLECTIONS: usize = 32;
+const COLLECTION_HEADER_SIZE: usize = 32;
+/// How much of a collection definition we look at.
+const COLLECTION_DEFINITION_SIZE: usize = 28;
+
+#[cfg(feature = "json")]
+/// The tables with sequence fields: the key in the serialized physics, what
+/// to call one definition in a warning, and JSON pointers to the sequence
+/// fields. Every sequence is in the definition's own collection.
+const SEQUENCE_FIELDS: &[(&str, &str, &[&str])] = &[
+    (
+        "monster_definitions",
+        "monster",
+        &[
+            "/stationary_sequence",
+            "/moving_sequence",
+            "/hit_sequence",
+            "/soft_dying_sequence",
+            "/hard_dying_sequence",
+            "/soft_dead_sequence",
+            "/hard_dead_sequence",
+            "/teleport_in_sequence",
+            "/teleport_out_sequence",
+            "/melee_attack/attack_sequence",
+            "/ranged_attack/attack_sequence",
+        ],
+    ),
+    ("effect_definitions", "effect", &["/sequence"]),
+    ("projectile_definitions", "projectile", &["/sequence"]),
+    (
+        "weapon_definitions",
+        "weapon",
+        &[
+            "/idle_sequence",
+            "/firing_sequence",
+            "/reloading_sequence",
+            "/charging_sequence",
+            "/charged_sequence",
+        ],
+    ),
+];
+
+/// What kind of graphics a collection holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "snake_case"))]
+pub enum CollectionType {
+    Unused,
+    Wall,
+    Object,
+    Interface,
+    Scenery,
+    Unknown(i16),
+}
+
+impl CollectionType {
+    fn from_raw(raw: i16) -> CollectionType {
+        match raw {
+            0 => CollectionType::Unused,
+            1 => CollectionType::Wall,
+            2 => CollectionType::Object,
+            3 => CollectionType::Interface,
+            4 => CollectionType::Scenery,
+            x => CollectionType::Unknown(x),
+        }
+    }
+}
+
+/// The counts from the start of one collection's definition. Sequences are
+/// what the engine calls "high-level shapes", and frames "low-level shapes".
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct Collection {
+    pub index: usize,
+    pub version: i16,
+    #[cfg_attr(feature = "json", serde(rename = "type"))]
+    pub kind: CollectionType,
+    pub color_count: i16,
+    pub clut_count: i16,
+    pub sequence_count: i16,
+    pub frame_count: i16,
+    pub bitmap_count: i16,
+    pub has_8_bit: bool,
+    pub has_16_bit: bool,
+}
+
+/// The collections of a Marathon 2/Infinity shapes file. Despite being
+/// stored alongside WADs, a shapes file is not one: it starts with a fixed
+/// table of 32 collection headers, each giving where the 8-bit and 16-bit
+/// versions of that collection are in the file.
+pub struct Shapes {
+    /// Indexed by collection number. None for a collection that isn't in
+    /// the file.
+    pub collections: [Option<Collection>; MAXIMUM_COLLECTIONS],
+}
+
+fn be16(data: &[u8], offset: usize) -> i16 {
+    i16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn be32(data: &[u8], offset: usize) -> i32 {
+    i32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl Shapes {
+    /// Reads the collection headers of a shapes file, and the counts from
+    /// each collection that's present. Where a collection has both 8-bit
+    /// and 16-bit versions, the counts come from the 8-bit one; apart from
+    /// the colors, they're the same.
+    pub fn read(data: &[u8]) -> anyhow::Result<Shapes> {
+        let mut collections = [const { None }; MAXIMUM_COLLECTIONS];
+        for (index, collection) in collections.iter_mut().enumerate() {
+            let header = data
+                .get(index * COLLECTION_HEADER_SIZE..)
+                .and_then(|x| x.get(..COLLECTION_HEADER_SIZE))
+                .ok_or(PhysicsEaterError::Truncated {
+                    expected: MAXIMUM_COLLECTIONS * COLLECTION_HEADER_SIZE,
+                    available: data.len(),
+                })?;
+            // 8-bit offset and length, then 16-bit offset and length
+            let has_8_bit = be32(header, 4) >= 0 && be32(header, 8) > 0;
+            let has_16_bit = be32(header, 12) >= 0 && be32(header, 16) > 0;
+            let offset = match (has_8_bit, has_16_bit) {
+                (true, _) => be32(header, 4),
+                (false, true) => be32(header, 12),
+                (false, false) => continue,
+            };
+            let definition = data
+                .get(offset as usize..)
+                .and_then(|x| x.get(..COLLECTION_DEFINITION_SIZE))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "collection {index} is at {offset:08X}, past the end \
+                         of the shapes file"
+                    )
+                })?;
+            *collection = Some(Collection {
+                index,
+                version: be16(definition, 0),
+                kind: CollectionType::from_raw(be16(definition, 2)),
+                color_count: be16(definition, 6),
+                clut_count: be16(definition, 8),
+                sequence_count: be16(definition, 14),
+                frame_count: be16(definition, 20),
+                bitmap_count: be16(definition, 26),
+                has_8_bit,
+                has_16_bit,
+            });
+        }
+        Ok(Shapes { collections })
+    }
+    pub fn open(
+        path: &Path,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Shapes> {
+        Shapes::read(&read_input(path, diagnostics)?)
+            .with_context(|| format!("unable to read shapes from {path:?}"))
+    }
+    /// How many sequences the given collection has, or None if it isn't in
+    /// the file.
+    pub fn sequence_count(&self, collection: usize) -> Option<u16> {
+        self.collections
+            .get(collection)?
+            .as_ref()
+            .map(|x| x.sequence_count.max(0) as u16)
+    }
+}
+
+#[cfg(feature = "json")]
+/// Prints the collections in a shapes file: a line for each collection, or
+/// with `json`, a JSON array of the ones that are present.
+pub fn show_shapes(
+    path: PathBuf,
+    json: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let shapes = Shapes::open(&path, diagnostics)?;
+    let mut out = std::io::stdout().lock();
+    if json {
+        let present: Vec<&Collection> =
+            shapes.collections.iter().flatten().collect();
+        serde_json::to_writer_pretty(&mut out, &present)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    for (index, collection) in shapes.collections.iter().enumerate() {
+        let Some(x) = collection else {
+            writeln!(out, "collection {index:2}: not present")?;
+            continue;
+        };
+        let depths = match (x.has_8_bit, x.has_16_bit) {
+            (true, true) => "8-bit and 16-bit",
+            (true, false) => "8-bit",
+            _ => "16-bit",
+        };
+        writeln!(
+            out,
+            "collection {index:2}: {}, {} CLUTs of {} colors, {} \
+             sequences, {} frames, {} bitmaps ({depths})",
+            format!("{:?}", x.kind).to_lowercase(),
+            x.clut_count,
+            x.color_count,
+            x.sequence_count,
+            x.frame_count,
+            x.bitmap_count,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+/// Works out which collection a definition uses, from its serialized form:
+/// an index, a name from the namedb, or an object with an index in it, under
+/// either "collection" or "shape" (with `--group-shapes`).
+fn collection_index(record: &Value, namedbs: &NameDbs) -> Option<usize> {
+    let collection = record
+        .get("collection")
+        .or_else(|| record.pointer("/shape/collection"))?;
+    match collection {
+        Value::Number(x) => x.as_u64().map(|x| x as usize),
+        Value::String(x) => namedbs.collection_names.resolve(x).ok(),
+        Value::Object(x) => {
+            x.get("index").and_then(Value::as_u64).map(|x| x as usize)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "json")]
+/// Warns about every sequence index, in already-serialized physics, that
+/// isn't a sequence of the collection it's in according to the given shapes.
+/// Returns how many warnings there were.
+pub fn check_sequences(
+    physics: &Value,
+    shapes: &Shapes,
+    namedbs: &NameDbs,
+    diagnostics: &Diagnostics,
+) -> usize {
+    let mut warnings = 0;
+    for (key, what, pointers) in SEQUENCE_FIELDS {
+        let Some(Value::Array(records)) = physics.get(key) else { continue };
+        for (index, record) in records.iter().enumerate() {
+            let Some(collection) = collection_index(record, namedbs) else { continue };
+            let label = match record.get("name") {
+                Some(Value::String(name)) => {
+                    format!("{what} #{index} ({name})")
+                }
+                _ => format!("{what} #{index}"),
+            };
+            let diagnostics = diagnostics.at(label);
+            for pointer in pointers.iter() {
+                let Some(sequence) =
+                    record.pointer(pointer).and_then(Value::as_u64)
+                else {
+                    continue;
+                };
+                let field = &pointer[1..];
+                match shapes.sequence_count(collection) {
+                    None => diagnostics.warn(
+                        WarningKind::BadSequence,
+                        format_args!(
+                            "{field} is {sequence}, but collection \
+                             {collection} isn't in the shapes file"
+                        ),
+                    ),
+                    Some(count) if sequence >= count as u64 => diagnostics
+                        .warn(
+                            WarningKind::BadSequence,
+                            format_args!(
+                                "{field} is {sequence}, but collection \
+                                 {collection} only has {count} sequences"
+                            ),
+                        ),
+                    Some(_) => continue,
+                }
+                warnings += 1;
+            }
+        }
+    }
+    warnings
+}
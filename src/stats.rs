@@ -0,0 +1,190 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Statistics about a physics file, for cataloging: how many of each kind of
+//! definition there are, what kinds of attack the monsters have, and so on.
+
+use super::*;
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How many definitions use each collection. Keyed by collection name, or
+/// index if it has no name, or "none" for definitions with no shape.
+pub type CollectionCounts = BTreeMap<String, usize>;
+
+#[derive(Serialize, Default)]
+pub struct MonsterStats {
+    pub count: usize,
+    pub with_melee_attack: usize,
+    pub with_ranged_attack: usize,
+    pub with_both_attacks: usize,
+    pub with_no_attack: usize,
+    pub collections: CollectionCounts,
+}
+
+#[derive(Serialize, Default)]
+pub struct EffectStats {
+    pub count: usize,
+    pub collections: CollectionCounts,
+}
+
+#[derive(Serialize, Default)]
+pub struct ProjectileStats {
+    pub count: usize,
+    pub guided: usize,
+    pub affected_by_gravity: usize,
+    pub collections: CollectionCounts,
+}
+
+#[derive(Serialize, Default)]
+pub struct WeaponStats {
+    pub count: usize,
+    pub automatic: usize,
+}
+
+/// Statistics about one physics file. A table that isn't present in the file
+/// is left out.
+#[derive(Serialize, Default)]
+pub struct PhysicsStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monsters: Option<MonsterStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effects: Option<EffectStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projectiles: Option<ProjectileStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weapons: Option<WeaponStats>,
+    pub has_physics_models: bool,
+}
+
+fn records<'a>(physics: &'a Value, key: &str) -> Option<&'a Vec<Value>> {
+    physics.get(key).and_then(Value::as_array)
+}
+
+fn is_set(record: &Value, pointer: &str) -> bool {
+    record
+        .pointer(pointer)
+        .is_some_and(|x| !x.is_null() && x != false)
+}
+
+fn count_collections(records: &[Value]) -> CollectionCounts {
+    let mut ret = CollectionCounts::new();
+    for record in records {
+        let key = match record.get("collection") {
+            None | Some(Value::Null) => "none".to_string(),
+            Some(Value::String(name)) => name.clone(),
+            Some(Value::Object(x)) => match x.get("index") {
+                Some(index) => index.to_string(),
+                None => Value::Object(x.clone()).to_string(),
+            },
+            Some(other) => other.to_string(),
+        };
+        *ret.entry(key).or_default() += 1;
+    }
+    ret
+}
+
+impl PhysicsStats {
+    pub fn gather(physics: &AnyPhysics) -> anyhow::Result<PhysicsStats> {
+        let physics = serde_json::to_value(physics)?;
+        let monsters = records(&physics, "monster_definitions").map(|x| {
+            let mut ret = MonsterStats {
+                count: x.len(),
+                collections: count_collections(x),
+                ..Default::default()
+            };
+            for monster in x {
+                let melee = is_set(monster, "/melee_attack");
+                let ranged = is_set(monster, "/ranged_attack");
+                ret.with_melee_attack += melee as usize;
+                ret.with_ranged_attack += ranged as usize;
+                ret.with_both_attacks += (melee && ranged) as usize;
+                ret.with_no_attack += (!melee && !ranged) as usize;
+            }
+            ret
+        });
+        let effects =
+            records(&physics, "effect_definitions").map(|x| EffectStats {
+                count: x.len(),
+                collections: count_collections(x),
+            });
+        let projectiles =
+            records(&physics, "projectile_definitions").map(|x| {
+                ProjectileStats {
+                    count: x.len(),
+                    guided: x
+                        .iter()
+                        .filter(|x| is_set(x, "/flags/guided"))
+                        .count(),
+                    affected_by_gravity: x
+                        .iter()
+                        .filter(|x| is_set(x, "/flags/affected_by_gravity"))
+                        .count(),
+                    collections: count_collections(x),
+                }
+            });
+        let weapons =
+            records(&physics, "weapon_definitions").map(|x| WeaponStats {
+                count: x.len(),
+                automatic: x
+                    .iter()
+                    .filter(|x| is_set(x, "/flags/is_automatic"))
+                    .count(),
+            });
+        Ok(PhysicsStats {
+            monsters,
+            effects,
+            projectiles,
+            weapons,
+            has_physics_models: physics.get("physics").is_some(),
+        })
+    }
+}
+
+/// Parses each physics file and outputs statistics about them all as JSON on
+/// stdout: an object, keyed by path, of `PhysicsStats`.
+pub fn print_stats(
+    paths: &[PathBuf],
+    namedbs: &NameDbs,
+    game: Game,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let mut all_stats = BTreeMap::new();
+    for path in paths {
+        let input = read_input(path, diagnostics)?;
+        let physics = parse_physics(
+            &input,
+            namedbs,
+            game,
+            lenient,
+            &diagnostics.at(path.display()),
+        )
+        .with_context(|| format!("unable to parse {path:?}"))?;
+        all_stats.insert(
+            path.display().to_string(),
+            PhysicsStats::gather(&physics)?,
+        );
+    }
+    serde_json::to_writer_pretty(std::io::stdout().lock(), &all_stats)?;
+    println!();
+    Ok(())
+}
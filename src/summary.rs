@@ -0,0 +1,342 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A human-readable summary of a physics file: one aligned table per kind of
+//! definition, showing only the most interesting fields. Built on top of the
+//! same serialized form that `convert-*-physics` outputs, so it works the
+//! same for M1 and M2 physics. So do the statistics, the CSV output, and the
+//! sequence and reference checks.
+
+use super::*;
+
+use std::io::{IsTerminal, Write};
+
+use anyhow::Context;
+use owo_colors::{OwoColorize, Style};
+use serde_json::Value;
+
+/// A column of a summary table: its heading, and a JSON pointer to the field
+/// it shows.
+type Column = (&'static str, &'static str);
+
+/// The tables to summarize, in order: the key in the serialized physics, the
+/// heading to show, and the columns to show.
+const TABLES: &[(&str, &str, &[Column])] = &[
+    (
+        "monster_definitions",
+        "Monsters",
+        &[
+            ("vitality", "/vitality"),
+            ("speed", "/speed"),
+            ("class", "/class"),
+            ("melee attack", "/melee_attack/projectile_type"),
+            ("ranged attack", "/ranged_attack/projectile_type"),
+        ],
+    ),
+    (
+        "effect_definitions",
+        "Effects",
+        &[
+            ("collection", "/collection"),
+            ("clut", "/clut"),
+            ("sequence", "/sequence"),
+        ],
+    ),
+    (
+        "projectile_definitions",
+        "Projectiles",
+        &[
+            ("damage", "/damage/base"),
+            ("random", "/damage/random"),
+            ("damage type", "/damage/damage_type"),
+            ("speed", "/speed"),
+            ("range", "/maximum_range"),
+            ("detonation", "/detonation_effect"),
+        ],
+    ),
+    (
+        "weapon_definitions",
+        "Weapons",
+        &[
+            ("class", "/weapon_class"),
+            ("item", "/item_type"),
+            ("ammo", "/triggers/0/ammunition_type"),
+            ("rounds", "/triggers/0/rounds_per_magazine"),
+            ("projectile", "/triggers/0/projectile_type"),
+        ],
+    ),
+];
+
+/// The columns to show for each player physics model (walking and running).
+const PHYSICS_COLUMNS: &[Column] = &[
+    ("forward", "/maximum_forward_velocity"),
+    ("backward", "/maximum_backward_velocity"),
+    ("acceleration", "/acceleration"),
+    ("gravity", "/gravitational_acceleration"),
+    ("terminal", "/terminal_velocity"),
+];
+
+/// Whether to color the summary: only when stdout is a terminal, and never
+/// when the `NO_COLOR` environment variable is set to anything non-empty (see
+/// <https://no-color.org/>).
+fn should_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+    !no_color && std::io::stdout().is_terminal()
+}
+
+/// Renders a field value as short, human-friendly text. Returns None for a
+/// missing or null field.
+fn render(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::Null => None,
+        Value::Number(x) => Some(match x.as_f64() {
+            Some(f) if !x.is_i64() && !x.is_u64() => {
+                let text = format!("{f:.3}");
+                text.trim_end_matches('0').trim_end_matches('.').to_string()
+            }
+            _ => x.to_string(),
+        }),
+        Value::String(x) => Some(x.clone()),
+        Value::Array(x) => Some(
+            x.iter()
+                .map(|x| render(Some(x)).unwrap_or_else(|| "none".into()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        // an unnamed thing with `--unnamed object`, or a bit object
+        Value::Object(x) => match x.get("name") {
+            Some(Value::String(name)) => Some(name.clone()),
+            _ => x
+                .get("index")
+                .or_else(|| x.get("bit"))
+                .and_then(|x| render(Some(x)))
+                .or_else(|| Some(Value::Object(x.clone()).to_string())),
+        },
+        Value::Bool(x) => Some(x.to_string()),
+    }
+}
+
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, text: &str, style: Style) -> String {
+        if self.color {
+            text.style(style).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Writes one aligned table. Each row is a label followed by one cell per
+/// column; a None cell is shown as a dimmed "none".
+fn write_table(
+    mut output: impl Write,
+    painter: &Painter,
+    title: &str,
+    headings: &[&str],
+    rows: &[Vec<Option<String>>],
+) -> anyhow::Result<()> {
+    let mut widths: Vec<usize> =
+        headings.iter().map(|x| x.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            let len = cell.as_ref().map_or(4, |x| x.chars().count());
+            *width = (*width).max(len);
+        }
+    }
+    let title = format!("{title} ({})", rows.len());
+    writeln!(output, "{}", painter.paint(&title, Style::new().bold()))?;
+    let mut line = String::from(" ");
+    for (heading, width) in headings.iter().zip(&widths) {
+        let padded = format!(" {heading:<width$}");
+        line += &painter.paint(&padded, Style::new().underline());
+    }
+    writeln!(output, "{}", line.trim_end())?;
+    for row in rows {
+        let mut line = String::from(" ");
+        for (n, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            let (text, style) = match cell {
+                Some(x) if n <= 1 => (x.as_str(), Style::new().cyan()),
+                Some(x) => (x.as_str(), Style::new()),
+                None => ("none", Style::new().dimmed()),
+            };
+            let padded = format!(" {text:<width$}");
+            line += &painter.paint(&padded, style);
+        }
+        writeln!(output, "{}", line.trim_end())?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Writes a summary of already-parsed physics.
+pub fn write_summary(
+    physics: &AnyPhysics,
+    mut output: impl Write,
+    color: bool,
+) -> anyhow::Result<()> {
+    let painter = Painter { color };
+    let physics = serde_json::to_value(physics)?;
+    for (key, title, columns) in TABLES {
+        let Some(Value::Array(records)) = physics.get(key) else { continue };
+        let mut headings = vec!["#", "name"];
+        headings.extend(columns.iter().map(|(heading, _)| *heading));
+        let rows: Vec<_> = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let name = render(record.get("name")).unwrap_or_default();
+                let mut row = vec![Some(index.to_string()), Some(name)];
+                row.extend(
+                    columns
+                        .iter()
+                        .map(|(_, pointer)| render(record.pointer(pointer))),
+                );
+                row
+            })
+            .collect();
+        write_table(&mut output, &painter, title, &headings, &rows)?;
+    }
+    if let Some(models) = physics.get("physics") {
+        let mut headings = vec!["model"];
+        headings.extend(PHYSICS_COLUMNS.iter().map(|(heading, _)| *heading));
+        let rows: Vec<_> = ["walking", "running"]
+            .into_iter()
+            .map(|model| {
+                let mut row = vec![Some(model.to_string())];
+                row.extend(PHYSICS_COLUMNS.iter().map(|(_, pointer)| {
+                    render(models.get(model).and_then(|x| x.pointer(pointer)))
+                }));
+                row
+            })
+            .collect();
+        write_table(&mut output, &painter, "Physics", &headings, &rows)?;
+    }
+    Ok(())
+}
+
+/// Renders one cell of `--tsv` output: like `render`, but "none" for null,
+/// nothing for a field the definition doesn't have, and with any tabs or
+/// line breaks turned into spaces.
+fn tsv_cell(value: Option<&Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(x) => render(Some(x))
+            .unwrap_or_else(|| "none".to_string())
+            .replace(['\t', '\n', '\r'], " "),
+    }
+}
+
+/// Turns a `--columns` column, e.g. "damage.base", into a JSON pointer.
+fn column_pointer(column: &str) -> String {
+    column
+        .split('.')
+        .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Writes the heading line of `--tsv` output.
+pub fn write_tsv_heading(
+    mut output: impl Write,
+    columns: &[String],
+    with_file: bool,
+) -> anyhow::Result<()> {
+    let mut line = vec!["table", "#"];
+    if with_file {
+        line.insert(0, "file");
+    }
+    line.extend(columns.iter().map(String::as_str));
+    writeln!(output, "{}", line.join("\t"))?;
+    Ok(())
+}
+
+/// Writes already-parsed physics as tab-separated values, for grepping: one
+/// line per definition, giving the table it's in, its index, then each of
+/// the given columns. A column is a field name, with dots to reach into
+/// nested fields, e.g. "damage.base" or "triggers.0.projectile_type". If
+/// `file` is given, it goes before everything else on every line.
+pub fn write_tsv(
+    physics: &AnyPhysics,
+    mut output: impl Write,
+    columns: &[String],
+    file: Option<&str>,
+) -> anyhow::Result<()> {
+    let physics = serde_json::to_value(physics)?;
+    let pointers: Vec<String> =
+        columns.iter().map(|x| column_pointer(x)).collect();
+    for (table, key) in TABLE_FILE_NAMES {
+        let Some(Value::Array(records)) = physics.get(key) else { continue };
+        for (index, record) in records.iter().enumerate() {
+            let mut line: Vec<String> =
+                file.map(str::to_string).into_iter().collect();
+            line.push(table.to_string());
+            line.push(index.to_string());
+            line.extend(pointers.iter().map(|x| tsv_cell(record.pointer(x))));
+            writeln!(output, "{}", line.join("\t"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses each physics file and prints a summary of it to stdout, colored if
+/// stdout is a terminal and `NO_COLOR` isn't set. If `tsv_columns` is given,
+/// prints those columns as tab-separated values instead (see `write_tsv`).
+pub fn summarize_physics(
+    paths: &[PathBuf],
+    namedbs: &NameDbs,
+    game: Game,
+    lenient: bool,
+    tsv_columns: Option<&[String]>,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let color = should_color();
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    if let Some(columns) = tsv_columns {
+        write_tsv_heading(&mut stdout, columns, paths.len() > 1)?;
+    }
+    for path in paths {
+        let input = read_input(path, diagnostics)?;
+        let physics = parse_physics(
+            &input,
+            namedbs,
+            game,
+            lenient,
+            &diagnostics.at(path.display()),
+        )
+        .with_context(|| format!("unable to parse {path:?}"))?;
+        if let Some(columns) = tsv_columns {
+            let file = path.display().to_string();
+            let file = (paths.len() > 1).then_some(file.as_str());
+            write_tsv(&physics, &mut stdout, columns, file)?;
+            continue;
+        }
+        if paths.len() > 1 {
+            let heading = format!("== {} ==", path.display());
+            writeln!(
+                stdout,
+                "{}\n",
+                Painter { color }.paint(&heading, Style::new().bold())
+            )?;
+        }
+        write_summary(&physics, &mut stdout, color)?;
+    }
+    Ok(())
+}
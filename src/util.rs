@@ -15,7 +15,35 @@
     with physics-eater. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::io::Read;
+use std::io::{Read, Write};
+
+use crate::{Diagnostics, WarningKind};
+
+/// Wraps a reader, keeping track of how many bytes have been read through it.
+pub struct CountingReader<R> {
+    inner: R,
+    bytes_read: usize,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader {
+            inner,
+            bytes_read: 0,
+        }
+    }
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let ret = self.inner.read(buf)?;
+        self.bytes_read += ret;
+        Ok(ret)
+    }
+}
 
 pub fn read16(mut input: impl Read) -> anyhow::Result<u16> {
     let mut buf = [0; 2];
@@ -29,6 +57,49 @@ pub fn read32(mut input: impl Read) -> anyhow::Result<u32> {
     Ok(u32::from_be_bytes(buf))
 }
 
+pub fn write16(mut output: impl Write, value: u16) -> anyhow::Result<()> {
+    output.write_all(&value.to_be_bytes())?;
+    Ok(())
+}
+
+pub fn write32(mut output: impl Write, value: u32) -> anyhow::Result<()> {
+    output.write_all(&value.to_be_bytes())?;
+    Ok(())
+}
+
+/// Reads `N` bytes, or returns `None` if the input was already at its end. An
+/// input that ends partway through is an error, not a clean end.
+pub fn read_array_opt<const N: usize>(
+    mut input: impl Read,
+) -> anyhow::Result<Option<[u8; N]>> {
+    let mut buf = [0; N];
+    let mut pos = 0;
+    while pos < N {
+        match input.read(&mut buf[pos..]) {
+            Ok(0) if pos == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(crate::PhysicsEaterError::Truncated {
+                    expected: N,
+                    available: pos,
+                }
+                .into())
+            }
+            Ok(n) => pos += n,
+            Err(x) if x.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(x) => return Err(x.into()),
+        }
+    }
+    Ok(Some(buf))
+}
+
+pub fn read16_opt(input: impl Read) -> anyhow::Result<Option<u16>> {
+    Ok(read_array_opt(input)?.map(u16::from_be_bytes))
+}
+
+pub fn read32_opt(input: impl Read) -> anyhow::Result<Option<u32>> {
+    Ok(read_array_opt(input)?.map(u32::from_be_bytes))
+}
+
 pub fn read_fx_16_16(input: impl Read) -> anyhow::Result<f32> {
     Ok(read32(input)? as i32 as f32 / 65536.0)
 }
@@ -49,6 +120,10 @@ pub fn read_angle(input: impl Read) -> anyhow::Result<f32> {
     Ok(read16(input)? as i16 as f32 * 360.0 / 512.0)
 }
 
+/// Reads a 16-bit field that may be NONE. As in the engine, NONE is -1, and
+/// any other negative value is treated as NONE too. Only for fields that
+/// really can be NONE; a mask has a meaning for every bit pattern, and
+/// should be read with `read16`.
 pub fn read_optional_16(input: impl Read) -> anyhow::Result<Option<u16>> {
     let ret = read16(input)?;
     if ret & 0x8000 != 0 {
@@ -58,9 +133,10 @@ pub fn read_optional_16(input: impl Read) -> anyhow::Result<Option<u16>> {
     }
 }
 
+/// Like `read_optional_16`, but 32 bits wide: any negative value is NONE.
 pub fn read_optional_32(input: impl Read) -> anyhow::Result<Option<u32>> {
     let ret = read32(input)?;
-    if ret & 0x8000 != 0 {
+    if ret & 0x8000_0000 != 0 {
         Ok(None)
     } else {
         Ok(Some(ret))
@@ -72,6 +148,64 @@ pub fn read_generic_bitfield32(input: impl Read) -> anyhow::Result<Vec<u32>> {
     Ok((0..32).filter(|x| ret & (1 << x) != 0).collect())
 }
 
+/// The inverse of `read_generic_bitfield32`: sets each of the given bits.
+pub fn write_generic_bitfield32(
+    output: impl Write,
+    bits: &[u32],
+) -> anyhow::Result<()> {
+    let mut value = 0u32;
+    for &bit in bits {
+        if bit >= 32 {
+            return Err(anyhow::anyhow!(
+                "bit {bit} doesn't fit in a 32-bit bitfield"
+            ));
+        }
+        value |= 1 << bit;
+    }
+    write32(output, value)
+}
+
+/// The characters Mac Roman bytes 0x80 through 0xFF stand for. (0xF0 is the
+/// Apple logo, which Apple maps to this private use character.)
+const MAC_ROMAN_HIGH: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é',
+    'è', 'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù',
+    'û', 'ü', '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨',
+    '≠', 'Æ', 'Ø', '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª',
+    'º', 'Ω', 'æ', 'ø', '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…',
+    '\u{A0}', 'À', 'Ã', 'Õ', 'Œ', 'œ', '–', '—', '“', '”', '‘', '’', '÷', '◊',
+    'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ', '‡', '·', '‚', '„', '‰', 'Â', 'Ê',
+    'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô', '\u{F8FF}', 'Ò', 'Ú', 'Û',
+    'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ',
+];
+
+/// Decodes Mac Roman text, which is what classic Mac resources (and the
+/// strings Marathon keeps in them) use. Unlike `String::from_utf8_lossy`,
+/// this never fails, and gets bytes like 0x8E ("é") and 0xD5 ("’") right.
+pub fn mac_roman_to_utf8(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&x| match x {
+            0..=0x7F => x as char,
+            _ => MAC_ROMAN_HIGH[x as usize - 0x80],
+        })
+        .collect()
+}
+
+/// The reverse of `mac_roman_to_utf8`. Returns None if the text has a
+/// character that Mac Roman doesn't have.
+pub fn utf8_to_mac_roman(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|x| match x {
+            '\0'..='\x7F' => Some(x as u8),
+            _ => MAC_ROMAN_HIGH
+                .iter()
+                .position(|&y| y == x)
+                .map(|y| y as u8 + 0x80),
+        })
+        .collect()
+}
+
 macro_rules! extract_flags {
     ($flags:ident, $flagbit:ident, $nextflag:ident, $($restflags:ident),+) => {
         extract_flags!($flags, $flagbit, $nextflag);
@@ -83,15 +217,179 @@ macro_rules! extract_flags {
     };
 }
 
+/// For `skip_serializing_if`.
+pub fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Warns about set bits of a flags field that no named flag covers, so that
+/// modders find out when their file uses a flag we don't understand. `bits`
+/// has already been shifted down by `shift`, which is added back on for the
+/// report.
+pub fn warn_unknown_flags(
+    diagnostics: &Diagnostics,
+    what: &str,
+    bits: u64,
+    shift: u32,
+) {
+    if bits == 0 {
+        return;
+    }
+    let bits: Vec<String> = (0..64)
+        .filter(|x| bits & (1 << x) != 0)
+        .map(|x| (x + shift).to_string())
+        .collect();
+    let plural = if bits.len() == 1 { "" } else { "s" };
+    diagnostics.warn(
+        WarningKind::UnknownFlags,
+        format_args!(
+            "{what} has unknown bit{plural} {} set, which will be passed \
+             through as-is",
+            bits.join(", ")
+        ),
+    );
+}
+
+/// Decodes a flags field into a struct of bools, one per bit, starting from
+/// the least significant. Any set bits beyond the named ones are warned
+/// about (see `warn_unknown_flags`) and kept, in their original positions, in
+/// the struct's `_reserved_bits` field, so that they survive a round trip.
+///
+/// `flags[n..] => Flags { ... }` decodes starting from bit `n` instead. If
+/// extra fields are given after a `;`, they are assumed to decode the bits
+/// above the named ones, and are responsible for checking them; one of them
+/// must be `_reserved_bits`.
+///
+/// The first argument is the `Diagnostics` to warn to.
+///
+/// `decode_flags! { impl Flags: u32 { ... } }`, as an item, instead gives
+/// `Flags` a `NAMES` constant, the names of the flags in bit order (for
+/// encoding), and a `from_bits` function that decodes them as above. Both
+/// come from the one list, so they can't disagree. `[n..]` works the same
+/// there, and `; field: Type` after the list makes `from_bits` take the
+/// value of the extra field and the reserved bits instead of a `Diagnostics`.
 macro_rules! decode_flags {
-    ($input:expr => $Flags:ident { $($flagname:ident),+ $(,)? }) => {
+    (impl $Flags:ident: $T:ty [$shift:literal..] { $($flagname:ident),+ $(,)? }) => {
+        impl $Flags {
+            pub const NAMES: &'static [&'static str] =
+                &[$(stringify!($flagname)),+];
+            fn from_bits(flags: $T, diagnostics: &Diagnostics) -> $Flags {
+                decode_flags!(diagnostics, flags[$shift..] => $Flags { $($flagname),+ })
+            }
+        }
+    };
+    (impl $Flags:ident: $T:ty { $($flagname:ident),+ $(,)? }) => {
+        decode_flags! { impl $Flags: $T [0..] { $($flagname),+ } }
+    };
+    (impl $Flags:ident: $T:ty { $($flagname:ident),+ $(,)? }
+     ; $field:ident: $FieldT:ty) => {
+        impl $Flags {
+            pub const NAMES: &'static [&'static str] =
+                &[$(stringify!($flagname)),+];
+            fn from_bits(
+                flags: $T,
+                $field: $FieldT,
+                _reserved_bits: $T,
+            ) -> $Flags {
+                decode_flags!((), flags => $Flags { $($flagname),+ }
+                    ; $field: $field, _reserved_bits: _reserved_bits)
+            }
+        }
+    };
+    ($diagnostics:expr, $input:ident[$shift:literal..] => $Flags:ident { $($flagname:ident),+ $(,)? }) => {
+        { #[allow(unused)] {
+            let flags = $input >> $shift;
+            let mut flagbit = 1;
+            extract_flags!(flags, flagbit, $($flagname),+);
+            let reserved = flags & !flagbit.wrapping_sub(1);
+            warn_unknown_flags(
+                $diagnostics,
+                stringify!($Flags),
+                reserved.into(),
+                $shift,
+            );
+            $Flags {
+                $($flagname,)+
+                _reserved_bits: reserved << $shift,
+            }
+        }}
+    };
+    ($diagnostics:expr, $input:expr => $Flags:ident { $($flagname:ident),+ $(,)? }) => {
+        { #[allow(unused)] {
+            let flags = $input;
+            decode_flags!($diagnostics, flags[0..] => $Flags { $($flagname),+ })
+        }}
+    };
+    ($diagnostics:expr, $input:expr => $Flags:ident { $($flagname:ident),+ $(,)? }
+     ; $($field:ident: $value:expr),+ $(,)?) => {
         { #[allow(unused)] {
             let flags = $input;
             let mut flagbit = 1;
             extract_flags!(flags, flagbit, $($flagname),+);
             $Flags {
-                $($flagname),+
+                $($flagname,)+
+                $($field: $value),+
             }
         }}
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generic_bitfield32_round_trip() {
+        let all: Vec<u32> = (0..32).collect();
+        for bits in [&[][..], &[0], &[3, 5, 17], &[0, 31], &all] {
+            let mut written = vec![];
+            write_generic_bitfield32(&mut written, bits).unwrap();
+            assert_eq!(written.len(), 4);
+            assert_eq!(read_generic_bitfield32(&written[..]).unwrap(), bits);
+        }
+        assert!(write_generic_bitfield32(vec![], &[32]).is_err());
+    }
+
+    #[test]
+    fn mac_roman() {
+        assert_eq!(mac_roman_to_utf8(b"Bob\xD5s"), "Bob\u{2019}s");
+        assert_eq!(mac_roman_to_utf8(b"Caf\x8E"), "Caf\u{E9}");
+        assert_eq!(utf8_to_mac_roman("Bob\u{2019}s").unwrap(), b"Bob\xD5s");
+        // every byte survives a round trip
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(utf8_to_mac_roman(&mac_roman_to_utf8(&all)).unwrap(), all);
+        assert_eq!(utf8_to_mac_roman("\u{1F600}"), None);
+    }
+
+    /// NONE is -1, but any negative value is taken as NONE; the largest
+    /// positive values, and zero, are real values.
+    #[test]
+    fn optional_none_sentinels() {
+        for none in [0xFFFF, 0x8000, 0x8001, 0xFFFE] {
+            let bytes = u16::to_be_bytes(none);
+            assert_eq!(
+                read_optional_16(&bytes[..]).unwrap(),
+                None,
+                "{none:X}"
+            );
+        }
+        for some in [0, 1, 0x7FFE, 0x7FFF] {
+            let bytes = u16::to_be_bytes(some);
+            assert_eq!(read_optional_16(&bytes[..]).unwrap(), Some(some));
+        }
+        for none in [0xFFFF_FFFF, 0x8000_0000, 0xFFFF_8000] {
+            let bytes = u32::to_be_bytes(none);
+            assert_eq!(
+                read_optional_32(&bytes[..]).unwrap(),
+                None,
+                "{none:X}"
+            );
+        }
+        // only the top bit of all 32 counts
+        for some in [0, 0x8000, 0xFFFF, 0x7FFF_FFFF] {
+            let bytes = u32::to_be_bytes(some);
+            assert_eq!(read_optional_32(&bytes[..]).unwrap(), Some(some));
+        }
+        assert!(read_optional_16(&[0xFF][..]).is_err());
+    }
+}
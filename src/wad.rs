@@ -15,30 +15,199 @@
     with physics-eater. If not, see <https://www.gnu.org/licenses/>.
 */
 
+#[cfg(feature = "json")]
+use std::io::stdout;
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fmt::{Debug, Formatter, Result as FmtResult},
     fs::File,
-    io::{Cursor, Read, Seek, SeekFrom},
-    path::PathBuf,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, Context};
+#[cfg(feature = "json")]
+use base64::prelude::*;
+#[cfg(feature = "json")]
+use serde::Serialize;
 
 use super::*;
 
 #[allow(unused)]
 const PRE_ENTRY_POINT_WADFILE_VERSION: u16 = 0;
 const WADFILE_HAS_DIRECTORY_ENTRY: u16 = 1;
-#[allow(unused)]
 const WADFILE_SUPPORTS_OVERLAYS: u16 = 2;
-#[allow(unused)]
 const WADFILE_HAS_INFINITY_STUFF: u16 = 4;
 const MAXIMUM_WADFILE_NAME_LENGTH: usize = 64;
 const MAXIMUM_DIRECTORY_ENTRIES_PER_FILE: usize = 64;
+const WAD_HEADER_SIZE: usize = 128;
+const ENTRY_HEADER_SIZE: u16 = 16;
+/// The size of an entry header in a WAD from before
+/// `WADFILE_HAS_DIRECTORY_ENTRY`, which has no "offset" field.
+const OLD_ENTRY_HEADER_SIZE: u16 = 12;
+/// The size of a directory entry in a WAD from before
+/// `WADFILE_HAS_DIRECTORY_ENTRY`, which has no index.
+const OLD_DIRECTORY_ENTRY_SIZE: u16 = 8;
+const CHECKSUM_OFFSET: usize = 68;
+const M1_CHUNK_HEADER_SIZE: u64 = 12;
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// An input file, opened by `open_input`.
+pub enum Input {
+    /// A file that's read straight from disk.
+    File(File),
+    /// A file that had to be decompressed or unwrapped, held in memory.
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::File(x) => x.read(buf),
+            Input::Memory(x) => x.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Input::File(x) => x.seek(pos),
+            Input::Memory(x) => x.seek(pos),
+        }
+    }
+}
+
+/// Returns true if data starting with `head` (at least 128 bytes of it, if
+/// the file is that long) needs decompressing or unwrapping before use.
+fn needs_unwrapping(head: &[u8]) -> bool {
+    head.starts_with(&GZIP_MAGIC) || detect_mac_wrapper(head).is_some()
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(input: impl Read, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut ret = Vec::new();
+    flate2::read::GzDecoder::new(input)
+        .read_to_end(&mut ret)
+        .with_context(|| format!("unable to decompress {path:?}"))?;
+    Ok(ret)
+}
+
+/// Without the `gzip` feature, gzipped input is recognized but can't be read.
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_input: impl Read, path: &Path) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow!(
+        "unable to decompress {path:?}: this build doesn't have the gzip \
+         feature"
+    ))
+}
+
+/// Decompresses and/or unwraps the contents of an input file, as many times
+/// as it takes to get to the real data.
+fn unwrap_input(
+    data: Vec<u8>,
+    path: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<u8>> {
+    if data.starts_with(&GZIP_MAGIC) {
+        unwrap_input(gunzip(&data[..], path)?, path, diagnostics)
+    } else {
+        unwrap_mac_file(data, path, diagnostics)
+    }
+}
+
+/// Opens an input file (a WAD, or bare M1 chunks) for reading. If it's
+/// gzip-compressed, it's transparently decompressed, and if it's wrapped in
+/// MacBinary or AppleSingle, or is an AppleDouble sidecar, the data fork is
+/// extracted (see `unwrap_mac_file`). A `zip:ARCHIVE!ENTRY` path reads an
+/// entry out of a zip archive (see `split_zip_path`).
+///
+/// The readers need to seek, and a gzip stream can't seek, so a gzipped file
+/// is decompressed into memory all at once. Physics files are small enough
+/// that this doesn't matter, but a gzipped map or shapes file will take up
+/// its full uncompressed size in memory. The same goes for a zip entry.
+pub fn open_input(
+    path: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Input> {
+    if split_zip_path(path).is_some() {
+        return Ok(Input::Memory(Cursor::new(read_input(path, diagnostics)?)));
+    }
+    let mut file = File::open(path)
+        .with_context(|| format!("unable to open {path:?}"))?;
+    let mut head = Vec::new();
+    (&mut file).take(128).read_to_end(&mut head)?;
+    if needs_unwrapping(&head) {
+        Ok(Input::Memory(Cursor::new(read_input(path, diagnostics)?)))
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Input::File(file))
+    }
+}
+
+/// Reads an entire input file into memory, decompressing and unwrapping it
+/// as `open_input` would.
+pub fn read_input(
+    path: &Path,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<u8>> {
+    let data = match split_zip_path(path) {
+        Some((archive, entry)) => read_zip_entry(archive, entry)?,
+        None => std::fs::read(path)
+            .with_context(|| format!("unable to read {path:?}"))?,
+    };
+    unwrap_input(data, path, diagnostics)
+}
+
+/// Reads exactly `length` bytes. Unlike `read_exact` into a preallocated
+/// buffer, this doesn't trust `length` with an allocation up front, so a
+/// corrupt length field in a short file can't make us allocate gigabytes.
+fn read_exactly(input: impl Read, length: usize) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    input.take(length as u64).read_to_end(&mut data)?;
+    if data.len() != length {
+        return Err(PhysicsEaterError::Truncated {
+            expected: length,
+            available: data.len(),
+        }
+        .into());
+    }
+    Ok(data)
+}
+
+/// Marathon's WAD checksum of some data: the standard CRC-32, as zlib and
+/// PNG use (reflected polynomial 0xEDB88320, starting from 0xFFFFFFFF, and
+/// inverted at the end). This is what Aleph One's `calculate_data_crc`
+/// computes. A WAD's checksum is this over the whole file, with the checksum
+/// field in the header zeroed.
+pub fn wad_checksum(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
 
+#[derive(Clone, PartialEq, Eq)]
 pub struct Chunk {
     pub kind: [u8; 4],
     pub data: Vec<u8>,
+    /// The last field of an M2 entry header. Bungie's headers describe it as
+    /// an "offset for inplace expansion of data", but nothing in the engine
+    /// ever reads it, and every Bungie file has zero here. Kept so that a
+    /// chunk can be written back out exactly as it was read. Always zero for
+    /// M1 chunks, which don't have the field.
+    pub offset: u32,
+    /// Where the chunk's data starts, from the beginning of the file.
+    pub data_offset: u64,
 }
 
 impl Debug for Chunk {
@@ -46,15 +215,39 @@ impl Debug for Chunk {
         f.debug_struct("Chunk")
             .field("kind", &String::from_utf8_lossy(&self.kind))
             .field("bytes.len()", &self.data.len())
+            .field("offset", &self.offset)
             .finish()
     }
 }
 
 impl Chunk {
+    /// A new chunk with the given tag and contents, for writing out.
+    pub fn new(kind: [u8; 4], data: Vec<u8>) -> Chunk {
+        Chunk {
+            kind,
+            data,
+            offset: 0,
+            data_offset: 0,
+        }
+    }
+    /// Reads the chunks of one M2 subfile, which starts at `base` within the
+    /// file, and whose entry headers are `entry_header_size` bytes long (see
+    /// `Wad::entry_header_length`). A nonzero entry header "offset" field is
+    /// an error, unless `lenient` is true, in which case it's only a warning.
     pub fn read_m2_chunks(
         mut input: impl Read + Seek,
+        base: u64,
+        entry_header_size: u16,
+        lenient: bool,
+        diagnostics: &Diagnostics,
     ) -> anyhow::Result<Vec<Chunk>> {
+        if entry_header_size < OLD_ENTRY_HEADER_SIZE {
+            return Err(anyhow!(
+                "WAD entry headers can't be only {entry_header_size} bytes long"
+            ));
+        }
         let mut chunks = vec![];
+        let mut visited = HashMap::new();
         let mut next_offset = 0;
         loop {
             let offset = next_offset;
@@ -63,62 +256,211 @@ impl Chunk {
                     .seek(SeekFrom::Start(offset as u64))
                     .context("unable to seek to a chunk of the WAD")?;
             }
-            let mut kind = [0; 4];
-            let Ok(()) = input.read_exact(&mut kind) else { break };
+            let Some(kind) = read_array_opt(&mut input)
+                .context("truncated chunk header in the WAD")?
+            else {
+                break;
+            };
             next_offset = read32(&mut input)
                 .context("unable to read a chunk of the WAD")?;
             let length = read32(&mut input)
                 .context("unable to read a chunk of the WAD")?;
-            let unknown = read32(&mut input)
+            visited.insert(offset, chunks.len());
+            if next_offset != 0 {
+                // A link to a chunk we've already read would have us going
+                // around in circles forever. A link back into this chunk
+                // (or before it) would end up in one, or in garbage.
+                if let Some(earlier) = visited.get(&next_offset) {
+                    return Err(anyhow!("chunk #{} {:?}, located at {:08X} within the subfile, links back to chunk #{earlier}", chunks.len(), String::from_utf8_lossy(&kind[..]), offset));
+                }
+                let end =
+                    offset as u64 + entry_header_size as u64 + length as u64;
+                if (next_offset as u64) < end {
+                    return Err(anyhow!("chunk #{} {:?}, located at {:08X} within the subfile, links to {:08X}, before its own end at {:08X}", chunks.len(), String::from_utf8_lossy(&kind[..]), offset, next_offset, end));
+                }
+            }
+            let expansion_offset = if entry_header_size >= ENTRY_HEADER_SIZE {
+                read32(&mut input)
+                    .context("unable to read a chunk of the WAD")?
+            } else {
+                0
+            };
+            // anything past the fields we know about is skipped
+            let extra = entry_header_size.saturating_sub(ENTRY_HEADER_SIZE);
+            read_exactly(&mut input, extra as usize)
                 .context("unable to read a chunk of the WAD")?;
-            if unknown != 0 {
-                return Err(anyhow!("chunk #{} {:?}, located at {:08X} within the subfile, has a nonzero value in the unknown-purpose \"offset\" field", chunks.len(), String::from_utf8_lossy(&kind[..]), offset));
+            if expansion_offset != 0 {
+                let message = format!("chunk #{} {:?}, located at {:08X} within the subfile, has a nonzero value in the unknown-purpose \"offset\" field", chunks.len(), String::from_utf8_lossy(&kind[..]), offset);
+                if lenient {
+                    diagnostics.warn(WarningKind::ChunkOffset, message);
+                } else {
+                    return Err(anyhow!(
+                        "{message} (use --lenient to accept it anyway)"
+                    ));
+                }
             }
-            let mut chunk_data = vec![0; length as usize];
-            input
-                .read_exact(&mut chunk_data)
+            let chunk_data = read_exactly(&mut input, length as usize)
                 .context("unable to read a chunk of the WAD")?;
             chunks.push(Chunk {
                 kind,
                 data: chunk_data,
+                offset: expansion_offset,
+                data_offset: base + offset as u64 + entry_header_size as u64,
             })
         }
         Ok(chunks)
     }
-    pub fn read_m1_chunks(mut input: impl Read) -> anyhow::Result<Vec<Chunk>> {
-        let mut chunks = vec![];
-        loop {
-            let mut kind = [0; 4];
-            let Ok(()) = input.read_exact(&mut kind) else { break };
-            let _ = read32(&mut input).context("unable to read a chunk")?;
-            let count =
-                read16(&mut input).context("unable to read a chunk")?;
-            let size = read16(&mut input).context("unable to read a chunk")?;
-            let length = count as usize * size as usize;
-            let mut chunk_data = vec![0; length];
-            input
-                .read_exact(&mut chunk_data)
-                .context("unable to read a chunk of a subfile of the WAD")?;
-            chunks.push(Chunk {
-                kind,
-                data: chunk_data,
-            })
+    /// Writes the chunks of one M2 subfile, linking each to the next, with
+    /// entry headers `entry_header_size` bytes long.
+    pub fn write_m2_chunks(
+        chunks: &[Chunk],
+        entry_header_size: u16,
+        mut output: impl Write,
+    ) -> anyhow::Result<()> {
+        if entry_header_size < OLD_ENTRY_HEADER_SIZE {
+            return Err(anyhow!(
+                "WAD entry headers can't be only {entry_header_size} bytes long"
+            ));
         }
-        Ok(chunks)
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let length = u32::try_from(chunk.data.len())
+                .map_err(|_| anyhow!("chunk #{i} is too big for a WAD"))?;
+            offset += entry_header_size as u32 + length;
+            let next_offset = if i + 1 == chunks.len() { 0 } else { offset };
+            output.write_all(&chunk.kind)?;
+            write32(&mut output, next_offset)?;
+            write32(&mut output, length)?;
+            if entry_header_size >= ENTRY_HEADER_SIZE {
+                write32(&mut output, chunk.offset)?;
+            }
+            let extra = entry_header_size.saturating_sub(ENTRY_HEADER_SIZE);
+            output.write_all(&vec![0; extra as usize])?;
+            output.write_all(&chunk.data)?;
+        }
+        Ok(())
     }
-    pub fn find(chunks: &[Chunk], kind: [u8; 4]) -> anyhow::Result<&[u8]> {
-        for chunk in chunks.iter() {
-            if chunk.kind == kind {
-                return Ok(&chunk.data);
+    /// Reads bare M1 chunks until the end of the input. Bytes left over after
+    /// the last complete chunk are an error, unless `lenient` is true, in
+    /// which case they're only a warning.
+    pub fn read_m1_chunks(
+        input: impl Read,
+        lenient: bool,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Vec<Chunk>> {
+        Chunk::iter_m1_chunks(input, lenient, diagnostics).collect()
+    }
+    /// Reads bare M1 chunks lazily, one at a time, so that each one can be
+    /// processed and dropped before the next is read. Iteration stops after
+    /// the first error.
+    pub fn iter_m1_chunks(
+        input: impl Read,
+        lenient: bool,
+        diagnostics: &Diagnostics,
+    ) -> impl Iterator<Item = anyhow::Result<Chunk>> {
+        let diagnostics = diagnostics.clone();
+        let mut input = CountingReader::new(input);
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
             }
+            let start = input.bytes_read();
+            let ret = match Chunk::read_m1_chunk(&mut input, start as u64) {
+                Ok(x) => x.map(Ok),
+                Err(x) => {
+                    // Find out how much is left, so we can say how much
+                    // we're giving up on.
+                    let _ = std::io::copy(&mut input, &mut std::io::sink());
+                    let orphaned = input.bytes_read() - start;
+                    let message = format!(
+                        "{orphaned} bytes after the last complete chunk are orphaned"
+                    );
+                    if lenient {
+                        diagnostics.warn(
+                            WarningKind::TrailingBytes,
+                            format_args!("{message} ({x:#})"),
+                        );
+                        None
+                    } else {
+                        Some(Err(x.context(message)))
+                    }
+                }
+            };
+            done = !matches!(ret, Some(Ok(_)));
+            ret
+        })
+    }
+    fn read_m1_chunk(
+        mut input: impl Read,
+        position: u64,
+    ) -> anyhow::Result<Option<Chunk>> {
+        let Some(kind) =
+            read_array_opt(&mut input).context("truncated chunk header")?
+        else {
+            return Ok(None);
+        };
+        let _ = read32(&mut input).context("truncated chunk header")?;
+        let count = read16(&mut input).context("truncated chunk header")?;
+        let size = read16(&mut input).context("truncated chunk header")?;
+        let length = count as usize * size as usize;
+        let chunk_data =
+            read_exactly(&mut input, length).with_context(|| {
+                format!("truncated {:?} chunk", String::from_utf8_lossy(&kind))
+            })?;
+        Ok(Some(Chunk {
+            kind,
+            data: chunk_data,
+            offset: 0,
+            data_offset: position + M1_CHUNK_HEADER_SIZE,
+        }))
+    }
+    /// Checks whether `head`, the start of a file `file_length` bytes long,
+    /// makes sense as the header of an M1 chunk. Returns what's wrong with
+    /// it, if anything.
+    pub fn m1_header_problem(head: &[u8], file_length: u64) -> Option<String> {
+        let Some(header) = head.get(..M1_CHUNK_HEADER_SIZE as usize) else { return Some("too short for a chunk header".to_string()) };
+        if !header[..4]
+            .iter()
+            .all(|x| x.is_ascii_graphic() || *x == b' ')
+        {
+            return Some("doesn't start with a chunk tag".to_string());
         }
-        Err(anyhow!(
-            "Unable to find chunk of type {:?}",
-            String::from_utf8_lossy(&kind)
-        ))
+        let count = u16::from_be_bytes([header[8], header[9]]) as u64;
+        let size = u16::from_be_bytes([header[10], header[11]]) as u64;
+        if M1_CHUNK_HEADER_SIZE + count * size > file_length {
+            return Some(format!(
+                "the first chunk claims {count} records of {size} bytes, \
+                 more than the file holds"
+            ));
+        }
+        None
+    }
+    /// Returns the data of the first chunk of the given kind, or an error if
+    /// there isn't one.
+    pub fn find(chunks: &[Chunk], kind: [u8; 4]) -> anyhow::Result<&[u8]> {
+        Chunk::find_optional(chunks, kind)
+            .ok_or_else(|| PhysicsEaterError::ChunkNotFound { kind }.into())
+    }
+    /// Returns the data of every chunk of the given kind, in the order they
+    /// appear.
+    pub fn find_all(chunks: &[Chunk], kind: [u8; 4]) -> Vec<&[u8]> {
+        chunks
+            .iter()
+            .filter(|chunk| chunk.kind == kind)
+            .map(|chunk| &chunk.data[..])
+            .collect()
+    }
+    /// Returns the data of the first chunk of the given kind, if any.
+    pub fn find_optional(chunks: &[Chunk], kind: [u8; 4]) -> Option<&[u8]> {
+        chunks
+            .iter()
+            .find(|chunk| chunk.kind == kind)
+            .map(|chunk| &chunk.data[..])
     }
 }
 
+#[derive(PartialEq, Eq)]
 pub struct Wad {
     pub wad_version: u16,
     pub data_version: u16,
@@ -131,6 +473,14 @@ pub struct Wad {
     pub directory_entry_base_size: u16,
     pub parent_checksum: u32,
     pub files: Vec<Vec<Chunk>>,
+    /// The index of each subfile, from its directory entry, which is how the
+    /// engine tells them apart. WADs from before `WADFILE_HAS_DIRECTORY_ENTRY`
+    /// don't have one, so their subfiles are numbered in order.
+    pub indices: Vec<u16>,
+    /// The application-specific data from each subfile's directory entry,
+    /// `application_specific_directory_data_size` bytes each. (Maps keep
+    /// level names and such here; physics files usually have none.)
+    pub directory_data: Vec<Vec<u8>>,
 }
 
 impl Debug for Wad {
@@ -138,16 +488,7 @@ impl Debug for Wad {
         f.debug_struct("Wad")
             .field("wad_version", &self.wad_version)
             .field("data_version", &self.data_version)
-            .field(
-                "file_name",
-                &String::from_utf8_lossy(
-                    &self.file_name[..self
-                        .file_name
-                        .iter()
-                        .position(|x| *x == 0)
-                        .unwrap_or(MAXIMUM_WADFILE_NAME_LENGTH)],
-                ),
-            )
+            .field("file_name", &self.file_name_str())
             .field("checksum", &self.checksum)
             .field("directory_offset", &self.directory_offset)
             .field("wad_count", &self.wad_count)
@@ -162,12 +503,325 @@ impl Debug for Wad {
             )
             .field("parent_checksum", &self.parent_checksum)
             .field("files", &self.files)
+            .field("indices", &self.indices)
+            .field(
+                "directory_data.len()",
+                &self.directory_data.iter().map(Vec::len).collect::<Vec<_>>(),
+            )
             .finish()
     }
 }
 
 impl Wad {
-    pub fn read_wad(mut input: impl Read + Seek) -> anyhow::Result<Wad> {
+    /// The name stored in the WAD header, up to the first NUL (or all 64
+    /// bytes, if there isn't one), decoded from Mac Roman.
+    pub fn file_name_str(&self) -> Cow<'_, str> {
+        let end = self
+            .file_name
+            .iter()
+            .position(|x| *x == 0)
+            .unwrap_or(self.file_name.len());
+        let name = &self.file_name[..end];
+        match std::str::from_utf8(name) {
+            Ok(name) if name.is_ascii() => Cow::Borrowed(name),
+            _ => Cow::Owned(mac_roman_to_utf8(name)),
+        }
+    }
+    /// Opens and parses the WAD file at the given path. With the `mmap`
+    /// feature, the file is memory-mapped and subfiles are parsed directly
+    /// out of the mapping. A gzipped or wrapped WAD is unwrapped into memory
+    /// first (see `open_input`).
+    pub fn open(
+        path: &Path,
+        lenient: bool,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Wad> {
+        let file = match open_input(path, diagnostics)? {
+            Input::File(file) => file,
+            Input::Memory(data) => {
+                return Wad::read_wad_from_bytes(
+                    data.get_ref(),
+                    lenient,
+                    diagnostics,
+                )
+            }
+        };
+        #[cfg(feature = "mmap")]
+        {
+            // Safety: the mapping only lives until we're done parsing, and
+            // nobody should be modifying a WAD while we read it. (If they
+            // do, we'll read garbage, same as we would without the mapping.)
+            let map = unsafe { memmap2::Mmap::map(&file) }
+                .with_context(|| format!("unable to map {path:?}"))?;
+            Wad::read_wad_from_bytes(&map, lenient, diagnostics)
+        }
+        #[cfg(not(feature = "mmap"))]
+        Wad::read_wad(file, lenient, diagnostics)
+    }
+    pub fn read_wad(
+        input: impl Read + Seek,
+        lenient: bool,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Wad> {
+        Wad::read_wad_with(
+            input,
+            |input, entry_header_size, offset, length| {
+                input
+                    .seek(SeekFrom::Start(offset as u64))
+                    .context("unable to seek to a subfile in WAD")?;
+                let data = read_exactly(input, length as usize)
+                    .context("unable to read a subfile in WAD")?;
+                Chunk::read_m2_chunks(
+                    Cursor::new(&data),
+                    offset as u64,
+                    entry_header_size,
+                    lenient,
+                    diagnostics,
+                )
+            },
+        )
+    }
+    /// Parses a WAD that is entirely in memory. Each subfile is parsed
+    /// straight out of `input`, instead of being copied into its own buffer
+    /// first.
+    pub fn read_wad_from_bytes(
+        input: &[u8],
+        lenient: bool,
+        diagnostics: &Diagnostics,
+    ) -> anyhow::Result<Wad> {
+        Wad::read_wad_with(
+            Cursor::new(input),
+            |_, entry_header_size, offset, length| {
+                let data = input
+                    .get(offset as usize..)
+                    .and_then(|x| x.get(..length as usize))
+                    .ok_or_else(|| {
+                        anyhow!("unable to read a subfile in WAD")
+                    })?;
+                Chunk::read_m2_chunks(
+                    Cursor::new(data),
+                    offset as u64,
+                    entry_header_size,
+                    lenient,
+                    diagnostics,
+                )
+            },
+        )
+    }
+    /// Resolves an overlay WAD against its parent: each subfile of the
+    /// result is the parent's subfile with the same index (the one in its
+    /// directory entry, not its position), with every chunk the overlay has
+    /// in that subfile replacing the parent's chunk with the same tag (or
+    /// being added, if the parent doesn't have one). Subfiles only the
+    /// overlay has are added after the parent's. The result has the
+    /// overlay's header, but isn't an overlay any more.
+    ///
+    /// Errors if this WAD isn't an overlay of `parent`, according to its
+    /// parent checksum.
+    pub fn overlay_onto(self, parent: &Wad) -> anyhow::Result<Wad> {
+        if self.wad_version < WADFILE_SUPPORTS_OVERLAYS {
+            return Err(anyhow!(
+                "this WAD can't be an overlay, its version ({}) is from \
+                 before overlays were supported",
+                self.wad_version
+            ));
+        }
+        if self.parent_checksum == 0 {
+            return Err(anyhow!(
+                "this WAD isn't an overlay, it has no parent checksum"
+            ));
+        }
+        if self.parent_checksum != parent.checksum {
+            return Err(anyhow!(
+                "this WAD is an overlay of a WAD with checksum {:08X}, but \
+                 the given parent's checksum is {:08X}",
+                self.parent_checksum,
+                parent.checksum
+            ));
+        }
+        let mut files = parent.files.clone();
+        let mut indices = parent.subfile_indices();
+        // the parent's directory data, but at the overlay's size
+        let size = self.application_specific_directory_data_size as usize;
+        let mut directory_data: Vec<Vec<u8>> = (0..files.len())
+            .map(|n| {
+                let mut data =
+                    parent.directory_data.get(n).cloned().unwrap_or_default();
+                data.resize(size, 0);
+                data
+            })
+            .collect();
+        let overlay_indices = self.subfile_indices();
+        for (n, overlay) in self.files.into_iter().enumerate() {
+            let index = overlay_indices[n];
+            let position = match indices.iter().position(|x| *x == index) {
+                Some(x) => x,
+                None => {
+                    files.push(vec![]);
+                    indices.push(index);
+                    directory_data.push(vec![0; size]);
+                    files.len() - 1
+                }
+            };
+            if let Some(data) = self.directory_data.get(n) {
+                directory_data[position] = data.clone();
+            }
+            let chunks = &mut files[position];
+            for chunk in overlay {
+                match chunks.iter_mut().find(|x| x.kind == chunk.kind) {
+                    Some(x) => *x = chunk,
+                    None => chunks.push(chunk),
+                }
+            }
+        }
+        Ok(Wad {
+            parent_checksum: 0,
+            files,
+            indices,
+            directory_data,
+            ..self
+        })
+    }
+    /// The index of each subfile: the one from its directory entry, or its
+    /// position if it doesn't have one (e.g. because it was added).
+    pub fn subfile_indices(&self) -> Vec<u16> {
+        (0..self.files.len())
+            .map(|n| self.indices.get(n).copied().unwrap_or(n as u16))
+            .collect()
+    }
+    /// How long each entry header (the header before each chunk) is. WADs
+    /// from before `WADFILE_HAS_DIRECTORY_ENTRY` don't give it in the header,
+    /// and always have short ones; later WADs can have longer ones than we
+    /// know what to do with, whose extra bytes are skipped.
+    pub fn entry_header_length(&self) -> u16 {
+        if self.wad_version < WADFILE_HAS_DIRECTORY_ENTRY {
+            OLD_ENTRY_HEADER_SIZE
+        } else {
+            self.entry_header_size
+        }
+    }
+    /// How long each directory entry is, not counting application-specific
+    /// data. Like the entry header size, it's fixed for old WADs.
+    fn directory_entry_base_length(&self) -> u16 {
+        if self.wad_version < WADFILE_HAS_DIRECTORY_ENTRY {
+            OLD_DIRECTORY_ENTRY_SIZE
+        } else {
+            self.directory_entry_base_size
+        }
+    }
+    /// How far apart the entries of the directory are: the base size plus the
+    /// size of the application-specific data that follows each entry.
+    pub fn directory_entry_length(&self) -> usize {
+        self.directory_entry_base_length() as usize
+            + self.application_specific_directory_data_size as usize
+    }
+    /// Checks that a WAD that was read from a file `file_length` bytes long
+    /// ends where its header says: right after the last of its `wad_count`
+    /// directory entries. Warns about anything appended after that, which
+    /// nothing reads.
+    pub fn check_extent(&self, file_length: u64, diagnostics: &Diagnostics) {
+        let end = self.directory_offset as u64
+            + self.wad_count as u64 * self.directory_entry_length() as u64;
+        if file_length > end {
+            diagnostics.warn(
+                WarningKind::TrailingBytes,
+                format_args!(
+                    "ignoring {} trailing bytes after the end of the \
+                     directory at {end:08X}",
+                    file_length - end
+                ),
+            );
+        }
+    }
+    /// Writes out a complete WAD: header, then each subfile in order, then
+    /// the directory. The directory offset, entry count and checksum are
+    /// recomputed; the other header fields are written as they are, and
+    /// the entry headers and directory entries are laid out as they say.
+    /// Each subfile's index and application-specific directory data are
+    /// written as they were read; a subfile without them (e.g. one that was
+    /// added) gets its position as its index, and zeroes for its data.
+    pub fn write_wad(&self, mut output: impl Write) -> anyhow::Result<()> {
+        if self.wad_version >= WADFILE_HAS_DIRECTORY_ENTRY
+            && self.directory_entry_base_size < OLD_DIRECTORY_ENTRY_SIZE
+        {
+            return Err(anyhow!(
+                "WAD directory entries can't be only {} bytes long",
+                self.directory_entry_base_size
+            ));
+        }
+        if self.files.len() > MAXIMUM_DIRECTORY_ENTRIES_PER_FILE {
+            return Err(anyhow!(
+                "a WAD can have at most {MAXIMUM_DIRECTORY_ENTRIES_PER_FILE} subfiles"
+            ));
+        }
+        let mut buf = vec![0; WAD_HEADER_SIZE];
+        let mut directory = vec![];
+        let indices = self.subfile_indices();
+        for (index, chunks) in self.files.iter().enumerate() {
+            let offset = buf.len() as u32;
+            Chunk::write_m2_chunks(
+                chunks,
+                self.entry_header_length(),
+                &mut buf,
+            )?;
+            let start = directory.len();
+            write32(&mut directory, offset)?;
+            write32(&mut directory, buf.len() as u32 - offset)?;
+            if self.directory_entry_base_length() >= 10 {
+                write16(&mut directory, indices[index])?;
+            }
+            directory.resize(
+                start + self.directory_entry_base_length() as usize,
+                0,
+            );
+            match self.directory_data.get(index) {
+                Some(data)
+                    if data.len()
+                        == self.application_specific_directory_data_size
+                            as usize =>
+                {
+                    directory.extend_from_slice(data)
+                }
+                Some(data) => {
+                    return Err(anyhow!(
+                    "subfile #{index} has {} bytes of application-specific \
+                         directory data, but the header says there are {}",
+                    data.len(),
+                    self.application_specific_directory_data_size
+                ))
+                }
+                None => {
+                    directory.resize(start + self.directory_entry_length(), 0)
+                }
+            }
+        }
+        let directory_offset =
+            u32::try_from(buf.len()).map_err(|_| anyhow!("WAD is too big"))?;
+        buf.extend_from_slice(&directory);
+        let mut header = &mut buf[..WAD_HEADER_SIZE];
+        write16(&mut header, self.wad_version)?;
+        write16(&mut header, self.data_version)?;
+        header.write_all(&self.file_name)?;
+        write32(&mut header, 0)?; // checksum, filled in below
+        write32(&mut header, directory_offset)?;
+        write16(&mut header, self.files.len() as u16)?;
+        write16(&mut header, self.application_specific_directory_data_size)?;
+        write16(&mut header, self.entry_header_size)?;
+        write16(&mut header, self.directory_entry_base_size)?;
+        write32(&mut header, self.parent_checksum)?;
+        update_checksum(&mut buf);
+        output.write_all(&buf)?;
+        Ok(())
+    }
+    fn read_wad_with<R: Read + Seek>(
+        mut input: R,
+        mut read_subfile: impl FnMut(
+            &mut R,
+            u16,
+            u32,
+            u32,
+        ) -> anyhow::Result<Vec<Chunk>>,
+    ) -> anyhow::Result<Wad> {
         if is_m1_physics(&mut input)? {
             return Err(anyhow!(
                 "this is a Marathon 1 physics file, not a WAD!"
@@ -184,33 +838,13 @@ impl Wad {
         let entry_header_size = read16(&mut input)?;
         let directory_entry_base_size = read16(&mut input)?;
         let parent_checksum = read32(&mut input)?;
-        let directory_entry_base_size =
-            if wad_version <= WADFILE_HAS_DIRECTORY_ENTRY {
-                8
-            } else {
-                directory_entry_base_size
-            };
-        let unit_size = application_specific_directory_data_size as usize
-            + directory_entry_base_size as usize;
-        let mut files = vec![];
-        for i in 0..MAXIMUM_DIRECTORY_ENTRIES_PER_FILE {
-            let offset = directory_offset as u64 + unit_size as u64 * i as u64;
-            input
-                .seek(SeekFrom::Start(offset))
-                .context("unable to seek to directory entry in WAD")?;
-            let Ok(offset) = read32(&mut input) else { break };
-            let length = read32(&mut input)?;
-            input
-                .seek(SeekFrom::Start(offset as u64))
-                .context("unable to seek to a subfile in WAD")?;
-            let mut data = vec![0; length as usize];
-            input
-                .read_exact(&mut data)
-                .context("unable to read a subfile in WAD")?;
-            let chunks = Chunk::read_m2_chunks(Cursor::new(&data))?;
-            files.push(chunks);
+        let file_length = input.seek(SeekFrom::End(0))?;
+        if directory_offset as u64 > file_length {
+            return Err(anyhow!(
+                "WAD directory offset {directory_offset:08X} is past the end of the file"
+            ));
         }
-        Ok(Wad {
+        let mut wad = Wad {
             wad_version,
             data_version,
             file_name,
@@ -221,21 +855,576 @@ impl Wad {
             entry_header_size,
             directory_entry_base_size,
             parent_checksum,
-            files,
+            files: vec![],
+            indices: vec![],
+            directory_data: vec![],
+        };
+        let entry_header_size = wad.entry_header_length();
+        let unit_size = wad.directory_entry_length();
+        if wad_version >= WADFILE_HAS_DIRECTORY_ENTRY
+            && directory_entry_base_size < OLD_DIRECTORY_ENTRY_SIZE
+        {
+            return Err(anyhow!(
+                "WAD directory entries can't be only \
+                 {directory_entry_base_size} bytes long"
+            ));
+        }
+        if wad_count as usize > MAXIMUM_DIRECTORY_ENTRIES_PER_FILE {
+            return Err(anyhow!(
+                "WAD header says there are {wad_count} subfiles, but a WAD \
+                 can have at most {MAXIMUM_DIRECTORY_ENTRIES_PER_FILE}"
+            ));
+        }
+        // The header's count is what the engine goes by, so anything after
+        // that many entries isn't part of the directory.
+        for i in 0..wad_count as usize {
+            let entry = directory_offset as u64 + unit_size as u64 * i as u64;
+            input
+                .seek(SeekFrom::Start(entry))
+                .context("unable to seek to directory entry in WAD")?;
+            let offset = read32(&mut input)
+                .context("truncated directory entry in WAD")?;
+            let length = read32(&mut input)
+                .context("truncated directory entry in WAD")?;
+            wad.indices
+                .push(if wad.directory_entry_base_length() >= 10 {
+                    read16(&mut input)
+                        .context("truncated directory entry in WAD")?
+                } else {
+                    i as u16
+                });
+            if offset as u64 + length as u64 > file_length {
+                return Err(anyhow!(
+                    "WAD directory entry #{i} points past the end of the file"
+                ));
+            }
+            input.seek(SeekFrom::Start(
+                entry + wad.directory_entry_base_length() as u64,
+            ))?;
+            wad.directory_data.push(
+                read_exactly(
+                    &mut input,
+                    application_specific_directory_data_size as usize,
+                )
+                .context("truncated directory entry in WAD")?,
+            );
+            wad.files.push(read_subfile(
+                &mut input,
+                entry_header_size,
+                offset,
+                length,
+            )?);
+        }
+        Ok(wad)
+    }
+}
+
+/// Puts together a new `Wad`, for `write_wad`. By default, the WAD is an
+/// unnamed Infinity-format WAD with no subfiles.
+///
+/// ```no_run
+/// # use physics_eater::*;
+/// let wad = WadBuilder::new()
+///     .file_name("Physics")
+///     .chunk(Chunk::new(*b"MNpx", std::fs::read("MNpx.bin")?))
+///     .chunk(Chunk::new(*b"FXpx", std::fs::read("FXpx.bin")?))
+///     .build()?;
+/// wad.write_wad(std::fs::File::create("Physics.phyA")?)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct WadBuilder {
+    wad_version: u16,
+    data_version: u16,
+    file_name: String,
+    parent_checksum: u32,
+    files: Vec<Vec<Chunk>>,
+}
+
+impl Default for WadBuilder {
+    fn default() -> WadBuilder {
+        WadBuilder {
+            wad_version: WADFILE_HAS_INFINITY_STUFF,
+            data_version: 0,
+            file_name: String::new(),
+            parent_checksum: 0,
+            files: vec![],
+        }
+    }
+}
+
+impl WadBuilder {
+    pub fn new() -> WadBuilder {
+        WadBuilder::default()
+    }
+    pub fn wad_version(mut self, wad_version: u16) -> WadBuilder {
+        self.wad_version = wad_version;
+        self
+    }
+    pub fn data_version(mut self, data_version: u16) -> WadBuilder {
+        self.data_version = data_version;
+        self
+    }
+    /// The name to put in the header. It's stored as Mac Roman, so it can
+    /// only have characters that Mac Roman has, and must fit in 63 bytes.
+    pub fn file_name(mut self, file_name: &str) -> WadBuilder {
+        self.file_name = file_name.to_string();
+        self
+    }
+    /// The checksum of the WAD this one is an overlay of.
+    pub fn parent_checksum(mut self, parent_checksum: u32) -> WadBuilder {
+        self.parent_checksum = parent_checksum;
+        self
+    }
+    /// Adds a subfile with the given chunks.
+    pub fn subfile(mut self, chunks: Vec<Chunk>) -> WadBuilder {
+        self.files.push(chunks);
+        self
+    }
+    /// Adds a chunk to the last subfile, starting the first one if there are
+    /// no subfiles yet.
+    pub fn chunk(mut self, chunk: Chunk) -> WadBuilder {
+        match self.files.last_mut() {
+            Some(chunks) => chunks.push(chunk),
+            None => self.files.push(vec![chunk]),
+        }
+        self
+    }
+    pub fn build(self) -> anyhow::Result<Wad> {
+        if self.wad_version > WADFILE_HAS_INFINITY_STUFF {
+            return Err(anyhow!("unknown WAD version {}", self.wad_version));
+        }
+        if self.files.len() > MAXIMUM_DIRECTORY_ENTRIES_PER_FILE {
+            return Err(anyhow!(
+                "a WAD can have at most {MAXIMUM_DIRECTORY_ENTRIES_PER_FILE} subfiles"
+            ));
+        }
+        let name = utf8_to_mac_roman(&self.file_name).ok_or_else(|| {
+            anyhow!("{:?} can't be written in Mac Roman", self.file_name)
+        })?;
+        // leave room for the terminating NUL
+        if name.len() >= MAXIMUM_WADFILE_NAME_LENGTH {
+            return Err(anyhow!(
+                "{:?} is too long for a WAD name, the limit is {} bytes",
+                self.file_name,
+                MAXIMUM_WADFILE_NAME_LENGTH - 1
+            ));
+        }
+        let mut file_name = [0; MAXIMUM_WADFILE_NAME_LENGTH];
+        file_name[..name.len()].copy_from_slice(&name);
+        Ok(Wad {
+            wad_version: self.wad_version,
+            data_version: self.data_version,
+            file_name,
+            checksum: 0,
+            directory_offset: 0,
+            wad_count: self.files.len() as u16,
+            application_specific_directory_data_size: 0,
+            entry_header_size: ENTRY_HEADER_SIZE,
+            directory_entry_base_size: if self.wad_version
+                >= WADFILE_HAS_DIRECTORY_ENTRY
+            {
+                10
+            } else {
+                8
+            },
+            parent_checksum: self.parent_checksum,
+            files: self.files,
+            indices: vec![],
+            directory_data: vec![],
         })
     }
 }
 
-pub fn show_wad(wad_path: PathBuf) -> anyhow::Result<()> {
-    let f = File::open(wad_path).context("unable to open file")?;
-    let wad = Wad::read_wad(f).context("unable to read wad")?;
+/// Checks whether `head`, the start of a file `file_length` bytes long,
+/// makes sense as a WAD header. Returns what's wrong with it, if anything.
+pub fn wad_header_problem(head: &[u8], file_length: u64) -> Option<String> {
+    if head.len() < WAD_HEADER_SIZE {
+        return Some("too short for a WAD header".to_string());
+    }
+    let wad_version = u16::from_be_bytes([head[0], head[1]]);
+    if wad_version > WADFILE_HAS_INFINITY_STUFF {
+        return Some(format!("unknown WAD version {wad_version}"));
+    }
+    let directory_offset =
+        u32::from_be_bytes(head[72..76].try_into().unwrap()) as u64;
+    if directory_offset < WAD_HEADER_SIZE as u64
+        || directory_offset > file_length
+    {
+        return Some(format!(
+            "directory offset {directory_offset:08X} is outside the file"
+        ));
+    }
+    None
+}
+
+/// Recomputes the checksum of a complete WAD file in memory, and stores it in
+/// the header. Returns the old and new checksums.
+fn update_checksum(wad: &mut [u8]) -> (u32, u32) {
+    let field = &mut wad[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4];
+    let old = u32::from_be_bytes(field.try_into().unwrap());
+    field.fill(0);
+    let new = wad_checksum(wad);
+    wad[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4]
+        .copy_from_slice(&new.to_be_bytes());
+    (old, new)
+}
+
+/// Copies a WAD file, fixing its checksum along the way, and says on stdout
+/// what the checksum was and is. Everything else is copied byte for byte, so
+/// unlike everything else, this doesn't take a compressed or wrapped WAD, or
+/// one inside a zip archive; unwrap it first.
+pub fn fix_checksum(
+    wad_path: PathBuf,
+    output_path: PathBuf,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    if split_zip_path(&wad_path).is_some() {
+        return Err(anyhow!(
+            "{wad_path:?} is in a zip archive; extract it before fixing its \
+             checksum"
+        ));
+    }
+    let mut data = std::fs::read(&wad_path)
+        .with_context(|| format!("unable to read {wad_path:?}"))?;
+    if needs_unwrapping(&data) {
+        return Err(anyhow!(
+            "{wad_path:?} is compressed, or wrapped in MacBinary or \
+             AppleSingle/AppleDouble; unwrap it before fixing its checksum"
+        ));
+    }
+    // Make sure it really is a WAD before we go writing into its header.
+    Wad::read_wad_from_bytes(&data, lenient, diagnostics)
+        .context("unable to read wad")?;
+    let (old, new) = update_checksum(&mut data);
+    if old == new {
+        println!("checksum was already correct ({new:08X})");
+    } else {
+        println!("checksum changed from {old:08X} to {new:08X}");
+    }
+    std::fs::write(&output_path, &data)
+        .with_context(|| format!("unable to write {output_path:?}"))?;
+    Ok(())
+}
+
+/// Parses a four-character chunk tag given on the command line.
+pub fn parse_tag(tag: &str) -> Result<[u8; 4], String> {
+    tag.as_bytes().try_into().map_err(|_| {
+        format!(
+            "chunk tags must be exactly four bytes long, not {}",
+            tag.len()
+        )
+    })
+}
+
+/// Reads the chunks of a bare M1 chunk file, or of one subfile of an M2 WAD.
+fn read_any_chunks(
+    path: &Path,
+    subfile: usize,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<Chunk>> {
+    let mut f = open_input(path, diagnostics)?;
+    if is_m1_physics(&mut f)? {
+        return Chunk::read_m1_chunks(f, lenient, diagnostics)
+            .context("unable to read chunks");
+    }
+    drop(f);
+    let mut wad =
+        Wad::open(path, lenient, diagnostics).context("unable to read wad")?;
+    if subfile >= wad.files.len() {
+        return Err(anyhow!(
+            "there is no subfile #{subfile}, the WAD only has {}",
+            wad.files.len()
+        ));
+    }
+    Ok(wad.files.swap_remove(subfile))
+}
+
+/// Writes the raw contents of one chunk out to a file.
+pub fn extract_chunk(
+    path: PathBuf,
+    tag: [u8; 4],
+    subfile: usize,
+    output_path: PathBuf,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let chunks = read_any_chunks(&path, subfile, lenient, diagnostics)?;
+    let data = Chunk::find(&chunks, tag)?;
+    std::fs::write(&output_path, data)
+        .with_context(|| format!("unable to write {output_path:?}"))?;
+    Ok(())
+}
+
+/// Replaces the contents of one chunk of an M2 WAD with the contents of a
+/// file, and writes out the resulting WAD.
+pub fn replace_chunk(
+    path: PathBuf,
+    tag: [u8; 4],
+    data_path: PathBuf,
+    subfile: usize,
+    output_path: PathBuf,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let data = std::fs::read(&data_path)
+        .with_context(|| format!("unable to read {data_path:?}"))?;
+    let mut f = open_input(&path, diagnostics)?;
+    if is_m1_physics(&mut f)? {
+        return Err(anyhow!("replace-chunk only works on M2 WADs"));
+    }
+    drop(f);
+    let mut wad = Wad::open(&path, lenient, diagnostics)
+        .context("unable to read wad")?;
+    let file_count = wad.files.len();
+    let chunks = wad.files.get_mut(subfile).ok_or_else(|| {
+        anyhow!(
+            "there is no subfile #{subfile}, the WAD only has {file_count}"
+        )
+    })?;
+    let chunk = chunks
+        .iter_mut()
+        .find(|chunk| chunk.kind == tag)
+        .ok_or(PhysicsEaterError::ChunkNotFound { kind: tag })?;
+    chunk.data = data;
+    let mut out = vec![];
+    wad.write_wad(&mut out)?;
+    std::fs::write(&output_path, out)
+        .with_context(|| format!("unable to write {output_path:?}"))?;
+    Ok(())
+}
+
+pub fn show_wad(
+    wad_path: PathBuf,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let wad = Wad::open(&wad_path, lenient, diagnostics)
+        .context("unable to read wad")?;
     dbg!(wad);
     Ok(())
 }
 
-pub fn show_chunks(wad_path: PathBuf) -> anyhow::Result<()> {
-    let f = File::open(wad_path).context("unable to open file")?;
-    let chunks = Chunk::read_m1_chunks(f).context("unable to read chunks")?;
-    dbg!(chunks);
+/// A machine-readable description of a chunk, for `show-chunks --json`.
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct ChunkSummary {
+    kind: String,
+    length: usize,
+    /// The chunk's contents, base64-encoded. Only present with
+    /// `--dump-data`.
+    #[cfg_attr(
+        feature = "json",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    data: Option<String>,
+}
+
+#[cfg(feature = "json")]
+impl ChunkSummary {
+    fn new(chunk: &Chunk, dump_data: bool) -> ChunkSummary {
+        ChunkSummary {
+            kind: String::from_utf8_lossy(&chunk.kind).into_owned(),
+            length: chunk.data.len(),
+            data: dump_data.then(|| BASE64_STANDARD.encode(&chunk.data)),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub fn show_chunks(
+    wad_path: PathBuf,
+    json: bool,
+    dump_data: bool,
+    lenient: bool,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    let f = open_input(&wad_path, diagnostics)?;
+    let chunks = Chunk::read_m1_chunks(f, lenient, diagnostics)
+        .context("unable to read chunks")?;
+    if json {
+        let summaries: Vec<ChunkSummary> = chunks
+            .iter()
+            .map(|x| ChunkSummary::new(x, dump_data))
+            .collect();
+        let mut out = stdout().lock();
+        serde_json::to_writer_pretty(&mut out, &summaries)?;
+        writeln!(out)?;
+    } else {
+        dbg!(chunks);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_all_finds_every_chunk_with_a_tag() {
+        let chunks = [
+            Chunk::new(*b"MNpx", vec![1]),
+            Chunk::new(*b"FXpx", vec![2]),
+            Chunk::new(*b"MNpx", vec![3, 4]),
+        ];
+        assert_eq!(
+            Chunk::find_all(&chunks, *b"MNpx"),
+            [&[1][..], &[3, 4][..]]
+        );
+        assert_eq!(Chunk::find_all(&chunks, *b"FXpx"), [&[2][..]]);
+        assert!(Chunk::find_all(&chunks, *b"PRpx").is_empty());
+        assert_eq!(Chunk::find(&chunks, *b"MNpx").unwrap(), [1]);
+    }
+
+    /// Read, write, read: writing out a WAD that was read in and reading it
+    /// back gives the same WAD, down to where each chunk was, and writing
+    /// that out again gives the same bytes.
+    #[test]
+    fn write_wad_round_trip() {
+        let bytes = include_bytes!("../benches/sample.phyA");
+        let diagnostics = Diagnostics::default();
+        let wad =
+            Wad::read_wad_from_bytes(bytes, false, &diagnostics).unwrap();
+        let mut written = vec![];
+        wad.write_wad(&mut written).unwrap();
+        let reread =
+            Wad::read_wad(Cursor::new(&written), false, &diagnostics).unwrap();
+        let mut rewritten = vec![];
+        reread.write_wad(&mut rewritten).unwrap();
+        assert_eq!(rewritten, written);
+        assert_eq!(
+            Wad {
+                checksum: wad.checksum,
+                directory_offset: wad.directory_offset,
+                ..reread
+            },
+            wad
+        );
+        assert!(diagnostics.recorded().is_empty());
+    }
+
+    /// A chunk whose next offset links back to an earlier chunk is an error,
+    /// not an endless loop.
+    #[test]
+    fn chunk_cycle_is_an_error() {
+        let chunks = [
+            Chunk::new(*b"MNpx", vec![0; 4]),
+            Chunk::new(*b"FXpx", vec![0; 4]),
+            Chunk::new(*b"PRpx", vec![0; 4]),
+        ];
+        let mut data = vec![];
+        Chunk::write_m2_chunks(&chunks, ENTRY_HEADER_SIZE, &mut data).unwrap();
+        // point the third chunk back at the second (a next offset of zero
+        // would just end the list)
+        let chunk_size = ENTRY_HEADER_SIZE as usize + 4;
+        let third = chunk_size * 2;
+        data[third + 4..third + 8]
+            .copy_from_slice(&(chunk_size as u32).to_be_bytes());
+        let error = Chunk::read_m2_chunks(
+            Cursor::new(&data),
+            0,
+            ENTRY_HEADER_SIZE,
+            false,
+            &Diagnostics::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "chunk #2 \"PRpx\", located at 00000028 within the subfile, \
+             links back to chunk #1"
+        );
+    }
+
+    /// A name that fills all 64 bytes has no NUL, and runs right up to the
+    /// end of the field.
+    #[test]
+    fn file_name_without_nul() {
+        let mut wad = WadBuilder::new().build().unwrap();
+        wad.file_name = [b'x'; MAXIMUM_WADFILE_NAME_LENGTH];
+        assert_eq!(wad.file_name_str(), "x".repeat(64));
+        // and the last byte is still decoded from Mac Roman
+        wad.file_name[63] = 0xD5;
+        assert_eq!(wad.file_name_str(), "x".repeat(63) + "\u{2019}");
+    }
+
+    /// A version 2 WAD whose header says its entry headers and directory
+    /// entries are longer than the ones we know: the extra bytes, whatever
+    /// they hold, are skipped.
+    #[test]
+    fn larger_entry_headers_are_skipped() {
+        let mut wad = WadBuilder::new()
+            .wad_version(WADFILE_SUPPORTS_OVERLAYS)
+            .chunk(Chunk::new(*b"MNpx", vec![1, 2, 3]))
+            .chunk(Chunk::new(*b"FXpx", vec![4, 5]))
+            .build()
+            .unwrap();
+        wad.entry_header_size = ENTRY_HEADER_SIZE + 8;
+        wad.directory_entry_base_size = 16;
+        let mut data = vec![];
+        wad.write_wad(&mut data).unwrap();
+        // fill the extra bytes of the first entry header, and of the
+        // directory entry, with junk
+        let extra = WAD_HEADER_SIZE + ENTRY_HEADER_SIZE as usize;
+        data[extra..extra + 8].fill(0xA5);
+        let directory = data.len() - 16;
+        data[directory + 10..].fill(0xA5);
+        let reread =
+            Wad::read_wad(Cursor::new(&data), false, &Diagnostics::default())
+                .unwrap();
+        assert_eq!(reread.entry_header_length(), 24);
+        assert_eq!(reread.directory_entry_length(), 16);
+        let [chunks] = &reread.files[..] else {
+            panic!("expected one subfile, got {}", reread.files.len())
+        };
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(Chunk::find(chunks, *b"MNpx").unwrap(), [1, 2, 3]);
+        assert_eq!(Chunk::find(chunks, *b"FXpx").unwrap(), [4, 5]);
+    }
+
+    /// An overlay's subfiles are merged by the index in their directory
+    /// entries, not by where they are in the directory, so an overlay with
+    /// only subfile #3 patches the parent's subfile #3.
+    #[test]
+    fn sparse_overlay() {
+        let diagnostics = Diagnostics::default();
+        let reread = |wad: &Wad| {
+            let mut data = vec![];
+            wad.write_wad(&mut data).unwrap();
+            Wad::read_wad(Cursor::new(&data), false, &diagnostics).unwrap()
+        };
+        let parent = reread(
+            &(0..4)
+                .fold(WadBuilder::new(), |builder, n| {
+                    builder.subfile(vec![
+                        Chunk::new(*b"MNpx", vec![n]),
+                        Chunk::new(*b"FXpx", vec![n]),
+                    ])
+                })
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(parent.indices, [0, 1, 2, 3]);
+        let mut overlay = WadBuilder::new()
+            .parent_checksum(parent.checksum)
+            .subfile(vec![
+                Chunk::new(*b"FXpx", vec![33]),
+                Chunk::new(*b"PRpx", vec![34]),
+            ])
+            .subfile(vec![Chunk::new(*b"MNpx", vec![77])])
+            .build()
+            .unwrap();
+        overlay.indices = vec![3, 7];
+        let overlay = reread(&overlay);
+        assert_eq!(overlay.indices, [3, 7]);
+        let merged = reread(&overlay.overlay_onto(&parent).unwrap());
+        assert_eq!(merged.indices, [0, 1, 2, 3, 7]);
+        assert_eq!(merged.files[..3], parent.files[..3]);
+        let chunks = &merged.files[3];
+        assert_eq!(Chunk::find(chunks, *b"MNpx").unwrap(), [3]);
+        assert_eq!(Chunk::find(chunks, *b"FXpx").unwrap(), [33]);
+        assert_eq!(Chunk::find(chunks, *b"PRpx").unwrap(), [34]);
+        assert_eq!(Chunk::find(&merged.files[4], *b"MNpx").unwrap(), [77]);
+    }
+}
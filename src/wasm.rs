@@ -0,0 +1,34 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A thin wasm-bindgen wrapper, for converting physics in a browser. Only
+//! built with the `wasm` feature. Everything here works on bytes that are
+//! already in memory; nothing touches the filesystem or stdout.
+
+use super::*;
+
+use wasm_bindgen::prelude::*;
+
+/// Parses a complete physics file, M1 or M2, given as a `Uint8Array`, and
+/// returns the same JSON that `convert-*-physics` would output, using the
+/// built-in names. M2 physics are parsed as Infinity physics.
+#[wasm_bindgen(js_name = physicsToJson)]
+pub fn physics_to_json(input: &[u8]) -> Result<String, JsError> {
+    let physics = parse_physics_from_bytes(input, &NameDbs::default())
+        .map_err(|x| JsError::new(&format!("{x:#}")))?;
+    serde_json::to_string(&physics).map_err(|x| JsError::new(&x.to_string()))
+}
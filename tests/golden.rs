@@ -0,0 +1,466 @@
+/*
+    This file is part of physics-eater, copyright 2023 Solra Bizna.
+
+    physics-eater is free software: you can redistribute it and/or modify it
+    under the terms of the GNU General Public License as published by the Free
+    Software Foundation, either version 3 of the License, or (at your option)
+    any later version.
+
+    physics-eater is distributed in the hope that it will be useful, but
+    WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+    or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+    more details.
+
+    You should have received a copy of the GNU General Public License along
+    with physics-eater. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Golden tests: the convert commands, run on the synthetic sample files in
+//! `benches/`, must output exactly the JSON in `tests/golden/`. After a
+//! deliberate change to the output, run the tests with
+//! `PHYSICS_EATER_BLESS=1` to rewrite the golden files, and review the diff.
+//! There are also round trips through patching and the binary formats.
+
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde_json::{Map, Value};
+
+fn manifest_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+/// Runs the binary with the given arguments, and returns its stdout. Panics if
+/// it fails.
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_physics-eater"))
+        .args(args)
+        .output()
+        .expect("unable to run physics-eater");
+    assert!(
+        output.status.success(),
+        "physics-eater {args:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("output wasn't UTF-8")
+}
+
+/// Runs the binary with the given arguments, expecting it to fail, and
+/// returns its stderr.
+fn run_failing(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_physics-eater"))
+        .args(args)
+        .output()
+        .expect("unable to run physics-eater");
+    assert!(!output.status.success(), "physics-eater {args:?} succeeded");
+    String::from_utf8(output.stderr).expect("errors weren't UTF-8")
+}
+
+fn check_golden(command: &str, sample: &str) {
+    let sample_path = manifest_path(&format!("benches/{sample}"));
+    let golden_path = manifest_path(&format!("tests/golden/{sample}.json"));
+    let output = run(&[command, sample_path.to_str().unwrap()]);
+    if std::env::var_os("PHYSICS_EATER_BLESS").is_some() {
+        fs::write(&golden_path, &output).unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&golden_path).unwrap();
+    // compare line by line, so a failure points at where they diverge
+    for (n, (got, expected)) in output.lines().zip(golden.lines()).enumerate()
+    {
+        assert_eq!(got, expected, "{sample}: line {} differs", n + 1);
+    }
+    assert_eq!(output, golden, "{sample}: output has the wrong length");
+}
+
+#[test]
+fn convert_m2_sample() {
+    check_golden("convert-m2-physics", "sample.phyA");
+}
+
+#[test]
+fn convert_m1_sample() {
+    check_golden("convert-m1-physics", "sample.phys");
+}
+
+/// Removes every `_reserved_bits` key. Unknown flag bits are passed through
+/// as-is and can't be patched, so they stay with the record they came from.
+fn strip_reserved_bits(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("_reserved_bits");
+            map.values_mut().for_each(strip_reserved_bits);
+        }
+        Value::Array(array) => array.iter_mut().for_each(strip_reserved_bits),
+        _ => (),
+    }
+}
+
+/// Like `==`, but numbers only have to agree to `f32` precision: a 16.16
+/// fixed point value over 256 doesn't survive the trip through `f32` exactly.
+fn roughly_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+            (a - b).abs() <= a.abs().max(b.abs()) * f32::EPSILON as f64
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| roughly_equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(k, a)| {
+                    b.get(k).is_some_and(|b| roughly_equal(a, b))
+                })
+        }
+        _ => a == b,
+    }
+}
+
+/// Parse, encode, parse: patches every definition of the M2 sample with the
+/// complete JSON of the next definition of the same kind, so that nearly
+/// every field gets encoded, then converts the result and checks that each
+/// definition now reads back as the one it was given.
+#[test]
+fn patch_roundtrip_m2_sample() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let sample_path = sample_path.to_str().unwrap();
+    let mut base: Value =
+        serde_json::from_str(&run(&["convert-m2-physics", sample_path]))
+            .unwrap();
+    strip_reserved_bits(&mut base);
+    let mut patch = Map::new();
+    for (kind, definitions) in base.as_object().unwrap() {
+        let Value::Array(definitions) = definitions else { continue };
+        let rotated = (0..definitions.len())
+            .map(|n| {
+                let next = &definitions[(n + 1) % definitions.len()];
+                (n.to_string(), next.clone())
+            })
+            .collect();
+        patch.insert(kind.clone(), Value::Object(rotated));
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let patch_path = dir.join("roundtrip_patch.json");
+    let output_path = dir.join("roundtrip.phyA");
+    fs::write(&patch_path, Value::Object(patch).to_string()).unwrap();
+    run(&[
+        "apply-patch",
+        patch_path.to_str().unwrap(),
+        sample_path,
+        "--output",
+        output_path.to_str().unwrap(),
+    ]);
+    let mut patched: Value = serde_json::from_str(&run(&[
+        "convert-m2-physics",
+        output_path.to_str().unwrap(),
+    ]))
+    .unwrap();
+    strip_reserved_bits(&mut patched);
+    for (kind, definitions) in base.as_object().unwrap() {
+        let Value::Array(definitions) = definitions else {
+            assert!(
+                roughly_equal(definitions, &patched[kind]),
+                "{kind} changed"
+            );
+            continue;
+        };
+        for n in 0..definitions.len() {
+            let expected = &definitions[(n + 1) % definitions.len()];
+            assert!(
+                roughly_equal(expected, &patched[kind][n]),
+                "{kind} #{n} didn't read back as it was written:\n\
+                 expected {expected}\n     got {}",
+                patched[kind][n]
+            );
+        }
+    }
+}
+
+/// Converts the M2 sample, with names, to the given binary format, decodes
+/// it, and checks that it's the same as the JSON output: names are still
+/// strings, and unnamed references are still numbers.
+fn check_binary_format(format: &str, decode: impl Fn(&[u8]) -> Value) {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let namedb = manifest_path("infinity_names");
+    let output_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("sample.{format}"));
+    let args = [
+        "convert-m2-physics",
+        "--namedb",
+        namedb.to_str().unwrap(),
+        sample_path.to_str().unwrap(),
+    ];
+    let json: Value = serde_json::from_str(&run(&args)).unwrap();
+    run(&[
+        &args[..],
+        &[
+            "--format",
+            format,
+            "--output",
+            output_path.to_str().unwrap(),
+        ],
+    ]
+    .concat());
+    let decoded = decode(&fs::read(&output_path).unwrap());
+    let marine = &decoded["monster_definitions"][0];
+    assert_eq!(marine["name"], "Marine");
+    assert_eq!(marine["impact_effect"], "fighter melee detonation");
+    assert_eq!(marine["melee_impact_effect"], 256);
+    assert_eq!(marine["contrail_effect"], Value::Null);
+    assert!(roughly_equal(&json, &decoded), "{format} differs from JSON");
+}
+
+#[test]
+fn msgpack_round_trip() {
+    check_binary_format("msgpack", |x| rmp_serde::from_slice(x).unwrap());
+}
+
+#[test]
+fn cbor_round_trip() {
+    check_binary_format("cbor", |x| ciborium::from_reader(x).unwrap());
+}
+
+/// A patch with several things wrong with it: every one is reported, and
+/// nothing is written, even with `--output`.
+#[test]
+fn bad_patch_is_reported_without_writing() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let patch_path = dir.join("bad_patch.json");
+    let output_path = dir.join("bad_patch.phyA");
+    let _ = fs::remove_file(&output_path);
+    let patch = serde_json::json!({
+        "monster_definitions": {
+            "0": {"vitality": "lots"},
+            "999": {"vitality": 1},
+        },
+        "effect_definitions": {"0": {"delay_sound": "no such sound"}},
+        "scenery_definitions": {},
+    });
+    fs::write(&patch_path, patch.to_string()).unwrap();
+    let patch_path = patch_path.to_str().unwrap();
+    let sample_path = sample_path.to_str().unwrap();
+    let stderr = run_failing(&[
+        "apply-patch",
+        patch_path,
+        sample_path,
+        "--output",
+        output_path.to_str().unwrap(),
+    ]);
+    for expected in [
+        "error: monster_definitions/0/vitality",
+        "error: monster_definitions/999: there are only",
+        "error: effect_definitions/0/delay_sound",
+        "error: \"scenery_definitions\" isn't a table",
+        "the patch has 4 problem(s), so nothing was written",
+    ] {
+        assert!(stderr.contains(expected), "no {expected:?} in:\n{stderr}");
+    }
+    assert!(!output_path.exists(), "the bad patch was written");
+    // checking it finds the same problems
+    let stderr =
+        run_failing(&["apply-patch", patch_path, sample_path, "--check"]);
+    assert!(stderr.contains("the patch has 4 problem(s)"), "{stderr}");
+}
+
+/// Changes two of the walking model's fields, and diffs the player physics
+/// before and after. The differences are whole 16.16 steps, per tick or per
+/// second.
+#[test]
+fn diff_player_physics_change() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let sample_path = sample_path.to_str().unwrap();
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let patch_path = dir.join("player_physics_patch.json");
+    let output_path = dir.join("player_physics.phyA");
+    let output_path = output_path.to_str().unwrap();
+    let patch = serde_json::json!({
+        "physics": {
+            "walking": {
+                "maximum_forward_velocity": 0.1,
+                "acceleration": 0.005,
+            },
+        },
+    });
+    fs::write(&patch_path, patch.to_string()).unwrap();
+    let patch_path = patch_path.to_str().unwrap();
+    run(&[
+        "apply-patch",
+        patch_path,
+        sample_path,
+        "--output",
+        output_path,
+    ]);
+    let diff = ["diff-player-physics", sample_path, output_path];
+    assert_eq!(
+        run(&diff),
+        "walking.maximum_forward_velocity: 1.998046875 -> 0.100006103515625 \
+         (-1.898040771484375)\n\
+         walking.acceleration: 511.0039367675781 -> 0.0050048828125 \
+         (-510.9989318847656)\n"
+    );
+    assert_eq!(
+        run(&[&diff[..], &["--tick-rate"]].concat()),
+        "walking.maximum_forward_velocity: 59.94140625 -> 3.00018310546875 \
+         (-56.94122314453125)\n\
+         walking.acceleration: 459903.5430908203 -> 4.50439453125 \
+         (-459899.03869628906)\n"
+    );
+    // a difference that isn't a whole number of steps is rounded to one
+    let change = physics_eater::PlayerPhysicsChange {
+        field: "walking.radius".to_string(),
+        old: 0.1,
+        new: 0.3,
+        step: 1.0 / 65536.0,
+    };
+    assert_eq!(change.new - change.old, 0.19999999999999998);
+    assert_eq!(change.delta(), 13107.0 / 65536.0);
+}
+
+/// Fixing a checksum copies the WAD byte for byte, so a wrapped one is
+/// refused rather than silently unwrapped.
+#[test]
+fn fix_checksum_copies_byte_for_byte() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let output_path = dir.join("fixed.phyA");
+    let output = output_path.to_str().unwrap();
+    let stdout =
+        run(&["fix-checksum", sample_path.to_str().unwrap(), "-o", output]);
+    assert!(stdout.starts_with("checksum "), "{stdout}");
+    let fixed = fs::read(&output_path).unwrap();
+    let mut corrupted = fixed.clone();
+    corrupted[68] ^= 0xFF; // the checksum
+    let corrupted_path = dir.join("corrupted.phyA");
+    fs::write(&corrupted_path, &corrupted).unwrap();
+    let stdout = run(&[
+        "fix-checksum",
+        corrupted_path.to_str().unwrap(),
+        "-o",
+        output,
+    ]);
+    assert!(stdout.starts_with("checksum changed from"), "{stdout}");
+    assert_eq!(fs::read(&output_path).unwrap(), fixed);
+    // only the magic number matters
+    let gzipped_path = dir.join("gzipped.phyA.gz");
+    fs::write(&gzipped_path, [&[0x1F, 0x8B][..], &fixed].concat()).unwrap();
+    let stderr = run_failing(&[
+        "fix-checksum",
+        gzipped_path.to_str().unwrap(),
+        "-o",
+        output,
+    ]);
+    assert!(stderr.contains("unwrap it before fixing"), "{stderr}");
+}
+
+/// Imports MML laid out the way a scenario's is, wrappers and all, onto the
+/// M2 sample, and checks that exactly what it gives has changed.
+#[test]
+fn import_scenario_mml() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let sample_path = sample_path.to_str().unwrap();
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let mml_path = dir.join("scenario.mml");
+    let output_path = dir.join("from_mml.phyA");
+    fs::write(
+        &mml_path,
+        r#"<marathon>
+  <stringset index="128">
+    <string index="0">Not physics</string>
+  </stringset>
+  <monsters>
+    <must_be_exterminated index="1" value="true"/>
+    <monster index="1" vitality="300" flags="3" immunities="6"
+             hit_shapes="7">
+      <melee_attack type="2" attack_shape="4"/>
+      <shrapnel_damage type="-1" scale="0.5"/>
+    </monster>
+  </monsters>
+  <weapons>
+    <weapon index="0" item_type="2">
+      <trigger index="1" rounds_per_magazine="12"/>
+    </weapon>
+  </weapons>
+  <physics>
+    <constants index="1" maximum_forward_velocity="0.125"/>
+  </physics>
+</marathon>
+"#,
+    )
+    .unwrap();
+    run(&[
+        "import-mml",
+        mml_path.to_str().unwrap(),
+        "--base",
+        sample_path,
+        "--output",
+        output_path.to_str().unwrap(),
+    ]);
+    let mut expected: Value =
+        serde_json::from_str(&run(&["convert-m2-physics", sample_path]))
+            .unwrap();
+    let imported: Value = serde_json::from_str(&run(&[
+        "convert-m2-physics",
+        output_path.to_str().unwrap(),
+    ]))
+    .unwrap();
+    let monster = &mut expected["monster_definitions"][1];
+    monster["vitality"] = 300.into();
+    let Value::Object(flags) = &mut monster["flags"] else {
+        panic!()
+    };
+    flags.remove("_reserved_bits");
+    for (name, value) in flags.iter_mut() {
+        *value = matches!(name.as_str(), "omniscient" | "flies").into();
+    }
+    monster["immunities"] = serde_json::json!([1, 2]);
+    monster["hit_sequence"] = 7.into();
+    monster["melee_attack"]["projectile_type"] = 2.into();
+    monster["melee_attack"]["attack_sequence"] = 4.into();
+    monster["shrapnel_damage"]["damage_type"] = Value::Null;
+    monster["shrapnel_damage"]["scale"] = 0.5.into();
+    let weapon = &mut expected["weapon_definitions"][0];
+    weapon["item_type"] = 2.into();
+    weapon["triggers"][1]["rounds_per_magazine"] = 12.into();
+    expected["physics"]["running"]["maximum_forward_velocity"] = 0.125.into();
+    assert!(
+        roughly_equal(&expected, &imported),
+        "expected {expected}\n     got {imported}"
+    );
+}
+
+/// Writes the M2 sample as MML and imports it back onto itself, which has to
+/// leave every byte as it was.
+#[test]
+fn mml_round_trip() {
+    let sample_path = manifest_path("benches/sample.phyA");
+    let sample_path = sample_path.to_str().unwrap();
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let mml_path = dir.join("sample.mml");
+    let output_path = dir.join("mml_round_trip.phyA");
+    let mml_path = mml_path.to_str().unwrap();
+    run(&[
+        "convert-m2-physics",
+        sample_path,
+        "--format",
+        "mml",
+        "-o",
+        mml_path,
+    ]);
+    run(&[
+        "import-mml",
+        mml_path,
+        "--base",
+        sample_path,
+        "--output",
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(fs::read(&output_path).unwrap() == fs::read(sample_path).unwrap());
+}